@@ -0,0 +1,2 @@
+drop function if exists ts_energy(integer[], timestamptz, timestamptz, text, interval);
+drop function if exists power_unit_factor(text);
@@ -0,0 +1,48 @@
+-- factor to convert power readings to kW, null for units which are not a power unit
+create or replace function power_unit_factor(unit text) returns double precision
+language sql immutable as $$
+    select case lower(unit)
+        when 'w' then 0.001
+        when 'kw' then 1.0
+        when 'mw' then 1000.0
+    end
+$$;
+
+-- energy in kWh attributed to every datapoint of the given power series
+-- rectangle: the reading times the distance to its predecessor (its successor for the first datapoint)
+-- trapezoid: the mean of the reading and its predecessor times the distance between them
+-- distances are capped at max_gap, so gaps in a series are not integrated
+-- series without a power unit are integrated as if they were in kW
+create or replace function ts_energy(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    method text default 'rectangle',
+    max_gap interval default interval '15 minutes'
+) returns table (meta_id integer, series_timestamp timestamptz, energy double precision)
+language sql stable as $$
+    with neighbours as (
+        select
+            ts.meta_id as meta_id,
+            ts.series_timestamp as series_timestamp,
+            ts.series_value * coalesce(power_unit_factor(meta.unit), 1.0) as power,
+            lag(ts.series_value * coalesce(power_unit_factor(meta.unit), 1.0)) over series as previous_power,
+            extract(epoch from ts.series_timestamp - lag(ts.series_timestamp) over series)::double precision / 3600 as previous_distance,
+            extract(epoch from lead(ts.series_timestamp) over series - ts.series_timestamp)::double precision / 3600 as next_distance
+        from ts
+            join meta on ts.meta_id = meta.id
+        where
+            ts.meta_id = any(meta_ids) and
+            ts.series_timestamp between from_timestamp and to_timestamp
+        window series as (partition by ts.meta_id order by ts.series_timestamp)
+    )
+    select
+        neighbours.meta_id,
+        neighbours.series_timestamp,
+        case method
+            when 'trapezoid'
+            then coalesce((neighbours.previous_power + neighbours.power) / 2 * least(neighbours.previous_distance, extract(epoch from max_gap)::double precision / 3600), 0)
+            else neighbours.power * least(coalesce(neighbours.previous_distance, neighbours.next_distance), extract(epoch from max_gap)::double precision / 3600)
+        end
+    from neighbours
+$$;
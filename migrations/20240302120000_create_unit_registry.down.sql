@@ -0,0 +1,11 @@
+create or replace function power_unit_factor(unit text) returns double precision
+language sql immutable as $$
+    select case lower(unit)
+        when 'w' then 0.001
+        when 'kw' then 1.0
+        when 'mw' then 1000.0
+    end
+$$;
+
+drop function if exists unit_factor(text, text);
+drop table if exists unit;
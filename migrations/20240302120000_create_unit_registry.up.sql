@@ -0,0 +1,45 @@
+-- registry of known units
+-- factor converts a value into the base unit of its dimension
+-- base units: power kW, energy kWh, price EUR/kWh, emission_factor kgco2eq/kWh, emissions kgco2eq, currency EUR
+create table if not exists unit (
+    id serial primary key,
+    name text collate "case_insensitive" unique not null,
+    dimension text not null check (dimension in ('power', 'energy', 'price', 'emission_factor', 'emissions', 'currency')),
+    factor double precision not null check (factor > 0),
+    created_at timestamptz not null default now(),
+    updated_at timestamptz not null default now()
+);
+select trigger_updated_at('unit');
+
+insert into unit (name, dimension, factor) values ('W', 'power', 0.001),
+                                                  ('kW', 'power', 1.0),
+                                                  ('MW', 'power', 1000.0),
+                                                  ('GW', 'power', 1000000.0),
+                                                  ('Wh', 'energy', 0.001),
+                                                  ('kWh', 'energy', 1.0),
+                                                  ('MWh', 'energy', 1000.0),
+                                                  ('GWh', 'energy', 1000000.0),
+                                                  ('EUR/kWh', 'price', 1.0),
+                                                  ('EUR/MWh', 'price', 0.001),
+                                                  ('ct/kWh', 'price', 0.01),
+                                                  ('gco2eq/kWh', 'emission_factor', 0.001),
+                                                  ('kgco2eq/kWh', 'emission_factor', 1.0),
+                                                  ('kgco2eq/MWh', 'emission_factor', 0.001),
+                                                  ('tco2eq/MWh', 'emission_factor', 1.0),
+                                                  ('gco2eq', 'emissions', 0.001),
+                                                  ('kgco2eq', 'emissions', 1.0),
+                                                  ('tco2eq', 'emissions', 1000.0),
+                                                  ('EUR', 'currency', 1.0),
+                                                  ('ct', 'currency', 0.01);
+
+-- factor to convert a value into the base unit of dimension, null if the unit is unknown or of another dimension
+create or replace function unit_factor(unit text, dimension text) returns double precision
+language sql stable as $$
+    select unit.factor from unit where unit.name = $1 and unit.dimension = $2
+$$;
+
+-- power units are now taken from the registry
+create or replace function power_unit_factor(unit text) returns double precision
+language sql stable as $$
+    select unit_factor(unit, 'power')
+$$;
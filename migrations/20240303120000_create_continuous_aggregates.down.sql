@@ -0,0 +1,4 @@
+drop function if exists ts_energy_rollup(integer[], timestamptz, timestamptz, interval, text);
+drop materialized view if exists ts_1d;
+drop materialized view if exists ts_1h;
+drop materialized view if exists ts_15min;
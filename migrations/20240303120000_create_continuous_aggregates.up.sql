@@ -0,0 +1,94 @@
+-- rollups of every series at 15 minutes, 1 hour and 1 day, see `Rollup` for which queries read them
+-- real time aggregation is enabled, so buckets which are not materialized yet are computed from ts
+-- energy is the mean reading times the bucket length, which matches ts_energy for series with
+-- a reading at least every 15 minutes, unit factors are applied when reading the rollups
+create materialized view ts_15min
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+    ts.meta_id,
+    time_bucket(interval '15 minutes', ts.series_timestamp) as bucket,
+    sum(ts.series_value) as sum_value,
+    count(ts.series_value) as count_value,
+    min(ts.series_value) as min_value,
+    max(ts.series_value) as max_value,
+    avg(ts.series_value) * 0.25 as energy
+from ts
+group by ts.meta_id, time_bucket(interval '15 minutes', ts.series_timestamp)
+with no data;
+
+create materialized view ts_1h
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+    ts_15min.meta_id,
+    time_bucket(interval '1 hour', ts_15min.bucket) as bucket,
+    sum(ts_15min.sum_value) as sum_value,
+    sum(ts_15min.count_value)::bigint as count_value,
+    min(ts_15min.min_value) as min_value,
+    max(ts_15min.max_value) as max_value,
+    sum(ts_15min.energy) as energy
+from ts_15min
+group by ts_15min.meta_id, time_bucket(interval '1 hour', ts_15min.bucket)
+with no data;
+
+create materialized view ts_1d
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+    ts_1h.meta_id,
+    time_bucket(interval '1 day', ts_1h.bucket) as bucket,
+    sum(ts_1h.sum_value) as sum_value,
+    sum(ts_1h.count_value)::bigint as count_value,
+    min(ts_1h.min_value) as min_value,
+    max(ts_1h.max_value) as max_value,
+    sum(ts_1h.energy) as energy
+from ts_1h
+group by ts_1h.meta_id, time_bucket(interval '1 day', ts_1h.bucket)
+with no data;
+
+-- without a start offset every invalidated bucket is refreshed, so imports of old data are picked up as well
+select add_continuous_aggregate_policy('ts_15min',
+    start_offset => null,
+    end_offset => interval '15 minutes',
+    schedule_interval => interval '15 minutes');
+select add_continuous_aggregate_policy('ts_1h',
+    start_offset => null,
+    end_offset => interval '1 hour',
+    schedule_interval => interval '1 hour');
+select add_continuous_aggregate_policy('ts_1d',
+    start_offset => null,
+    end_offset => interval '1 day',
+    schedule_interval => interval '1 day');
+
+-- energy in kWh of the given power series per rollup bucket
+-- without a rollup this is ts_energy, which integrates every datapoint
+create or replace function ts_energy_rollup(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    rollup interval default null,
+    method text default 'rectangle'
+) returns table (meta_id integer, series_timestamp timestamptz, energy double precision)
+language plpgsql stable as $$
+declare
+    rollup_view text;
+begin
+    if rollup is null then
+        return query select * from ts_energy(meta_ids, from_timestamp, to_timestamp, method);
+        return;
+    end if;
+    rollup_view := case rollup
+        when interval '15 minutes' then 'ts_15min'
+        when interval '1 hour' then 'ts_1h'
+        when interval '1 day' then 'ts_1d'
+    end;
+    if rollup_view is null then
+        raise exception 'no rollup for interval %', rollup;
+    end if;
+    return query execute format(
+        'select r.meta_id, r.bucket, r.energy * coalesce(power_unit_factor(meta.unit), 1.0)
+        from %I r
+            join meta on r.meta_id = meta.id
+        where r.meta_id = any($1) and r.bucket between $2 and $3',
+        rollup_view
+    ) using meta_ids, from_timestamp, to_timestamp;
+end
+$$;
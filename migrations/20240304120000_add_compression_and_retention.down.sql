@@ -0,0 +1,8 @@
+select delete_job(job_id) from timescaledb_information.jobs where proc_name = 'series_retention_job';
+drop procedure if exists series_retention_job(integer, jsonb);
+drop function if exists apply_series_retention();
+alter table meta drop column if exists retention;
+
+select remove_compression_policy('ts', if_exists => true);
+select decompress_chunk(chunk, if_compressed => true) from show_chunks('ts') chunk;
+alter table ts set (timescaledb.compress = false);
@@ -0,0 +1,36 @@
+-- native compression of ts, datapoints of a series are stored together ordered by time
+-- id is part of the primary key, so it has to be an order by column as well
+alter table ts set (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'meta_id',
+    timescaledb.compress_orderby = 'series_timestamp, id'
+);
+-- can be changed with PUT /v1/admin/compression/
+select add_compression_policy('ts', compress_after => interval '30 days');
+
+-- retention per series, datapoints older than now() - retention are deleted
+-- chunks hold all series, so retention cannot be left to drop_chunks
+alter table meta add column if not exists retention interval check (retention > interval '0');
+
+create or replace function apply_series_retention() returns bigint
+language sql as $$
+    with deleted as (
+        delete from ts
+        using meta
+        where
+            ts.meta_id = meta.id and
+            meta.retention is not null and
+            ts.series_timestamp < now() - meta.retention
+        returning 1
+    )
+    select count(*) from deleted
+$$;
+
+create or replace procedure series_retention_job(job_id integer, config jsonb)
+language plpgsql as $$
+begin
+    perform apply_series_retention();
+end
+$$;
+
+select add_job('series_retention_job', interval '1 hour');
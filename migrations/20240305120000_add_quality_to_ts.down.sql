@@ -0,0 +1,80 @@
+drop function ts_energy_rollup(integer[], timestamptz, timestamptz, interval, text, boolean);
+drop function ts_energy(integer[], timestamptz, timestamptz, text, interval, boolean);
+
+-- energy in kWh attributed to every datapoint of the given power series
+-- rectangle: the reading times the distance to its predecessor (its successor for the first datapoint)
+-- trapezoid: the mean of the reading and its predecessor times the distance between them
+-- distances are capped at max_gap, so gaps in a series are not integrated
+-- series without a power unit are integrated as if they were in kW
+create function ts_energy(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    method text default 'rectangle',
+    max_gap interval default interval '15 minutes'
+) returns table (meta_id integer, series_timestamp timestamptz, energy double precision)
+language sql stable as $$
+    with neighbours as (
+        select
+            ts.meta_id as meta_id,
+            ts.series_timestamp as series_timestamp,
+            ts.series_value * coalesce(power_unit_factor(meta.unit), 1.0) as power,
+            lag(ts.series_value * coalesce(power_unit_factor(meta.unit), 1.0)) over series as previous_power,
+            extract(epoch from ts.series_timestamp - lag(ts.series_timestamp) over series)::double precision / 3600 as previous_distance,
+            extract(epoch from lead(ts.series_timestamp) over series - ts.series_timestamp)::double precision / 3600 as next_distance
+        from ts
+            join meta on ts.meta_id = meta.id
+        where
+            ts.meta_id = any(meta_ids) and
+            ts.series_timestamp between from_timestamp and to_timestamp
+        window series as (partition by ts.meta_id order by ts.series_timestamp)
+    )
+    select
+        neighbours.meta_id,
+        neighbours.series_timestamp,
+        case method
+            when 'trapezoid'
+            then coalesce((neighbours.previous_power + neighbours.power) / 2 * least(neighbours.previous_distance, extract(epoch from max_gap)::double precision / 3600), 0)
+            else neighbours.power * least(coalesce(neighbours.previous_distance, neighbours.next_distance), extract(epoch from max_gap)::double precision / 3600)
+        end
+    from neighbours
+$$;
+
+-- energy in kWh of the given power series per rollup bucket
+-- without a rollup this is ts_energy, which integrates every datapoint
+create function ts_energy_rollup(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    rollup interval default null,
+    method text default 'rectangle'
+) returns table (meta_id integer, series_timestamp timestamptz, energy double precision)
+language plpgsql stable as $$
+declare
+    rollup_view text;
+begin
+    if rollup is null then
+        return query select * from ts_energy(meta_ids, from_timestamp, to_timestamp, method);
+        return;
+    end if;
+    rollup_view := case rollup
+        when interval '15 minutes' then 'ts_15min'
+        when interval '1 hour' then 'ts_1h'
+        when interval '1 day' then 'ts_1d'
+    end;
+    if rollup_view is null then
+        raise exception 'no rollup for interval %', rollup;
+    end if;
+    return query execute format(
+        'select r.meta_id, r.bucket, r.energy * coalesce(power_unit_factor(meta.unit), 1.0)
+        from %I r
+            join meta on r.meta_id = meta.id
+        where r.meta_id = any($1) and r.bucket between $2 and $3',
+        rollup_view
+    ) using meta_ids, from_timestamp, to_timestamp;
+end
+$$;
+
+alter table ts drop column quality;
+
+drop type datapoint_quality;
@@ -0,0 +1,175 @@
+-- previous values of overwritten and deleted datapoints
+-- a revision was the stored value from valid_from until valid_to
+create table ts_revision (
+    id bigserial primary key,
+    ts_id bigint not null,
+    meta_id integer references meta(id) on delete cascade,
+    series_timestamp timestamptz not null,
+    series_value double precision not null,
+    quality datapoint_quality not null,
+    created_at timestamptz not null,
+    valid_from timestamptz not null,
+    valid_to timestamptz not null default now(),
+    operation text not null check (operation in ('update', 'delete'))
+);
+create index idx_ts_revision_meta_id_series_timestamp on ts_revision(meta_id, series_timestamp);
+
+-- valid_from is the time the value was written, updated_at is set on every update of ts
+create function ts_keep_revision() returns trigger
+language plpgsql as $$
+begin
+    if current_setting('ts_revision.skip', true) = 'on' then
+        return null;
+    end if;
+    insert into ts_revision (ts_id, meta_id, series_timestamp, series_value, quality, created_at, valid_from, operation)
+    values (old.id, old.meta_id, old.series_timestamp, old.series_value, old.quality, old.created_at, old.updated_at, lower(tg_op));
+    return null;
+end
+$$;
+
+create trigger ts_keep_revision
+    after update or delete on ts
+    for each row execute function ts_keep_revision();
+
+-- datapoints of the given series as they were stored at as_of, the current ones if as_of is null
+-- meta_ids null selects all series
+create function ts_as_of(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    as_of timestamptz default null
+) returns table (
+    id bigint,
+    meta_id integer,
+    series_timestamp timestamptz,
+    series_value double precision,
+    quality datapoint_quality,
+    created_at timestamptz,
+    updated_at timestamptz
+)
+language sql stable as $$
+    select ts.id, ts.meta_id, ts.series_timestamp, ts.series_value, ts.quality, ts.created_at, ts.updated_at
+    from ts
+    where
+        (meta_ids is null or ts.meta_id = any(meta_ids)) and
+        ts.series_timestamp between from_timestamp and to_timestamp and
+        (as_of is null or ts.updated_at <= as_of)
+    union all
+    select r.ts_id, r.meta_id, r.series_timestamp, r.series_value, r.quality, r.created_at, r.valid_from
+    from ts_revision r
+    where
+        as_of is not null and
+        (meta_ids is null or r.meta_id = any(meta_ids)) and
+        r.series_timestamp between from_timestamp and to_timestamp and
+        r.valid_from <= as_of and
+        r.valid_to > as_of
+$$;
+
+-- datapoints dropped by retention are not revisions, their history is dropped as well
+create or replace function apply_series_retention() returns bigint
+language plpgsql as $$
+declare
+    deleted bigint;
+begin
+    perform set_config('ts_revision.skip', 'on', true);
+    delete from ts
+    using meta
+    where
+        ts.meta_id = meta.id and
+        meta.retention is not null and
+        ts.series_timestamp < now() - meta.retention;
+    get diagnostics deleted = row_count;
+    perform set_config('ts_revision.skip', 'off', true);
+
+    delete from ts_revision
+    using meta
+    where
+        ts_revision.meta_id = meta.id and
+        meta.retention is not null and
+        ts_revision.series_timestamp < now() - meta.retention;
+    return deleted;
+end
+$$;
+
+drop function ts_energy_rollup(integer[], timestamptz, timestamptz, interval, text, boolean);
+drop function ts_energy(integer[], timestamptz, timestamptz, text, interval, boolean);
+
+-- energy in kWh attributed to every datapoint of the given power series
+-- rectangle: the reading times the distance to its predecessor (its successor for the first datapoint)
+-- trapezoid: the mean of the reading and its predecessor times the distance between them
+-- distances are capped at max_gap, so gaps in a series are not integrated
+-- series without a power unit are integrated as if they were in kW
+-- with exclude_flagged only measured datapoints are integrated, the time covered by flagged ones is treated as a gap
+-- with as_of the datapoints are integrated as they were stored at that time, see ts_as_of
+create function ts_energy(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    method text default 'rectangle',
+    max_gap interval default interval '15 minutes',
+    exclude_flagged boolean default false,
+    as_of timestamptz default null
+) returns table (meta_id integer, series_timestamp timestamptz, energy double precision)
+language sql stable as $$
+    with neighbours as (
+        select
+            ts.meta_id as meta_id,
+            ts.series_timestamp as series_timestamp,
+            ts.series_value * coalesce(power_unit_factor(meta.unit), 1.0) as power,
+            lag(ts.series_value * coalesce(power_unit_factor(meta.unit), 1.0)) over series as previous_power,
+            extract(epoch from ts.series_timestamp - lag(ts.series_timestamp) over series)::double precision / 3600 as previous_distance,
+            extract(epoch from lead(ts.series_timestamp) over series - ts.series_timestamp)::double precision / 3600 as next_distance
+        from ts_as_of(meta_ids, from_timestamp, to_timestamp, as_of) ts
+            join meta on ts.meta_id = meta.id
+        where
+            not exclude_flagged or ts.quality = 'measured'
+        window series as (partition by ts.meta_id order by ts.series_timestamp)
+    )
+    select
+        neighbours.meta_id,
+        neighbours.series_timestamp,
+        case method
+            when 'trapezoid'
+            then coalesce((neighbours.previous_power + neighbours.power) / 2 * least(neighbours.previous_distance, extract(epoch from max_gap)::double precision / 3600), 0)
+            else neighbours.power * least(coalesce(neighbours.previous_distance, neighbours.next_distance), extract(epoch from max_gap)::double precision / 3600)
+        end
+    from neighbours
+$$;
+
+-- energy in kWh of the given power series per rollup bucket
+-- without a rollup this is ts_energy, which integrates every datapoint
+-- rollups know neither the quality nor the revisions of datapoints, so exclude_flagged and as_of bypass them
+create function ts_energy_rollup(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    rollup interval default null,
+    method text default 'rectangle',
+    exclude_flagged boolean default false,
+    as_of timestamptz default null
+) returns table (meta_id integer, series_timestamp timestamptz, energy double precision)
+language plpgsql stable as $$
+declare
+    rollup_view text;
+begin
+    if rollup is null or exclude_flagged or as_of is not null then
+        return query select * from ts_energy(meta_ids, from_timestamp, to_timestamp, method, interval '15 minutes', exclude_flagged, as_of);
+        return;
+    end if;
+    rollup_view := case rollup
+        when interval '15 minutes' then 'ts_15min'
+        when interval '1 hour' then 'ts_1h'
+        when interval '1 day' then 'ts_1d'
+    end;
+    if rollup_view is null then
+        raise exception 'no rollup for interval %', rollup;
+    end if;
+    return query execute format(
+        'select r.meta_id, r.bucket, r.energy * coalesce(power_unit_factor(meta.unit), 1.0)
+        from %I r
+            join meta on r.meta_id = meta.id
+        where r.meta_id = any($1) and r.bucket between $2 and $3',
+        rollup_view
+    ) using meta_ids, from_timestamp, to_timestamp;
+end
+$$;
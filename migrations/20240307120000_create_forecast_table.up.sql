@@ -0,0 +1,12 @@
+-- forecasts of a series, every target timestamp has one value per issue time
+create table forecast (
+    id bigserial primary key,
+    meta_id integer not null references meta(id) on delete cascade,
+    issued_at timestamptz not null,
+    target_timestamp timestamptz not null,
+    value double precision not null,
+    created_at timestamptz not null default now(),
+    unique (meta_id, issued_at, target_timestamp)
+);
+-- the latest forecast of a target is the first one in this index
+create index idx_forecast_meta_id_target_timestamp on forecast(meta_id, target_timestamp, issued_at desc);
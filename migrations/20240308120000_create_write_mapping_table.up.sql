@@ -0,0 +1,13 @@
+-- mappings of line protocol measurements, tags and fields to meta identifiers, see /v1/write
+-- identifier may reference {measurement}, {field} and tags like {device}
+create table write_mapping (
+    id serial primary key,
+    measurement text not null,
+    field text,
+    tags jsonb not null default '{}',
+    identifier text not null,
+    meta_defaults jsonb,
+    created_at timestamptz not null default now(),
+    updated_at timestamptz not null default now()
+);
+select trigger_updated_at('write_mapping');
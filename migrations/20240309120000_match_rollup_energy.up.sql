@@ -0,0 +1,156 @@
+-- energy of the rollups used to be the mean reading times the bucket length, which differs from ts_energy
+-- for irregular series, now the 15 minute rollup holds what ts_energy needs to integrate its buckets
+-- continuous aggregates can't be altered, so all of them are recreated and materialized again by their policies
+drop materialized view if exists ts_1d;
+drop materialized view if exists ts_1h;
+drop materialized view if exists ts_15min;
+
+-- rectangle energy in kWh of ordered datapoints, without the first one, which depends on its predecessor
+-- the state holds the epoch of the previous datapoint and the energy so far
+create function rectangle_energy_step(
+    state double precision[],
+    series_timestamp timestamptz,
+    series_value double precision
+) returns double precision[]
+language sql immutable as $$
+    select array[
+        extract(epoch from series_timestamp)::double precision,
+        coalesce(state[2], 0) + coalesce(series_value * (extract(epoch from series_timestamp)::double precision - state[1]) / 3600, 0)
+    ]
+$$;
+
+create function rectangle_energy_final(state double precision[]) returns double precision
+language sql immutable as $$
+    select state[2]
+$$;
+
+create aggregate rectangle_energy(timestamptz, double precision) (
+    sfunc = rectangle_energy_step,
+    stype = double precision[],
+    finalfunc = rectangle_energy_final
+);
+
+-- rollups of every series at 15 minutes, 1 hour and 1 day, see `Rollup` for which queries read them
+-- real time aggregation is enabled, so buckets which are not materialized yet are computed from ts
+-- energy is only held by the 15 minute rollup, datapoints within a bucket are closer than the max_gap
+-- of ts_energy, so only the first datapoint of a bucket needs its predecessor, see ts_energy_rollup
+create materialized view ts_15min
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+    ts.meta_id,
+    time_bucket(interval '15 minutes', ts.series_timestamp) as bucket,
+    sum(ts.series_value) as sum_value,
+    count(ts.series_value) as count_value,
+    min(ts.series_value) as min_value,
+    max(ts.series_value) as max_value,
+    min(ts.series_timestamp) as first_timestamp,
+    max(ts.series_timestamp) as last_timestamp,
+    first(ts.series_value, ts.series_timestamp) as first_value,
+    rectangle_energy(ts.series_timestamp, ts.series_value order by ts.series_timestamp) as energy
+from ts
+group by ts.meta_id, time_bucket(interval '15 minutes', ts.series_timestamp)
+with no data;
+
+create materialized view ts_1h
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+    ts_15min.meta_id,
+    time_bucket(interval '1 hour', ts_15min.bucket) as bucket,
+    sum(ts_15min.sum_value) as sum_value,
+    sum(ts_15min.count_value)::bigint as count_value,
+    min(ts_15min.min_value) as min_value,
+    max(ts_15min.max_value) as max_value
+from ts_15min
+group by ts_15min.meta_id, time_bucket(interval '1 hour', ts_15min.bucket)
+with no data;
+
+create materialized view ts_1d
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+    ts_1h.meta_id,
+    time_bucket(interval '1 day', ts_1h.bucket) as bucket,
+    sum(ts_1h.sum_value) as sum_value,
+    sum(ts_1h.count_value)::bigint as count_value,
+    min(ts_1h.min_value) as min_value,
+    max(ts_1h.max_value) as max_value
+from ts_1h
+group by ts_1h.meta_id, time_bucket(interval '1 day', ts_1h.bucket)
+with no data;
+
+select add_continuous_aggregate_policy('ts_15min',
+    start_offset => null,
+    end_offset => interval '15 minutes',
+    schedule_interval => interval '15 minutes');
+select add_continuous_aggregate_policy('ts_1h',
+    start_offset => null,
+    end_offset => interval '1 hour',
+    schedule_interval => interval '1 hour');
+select add_continuous_aggregate_policy('ts_1d',
+    start_offset => null,
+    end_offset => interval '1 day',
+    schedule_interval => interval '1 day');
+
+drop function ts_energy_rollup(integer[], timestamptz, timestamptz, interval, text, boolean, timestamptz);
+
+-- energy in kWh of the given power series, equal to ts_energy with the rectangle method
+-- with a rollup the complete 15 minute buckets within the range are read from ts_15min and attributed to
+-- the start of their bucket, the datapoints before the first and after the last of them are read from ts
+-- rollups know neither the quality nor the revisions of datapoints, so exclude_flagged and as_of bypass them
+create function ts_energy_rollup(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    rollup interval default null,
+    method text default 'rectangle',
+    exclude_flagged boolean default false,
+    as_of timestamptz default null
+) returns table (meta_id integer, series_timestamp timestamptz, energy double precision)
+language plpgsql stable as $$
+declare
+    max_gap interval := interval '15 minutes';
+    interior_from timestamptz := time_bucket(interval '15 minutes', from_timestamp - interval '1 microsecond') + interval '15 minutes';
+    interior_to timestamptz := time_bucket(interval '15 minutes', to_timestamp + interval '1 microsecond');
+begin
+    if rollup is null or method <> 'rectangle' or exclude_flagged or as_of is not null or interior_from >= interior_to then
+        return query select * from ts_energy(meta_ids, from_timestamp, to_timestamp, method, max_gap, exclude_flagged, as_of);
+        return;
+    end if;
+    return query
+    with segments as (
+        select r.meta_id, r.bucket, r.first_timestamp, r.first_value, r.last_timestamp, r.energy
+        from ts_15min r
+        where r.meta_id = any(meta_ids) and r.bucket >= interior_from and r.bucket < interior_to
+        union all
+        select ts.meta_id, ts.series_timestamp, ts.series_timestamp, ts.series_value, ts.series_timestamp, 0
+        from ts
+        where
+            ts.meta_id = any(meta_ids) and
+            ts.series_timestamp between from_timestamp and to_timestamp and
+            (ts.series_timestamp < interior_from or ts.series_timestamp >= interior_to)
+    ),
+    neighbours as (
+        select
+            segments.*,
+            extract(epoch from segments.first_timestamp - lag(segments.last_timestamp) over series)::double precision / 3600 as previous_distance
+        from segments
+        window series as (partition by segments.meta_id order by segments.bucket)
+    )
+    select
+        neighbours.meta_id,
+        neighbours.bucket,
+        (neighbours.energy + neighbours.first_value * least(
+            -- like ts_energy, the first datapoint of the range is integrated up to its successor
+            coalesce(neighbours.previous_distance, (
+                select extract(epoch from min(ts.series_timestamp) - neighbours.first_timestamp)::double precision / 3600
+                from ts
+                where
+                    ts.meta_id = neighbours.meta_id and
+                    ts.series_timestamp > neighbours.first_timestamp and
+                    ts.series_timestamp <= to_timestamp
+            )),
+            extract(epoch from max_gap)::double precision / 3600
+        )) * coalesce(power_unit_factor(meta.unit), 1.0)
+    from neighbours
+        join meta on neighbours.meta_id = meta.id;
+end
+$$;
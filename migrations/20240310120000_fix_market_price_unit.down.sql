@@ -0,0 +1 @@
+-- the previous unit of the market price series is not known anymore, it is kept as EUR/MWh
@@ -0,0 +1,7 @@
+-- SMARD publishes market prices in EUR/MWh, series registered before the unit registry may carry
+-- another unit like MWh, which the cost kpis reject instead of guessing the price unit
+update meta
+set unit = 'EUR/MWh'
+where
+    meta.identifier = 'smard_market_price' and
+    unit_factor(meta.unit, 'price') is null;
@@ -0,0 +1,170 @@
+-- the hourly and daily rollups hold the energy of their buckets as well, so ts_energy_rollup can read
+-- the rollup the query is bucketed by instead of every 15 minute bucket
+-- continuous aggregates can't be altered, so both are recreated and materialized again by their policies
+drop materialized view if exists ts_1d;
+drop materialized view if exists ts_1h;
+
+-- rectangle energy in kWh of consecutive buckets ordered by time, each given by its first datapoint,
+-- its last datapoint and the energy of its datapoints without the first one
+-- the first datapoint of every bucket but the first is integrated up to the last datapoint of the
+-- previous bucket, capped at the max_gap of ts_energy_rollup
+-- the state holds the epoch of the last datapoint of the previous bucket and the energy so far
+create function rectangle_energy_merge_step(
+    state double precision[],
+    first_timestamp timestamptz,
+    first_value double precision,
+    last_timestamp timestamptz,
+    energy double precision
+) returns double precision[]
+language sql immutable as $$
+    select array[
+        extract(epoch from last_timestamp)::double precision,
+        coalesce(state[2], 0) + energy + case
+            when state is null then 0
+            else first_value * least(extract(epoch from first_timestamp)::double precision - state[1], 900) / 3600
+        end
+    ]
+$$;
+
+create aggregate rectangle_energy_merge(timestamptz, double precision, timestamptz, double precision) (
+    sfunc = rectangle_energy_merge_step,
+    stype = double precision[],
+    finalfunc = rectangle_energy_final
+);
+
+create materialized view ts_1h
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+    ts_15min.meta_id,
+    time_bucket(interval '1 hour', ts_15min.bucket) as bucket,
+    sum(ts_15min.sum_value) as sum_value,
+    sum(ts_15min.count_value)::bigint as count_value,
+    min(ts_15min.min_value) as min_value,
+    max(ts_15min.max_value) as max_value,
+    min(ts_15min.first_timestamp) as first_timestamp,
+    max(ts_15min.last_timestamp) as last_timestamp,
+    first(ts_15min.first_value, ts_15min.first_timestamp) as first_value,
+    rectangle_energy_merge(
+        ts_15min.first_timestamp,
+        ts_15min.first_value,
+        ts_15min.last_timestamp,
+        ts_15min.energy
+        order by ts_15min.bucket
+    ) as energy
+from ts_15min
+group by ts_15min.meta_id, time_bucket(interval '1 hour', ts_15min.bucket)
+with no data;
+
+create materialized view ts_1d
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+    ts_1h.meta_id,
+    time_bucket(interval '1 day', ts_1h.bucket) as bucket,
+    sum(ts_1h.sum_value) as sum_value,
+    sum(ts_1h.count_value)::bigint as count_value,
+    min(ts_1h.min_value) as min_value,
+    max(ts_1h.max_value) as max_value,
+    min(ts_1h.first_timestamp) as first_timestamp,
+    max(ts_1h.last_timestamp) as last_timestamp,
+    first(ts_1h.first_value, ts_1h.first_timestamp) as first_value,
+    rectangle_energy_merge(
+        ts_1h.first_timestamp,
+        ts_1h.first_value,
+        ts_1h.last_timestamp,
+        ts_1h.energy
+        order by ts_1h.bucket
+    ) as energy
+from ts_1h
+group by ts_1h.meta_id, time_bucket(interval '1 day', ts_1h.bucket)
+with no data;
+
+select add_continuous_aggregate_policy('ts_1h',
+    start_offset => null,
+    end_offset => interval '1 hour',
+    schedule_interval => interval '1 hour');
+select add_continuous_aggregate_policy('ts_1d',
+    start_offset => null,
+    end_offset => interval '1 day',
+    schedule_interval => interval '1 day');
+
+drop function ts_energy_rollup(integer[], timestamptz, timestamptz, interval, text, boolean, timestamptz);
+
+-- energy in kWh of the given power series, equal to ts_energy with the rectangle method
+-- with a rollup the complete buckets of the rollup within the range are read from its view and attributed
+-- to the start of their bucket, the datapoints before the first and after the last of them are read from ts
+-- rollups know neither the quality nor the revisions of datapoints, so exclude_flagged and as_of bypass them
+create function ts_energy_rollup(
+    meta_ids integer[],
+    from_timestamp timestamptz,
+    to_timestamp timestamptz,
+    rollup interval default null,
+    method text default 'rectangle',
+    exclude_flagged boolean default false,
+    as_of timestamptz default null
+) returns table (meta_id integer, series_timestamp timestamptz, energy double precision)
+language plpgsql stable as $$
+declare
+    max_gap interval := interval '15 minutes';
+    rollup_view text;
+    interior_from timestamptz;
+    interior_to timestamptz;
+begin
+    if rollup is null or method <> 'rectangle' or exclude_flagged or as_of is not null then
+        return query select * from ts_energy(meta_ids, from_timestamp, to_timestamp, method, max_gap, exclude_flagged, as_of);
+        return;
+    end if;
+    rollup_view := case rollup
+        when interval '15 minutes' then 'ts_15min'
+        when interval '1 hour' then 'ts_1h'
+        when interval '1 day' then 'ts_1d'
+    end;
+    if rollup_view is null then
+        raise exception 'no rollup for interval %', rollup;
+    end if;
+    interior_from := time_bucket(rollup, from_timestamp - interval '1 microsecond') + rollup;
+    interior_to := time_bucket(rollup, to_timestamp + interval '1 microsecond');
+    if interior_from >= interior_to then
+        return query select * from ts_energy(meta_ids, from_timestamp, to_timestamp, method, max_gap);
+        return;
+    end if;
+    return query execute format(
+        'with segments as (
+            select r.meta_id, r.bucket, r.first_timestamp, r.first_value, r.last_timestamp, r.energy
+            from %I r
+            where r.meta_id = any($1) and r.bucket >= $4 and r.bucket < $5
+            union all
+            select ts.meta_id, ts.series_timestamp, ts.series_timestamp, ts.series_value, ts.series_timestamp, 0
+            from ts
+            where
+                ts.meta_id = any($1) and
+                ts.series_timestamp between $2 and $3 and
+                (ts.series_timestamp < $4 or ts.series_timestamp >= $5)
+        ),
+        neighbours as (
+            select
+                segments.*,
+                extract(epoch from segments.first_timestamp - lag(segments.last_timestamp) over series)::double precision / 3600 as previous_distance
+            from segments
+            window series as (partition by segments.meta_id order by segments.bucket)
+        )
+        select
+            neighbours.meta_id,
+            neighbours.bucket,
+            (neighbours.energy + neighbours.first_value * least(
+                -- like ts_energy, the first datapoint of the range is integrated up to its successor
+                coalesce(neighbours.previous_distance, (
+                    select extract(epoch from min(ts.series_timestamp) - neighbours.first_timestamp)::double precision / 3600
+                    from ts
+                    where
+                        ts.meta_id = neighbours.meta_id and
+                        ts.series_timestamp > neighbours.first_timestamp and
+                        ts.series_timestamp <= $3
+                )),
+                extract(epoch from $6)::double precision / 3600
+            )) * coalesce(power_unit_factor(meta.unit), 1.0)
+        from neighbours
+            join meta on neighbours.meta_id = meta.id',
+        rollup_view
+    ) using meta_ids, from_timestamp, to_timestamp, interior_from, interior_to, max_gap;
+end
+$$;
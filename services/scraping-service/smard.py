@@ -24,7 +24,7 @@ class SmardScraper(Scraper):
     BASE_URL = "https://www.smard.de/app/chart_data/"
     TIMESTAMPS_URL = BASE_URL + "{filter_value}/{region}/index_{resolution}.json"
     TIMESERIES_URL = BASE_URL + "{filter_value}/{region}/{filter_value}_{region}_{resolution}_{timestamp}.json"
-    SMARD_UNIT = "eur/mwh"
+    SMARD_UNIT = "EUR/MWh"
     SMARD_IDENTIFIER = "smard_market_price"
 
     def __init__(self) -> None:
@@ -9,6 +9,7 @@ pub struct AppConfig {
     pub port: u16,
     pub run_migrations: bool,
     pub load_initial_data_path: Option<String>,
+    pub mqtt_config_path: Option<String>,
 }
 
 pub fn read_log_level() -> Level {
@@ -37,6 +38,7 @@ impl AppConfig {
             .map(|x| x.parse::<bool>().unwrap())
             .unwrap_or(false);
         let load_initial_data_path = var("LOAD_INITIAL_DATA_PATH").ok();
+        let mqtt_config_path = var("MQTT_CONFIG_PATH").ok();
         AppConfig {
             database_url,
             redis_url,
@@ -44,6 +46,7 @@ impl AppConfig {
             port,
             run_migrations,
             load_initial_data_path,
+            mqtt_config_path,
         }
     }
 }
@@ -1,5 +1,9 @@
+use crate::models::TimestampFilter;
+use axum::extract::Query;
+use axum::http::Uri;
 use redis::aio::Connection;
 use redis::{AsyncCommands, RedisResult};
+use time::OffsetDateTime;
 
 pub struct Cache {
     connection: Connection,
@@ -21,12 +25,45 @@ impl Cache {
         value: &str,
         ttl_seconds: i64,
     ) -> RedisResult<()> {
-        self.connection.set(key, value).await?;
+        self.connection.set::<_, _, ()>(key, value).await?;
         if ttl_seconds > 0 {
-            self.connection.expire(key, ttl_seconds).await?;
+            self.connection.expire::<_, ()>(key, ttl_seconds).await?;
         }
         Ok(())
     }
+    /// Remove cached kpi results whose `from`/`to` range overlaps with the given range.
+    /// Keys are the request uris, so the range is recovered from their query string.
+    /// Keys are iterated with SCAN instead of KEYS, which would block redis for the whole keyspace.
+    pub(crate) async fn invalidate_kpis(
+        &mut self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> RedisResult<usize> {
+        let mut keys: Vec<String> = vec![];
+        let mut iter = self.connection.scan_match("/v1/kpi/*").await?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        drop(iter);
+
+        let stale = keys
+            .into_iter()
+            .filter(|key| {
+                // keys whose range can't be recovered might overlap, so they are removed as well
+                key.parse::<Uri>()
+                    .ok()
+                    .and_then(|uri| Query::<TimestampFilter>::try_from_uri(&uri).ok())
+                    .map(|Query(filter)| {
+                        filter.from.is_none_or(|x| x <= to) && filter.to.is_none_or(|x| x >= from)
+                    })
+                    .unwrap_or(true)
+            })
+            .collect::<Vec<_>>();
+        if !stale.is_empty() {
+            self.connection.del::<_, ()>(&stale).await?;
+        }
+        Ok(stale.len())
+    }
 }
 
 #[tokio::test]
@@ -63,3 +100,29 @@ async fn test_cache_vec_structs() {
         .count();
     assert!(matching == data.len() && matching == deserialized.len());
 }
+
+#[tokio::test]
+async fn test_invalidate_kpis() {
+    use crate::app_config::AppConfig;
+    use time::macros::datetime;
+
+    let app_config = AppConfig::new();
+    let mut cache = Cache::new(&app_config.redis_url).await.unwrap();
+    let overlapping = "/v1/kpi/test_invalidate/?from=2019-01-01T00:00:00Z&to=2019-02-01T00:00:00Z";
+    let disjoint = "/v1/kpi/test_invalidate/?from=2020-01-01T00:00:00Z&to=2020-02-01T00:00:00Z";
+    cache.set(overlapping, "{}", 5).await.unwrap();
+    cache.set(disjoint, "{}", 5).await.unwrap();
+    let unparsable = "/v1/kpi/test_invalidate/?from=yesterday";
+    cache.set(unparsable, "{}", 5).await.unwrap();
+
+    cache
+        .invalidate_kpis(
+            datetime!(2019-01-15 0:00 UTC),
+            datetime!(2019-01-16 0:00 UTC),
+        )
+        .await
+        .unwrap();
+    assert!(cache.get(overlapping).await.is_err());
+    assert!(cache.get(disjoint).await.is_ok());
+    assert!(cache.get(unparsable).await.is_err());
+}
@@ -0,0 +1,264 @@
+use crate::error::ApiError;
+use crate::models::{Datapoint, TimeseriesMeta};
+
+use arrow::array::{Array, ArrayRef, Float64Array, StringArray, TimestampMicrosecondArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+pub const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+pub const PARQUET_CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+/// Binary formats for columnar clients like polars and pyarrow, selected by the `Accept` header.
+/// Timestamps are written in microseconds since the epoch in UTC, which is the precision of postgres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnarFormat {
+    ArrowStream,
+    Parquet,
+}
+
+impl ColumnarFormat {
+    /// The first columnar media type in the `Accept` header, None for all other clients.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|x| x.to_str().ok())
+            .flat_map(|x| x.split(','))
+            .find_map(|x| match x.split(';').next().unwrap_or_default().trim() {
+                ARROW_STREAM_CONTENT_TYPE => Some(Self::ArrowStream),
+                PARQUET_CONTENT_TYPE | "application/x-parquet" => Some(Self::Parquet),
+                _ => None,
+            })
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::ArrowStream => ARROW_STREAM_CONTENT_TYPE,
+            Self::Parquet => PARQUET_CONTENT_TYPE,
+        }
+    }
+
+    /// Encode a batch as an Arrow IPC stream or a snappy compressed Parquet file.
+    pub fn encode(self, batch: &RecordBatch) -> Result<Vec<u8>, ApiError> {
+        let mut buffer = vec![];
+        match self {
+            Self::ArrowStream => {
+                let mut writer = StreamWriter::try_new(&mut buffer, &batch.schema())?;
+                writer.write(batch)?;
+                writer.finish()?;
+            }
+            Self::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let mut writer =
+                    ArrowWriter::try_new(&mut buffer, batch.schema(), Some(properties))?;
+                writer.write(batch)?;
+                writer.close()?;
+            }
+        }
+        Ok(buffer)
+    }
+
+    pub fn response(self, batch: &RecordBatch) -> Result<Response, ApiError> {
+        let body = self.encode(batch)?;
+        Ok(([(header::CONTENT_TYPE, self.content_type())], body).into_response())
+    }
+}
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+}
+
+fn timestamp_array(timestamps: impl Iterator<Item = OffsetDateTime>) -> ArrayRef {
+    let micros = timestamps.map(|x| (x.unix_timestamp_nanos() / 1_000) as i64);
+    Arc::new(TimestampMicrosecondArray::from_iter_values(micros).with_timezone("UTC"))
+}
+
+/// Datapoints of a single series with the columns `timestamp`, `value` and `quality`.
+/// The identifier and unit of the series are kept in the schema metadata.
+pub fn datapoints_batch(
+    metadata: &TimeseriesMeta,
+    datapoints: &[Datapoint],
+) -> Result<RecordBatch, ApiError> {
+    let schema = Schema::new(vec![
+        Field::new("timestamp", timestamp_type(), false),
+        Field::new("value", DataType::Float64, false),
+        Field::new("quality", DataType::Utf8, false),
+    ])
+    .with_metadata(HashMap::from([
+        (String::from("identifier"), metadata.identifier.clone()),
+        (String::from("unit"), metadata.unit.clone()),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        timestamp_array(datapoints.iter().map(|x| x.timestamp)),
+        Arc::new(Float64Array::from_iter_values(
+            datapoints.iter().map(|x| x.value),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            datapoints.iter().map(|x| x.quality.as_str()),
+        )),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Several series in the layout of the csv export, a `time_column` and one nullable column per identifier.
+/// The unit of every series is kept in the metadata of its column.
+pub fn aligned_batch(
+    metadata: &[TimeseriesMeta],
+    time_column: &str,
+    rows: &[(OffsetDateTime, Vec<Option<f64>>)],
+) -> Result<RecordBatch, ApiError> {
+    let mut fields = vec![Field::new(time_column, timestamp_type(), false)];
+    fields.extend(metadata.iter().map(|meta| {
+        Field::new(&meta.identifier, DataType::Float64, true)
+            .with_metadata(HashMap::from([(String::from("unit"), meta.unit.clone())]))
+    }));
+    let mut columns = vec![timestamp_array(rows.iter().map(|x| x.0))];
+    columns.extend(
+        (0..metadata.len())
+            .map(|i| Arc::new(rows.iter().map(|x| x.1[i]).collect::<Float64Array>()) as ArrayRef),
+    );
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Columns of a parquet file laid out like an aligned batch.
+/// Columns which are not in the file are missing in `values`, null values mark timestamps without a value.
+pub struct ParquetColumns {
+    pub timestamps: Vec<OffsetDateTime>,
+    pub values: HashMap<String, Vec<Option<f64>>>,
+}
+
+/// Read the `time_column` and the numeric columns named like `identifiers` of a parquet file.
+/// The time column may hold timestamps or strings, timestamps without a timezone are taken as UTC.
+pub fn read_parquet(
+    file: Bytes,
+    time_column: &str,
+    identifiers: &[String],
+) -> Result<ParquetColumns, ApiError> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+    let mut columns = ParquetColumns {
+        timestamps: vec![],
+        values: HashMap::new(),
+    };
+    for batch in reader {
+        let batch = batch?;
+        let time = batch
+            .column_by_name(time_column)
+            .ok_or_else(|| ApiError::InvalidParquet(format!("missing column '{}'", time_column)))?;
+        let time = cast(time, &timestamp_type())
+            .map_err(|e| ApiError::InvalidParquet(format!("column '{}': {}", time_column, e)))?;
+        let time = time
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .expect("cast to a microsecond timestamp");
+        if time.null_count() > 0 {
+            return Err(ApiError::InvalidParquet(format!(
+                "column '{}' contains null timestamps",
+                time_column
+            )));
+        }
+        for micros in time.values() {
+            let timestamp = OffsetDateTime::from_unix_timestamp_nanos(*micros as i128 * 1_000)
+                .map_err(anyhow::Error::from)?;
+            columns.timestamps.push(timestamp);
+        }
+
+        for identifier in identifiers {
+            let Some(column) = batch.column_by_name(identifier) else {
+                continue;
+            };
+            let column = cast(column, &DataType::Float64)
+                .map_err(|e| ApiError::InvalidParquet(format!("column '{}': {}", identifier, e)))?;
+            let column = column
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .expect("cast to float64");
+            columns
+                .values
+                .entry(identifier.clone())
+                .or_default()
+                .extend(column.iter());
+        }
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_columnar_format_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ColumnarFormat::from_headers(&headers), None);
+
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        assert_eq!(ColumnarFormat::from_headers(&headers), None);
+
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/vnd.apache.parquet;q=0.9, application/json"),
+        );
+        assert_eq!(
+            ColumnarFormat::from_headers(&headers),
+            Some(ColumnarFormat::Parquet)
+        );
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let meta = TimeseriesMeta {
+            id: 1,
+            identifier: String::from("pv"),
+            unit: String::from("kW"),
+            carrier: None,
+            consumption: None,
+            description: None,
+            local: None,
+        };
+        let rows = vec![
+            (datetime!(2019-01-01 0:00 UTC), vec![Some(1.5)]),
+            (datetime!(2019-01-01 0:15:00.5 UTC), vec![None]),
+        ];
+        let batch = aligned_batch(&[meta], "Time", &rows).unwrap();
+        let file = ColumnarFormat::Parquet.encode(&batch).unwrap();
+
+        let columns = read_parquet(Bytes::from(file), "Time", &[String::from("pv")]).unwrap();
+        assert_eq!(
+            columns.timestamps,
+            [
+                datetime!(2019-01-01 0:00 UTC),
+                datetime!(2019-01-01 0:15:00.5 UTC)
+            ]
+        );
+        assert_eq!(columns.values["pv"], [Some(1.5), None]);
+
+        let missing = read_parquet(Bytes::from(file_without_time()), "Time", &[]);
+        assert!(matches!(missing, Err(ApiError::InvalidParquet(_))));
+    }
+
+    fn file_without_time() -> Vec<u8> {
+        let schema = Schema::new(vec![Field::new("value", DataType::Float64, false)]);
+        let columns: Vec<ArrayRef> = vec![Arc::new(Float64Array::from(vec![1.0]))];
+        let batch = RecordBatch::try_new(Arc::new(schema), columns).unwrap();
+        ColumnarFormat::Parquet.encode(&batch).unwrap()
+    }
+}
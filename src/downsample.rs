@@ -0,0 +1,244 @@
+use crate::models::{Datapoint, DownsampleMethod};
+use std::collections::VecDeque;
+
+/// Reduces `total` datapoints pushed in timestamp order to at most `points` datapoints.
+pub enum Downsampler {
+    Lttb(Lttb),
+    MinMax(MinMax),
+}
+
+impl Downsampler {
+    pub fn new(method: DownsampleMethod, total: usize, points: usize) -> Self {
+        match method {
+            DownsampleMethod::Lttb => Self::Lttb(Lttb::new(total, points)),
+            // every bucket contributes up to two datapoints
+            DownsampleMethod::MinMax if total <= points => Self::MinMax(MinMax::new(total, total)),
+            DownsampleMethod::MinMax => Self::MinMax(MinMax::new(total, points / 2)),
+        }
+    }
+
+    pub fn push(&mut self, datapoint: Datapoint) {
+        match self {
+            Self::Lttb(lttb) => lttb.push(datapoint),
+            Self::MinMax(min_max) => min_max.push(datapoint),
+        }
+    }
+
+    pub fn finish(self) -> Vec<Datapoint> {
+        match self {
+            Self::Lttb(lttb) => lttb.finish(),
+            Self::MinMax(min_max) => min_max.finish(),
+        }
+    }
+}
+
+fn x(datapoint: &Datapoint) -> f64 {
+    datapoint.timestamp.unix_timestamp_nanos() as f64 / 1e9
+}
+
+/// Largest-Triangle-Three-Buckets over datapoints pushed in timestamp order.
+/// `total` is the expected number of datapoints, at most `threshold` of them are kept.
+/// Only the datapoints of two buckets are held in memory at a time.
+pub struct Lttb {
+    threshold: usize,
+    every: f64,
+    pushed: usize,
+    selected: Vec<Datapoint>,
+    buckets: VecDeque<(usize, Vec<Datapoint>)>,
+    held: Option<Datapoint>,
+}
+
+impl Lttb {
+    pub fn new(total: usize, threshold: usize) -> Self {
+        Self {
+            threshold,
+            every: total.saturating_sub(2) as f64 / threshold.saturating_sub(2).max(1) as f64,
+            pushed: 0,
+            selected: vec![],
+            buckets: VecDeque::new(),
+            held: None,
+        }
+    }
+
+    pub fn push(&mut self, datapoint: Datapoint) {
+        // the last datapoint is always kept, so it must not end up in a bucket
+        if let Some(previous) = self.held.replace(datapoint) {
+            self.add(previous);
+        }
+    }
+
+    fn add(&mut self, datapoint: Datapoint) {
+        let index = self.pushed;
+        self.pushed += 1;
+        if index == 0 {
+            self.selected.push(datapoint);
+            return;
+        }
+        let bucket =
+            (((index - 1) as f64 / self.every) as usize).min(self.threshold.saturating_sub(3));
+        match self.buckets.back_mut() {
+            Some((last, datapoints)) if *last == bucket => datapoints.push(datapoint),
+            _ => self.buckets.push_back((bucket, vec![datapoint])),
+        }
+        // a bucket is complete once the bucket after its successor has started
+        if self.buckets.len() == 3 {
+            self.select();
+        }
+    }
+
+    /// keep the datapoint of the oldest bucket spanning the largest triangle with the
+    /// last selected datapoint and the average of the following bucket
+    fn select(&mut self) {
+        let Some((_, bucket)) = self.buckets.pop_front() else {
+            return;
+        };
+        let (next_x, next_y) = match (self.buckets.front(), &self.held) {
+            (Some((_, next)), _) => (
+                next.iter().map(x).sum::<f64>() / next.len() as f64,
+                next.iter().map(|d| d.value).sum::<f64>() / next.len() as f64,
+            ),
+            (None, Some(last)) => (x(last), last.value),
+            (None, None) => return self.selected.extend(bucket),
+        };
+        let previous = self.selected.last().unwrap();
+        let (previous_x, previous_y) = (x(previous), previous.value);
+        let area = |d: &Datapoint| {
+            ((previous_x - next_x) * (d.value - previous_y)
+                - (previous_x - x(d)) * (next_y - previous_y))
+                .abs()
+        };
+        let largest = bucket
+            .into_iter()
+            .max_by(|a, b| area(a).total_cmp(&area(b)))
+            .unwrap();
+        self.selected.push(largest);
+    }
+
+    pub fn finish(mut self) -> Vec<Datapoint> {
+        if self.threshold < 3 || self.pushed < self.threshold {
+            // nothing to reduce, keep every datapoint
+            self.selected
+                .extend(self.buckets.drain(..).flat_map(|(_, bucket)| bucket));
+        } else {
+            while !self.buckets.is_empty() {
+                self.select();
+            }
+        }
+        self.selected.extend(self.held);
+        self.selected
+    }
+}
+
+/// Keep the minimum and maximum of `buckets` equally sized buckets of datapoints pushed in timestamp order.
+pub struct MinMax {
+    buckets: usize,
+    total: usize,
+    pushed: usize,
+    selected: Vec<Datapoint>,
+    bucket: Option<(usize, Datapoint, Datapoint)>,
+}
+
+impl MinMax {
+    pub fn new(total: usize, buckets: usize) -> Self {
+        Self {
+            buckets: buckets.max(1),
+            total: total.max(1),
+            pushed: 0,
+            selected: vec![],
+            bucket: None,
+        }
+    }
+
+    pub fn push(&mut self, datapoint: Datapoint) {
+        let index = (self.pushed * self.buckets / self.total).min(self.buckets - 1);
+        self.pushed += 1;
+        match &mut self.bucket {
+            Some((bucket, min, max)) if *bucket == index => {
+                if datapoint.value < min.value {
+                    *min = datapoint;
+                } else if datapoint.value > max.value {
+                    *max = datapoint;
+                }
+            }
+            _ => {
+                self.flush();
+                self.bucket = Some((index, datapoint.clone(), datapoint));
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some((_, min, max)) = self.bucket.take() {
+            if min.timestamp == max.timestamp {
+                self.selected.push(min);
+            } else if min.timestamp < max.timestamp {
+                self.selected.extend([min, max]);
+            } else {
+                self.selected.extend([max, min]);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<Datapoint> {
+        self.flush();
+        self.selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lttb, MinMax};
+    use crate::models::Datapoint;
+    use time::{Duration, OffsetDateTime};
+
+    fn series(values: &[f64]) -> Vec<Datapoint> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Datapoint {
+                id: i as i64,
+                timestamp: OffsetDateTime::UNIX_EPOCH + Duration::minutes(15 * i as i64),
+                value: *value,
+                quality: Default::default(),
+                created_at: OffsetDateTime::UNIX_EPOCH,
+                updated_at: OffsetDateTime::UNIX_EPOCH,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lttb() {
+        let values: Vec<f64> = (0..100)
+            .map(|i| if i == 42 { 100.0 } else { (i % 5) as f64 })
+            .collect();
+        let mut lttb = Lttb::new(values.len(), 10);
+        for datapoint in series(&values) {
+            lttb.push(datapoint);
+        }
+        let downsampled = lttb.finish();
+        assert_eq!(downsampled.len(), 10);
+        assert_eq!(downsampled.first().unwrap().id, 0);
+        assert_eq!(downsampled.last().unwrap().id, 99);
+        // the peak is kept
+        assert!(downsampled.iter().any(|d| d.value == 100.0));
+        assert!(downsampled
+            .windows(2)
+            .all(|w| w[0].timestamp < w[1].timestamp));
+
+        let mut lttb = Lttb::new(4, 10);
+        for datapoint in series(&[1.0, 2.0, 3.0, 4.0]) {
+            lttb.push(datapoint);
+        }
+        assert_eq!(lttb.finish().len(), 4);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let mut min_max = MinMax::new(6, 2);
+        for datapoint in series(&[1.0, 5.0, 3.0, 2.0, -1.0, 0.0]) {
+            min_max.push(datapoint);
+        }
+        let values: Vec<f64> = min_max.finish().iter().map(|d| d.value).collect();
+        assert_eq!(values, vec![1.0, 5.0, 2.0, -1.0]);
+    }
+}
@@ -36,6 +36,12 @@ pub enum ApiError {
     #[error(transparent)]
     CsvError(#[from] csv::Error),
 
+    #[error(transparent)]
+    ArrowError(#[from] arrow::error::ArrowError),
+
+    #[error(transparent)]
+    ParquetError(#[from] parquet::errors::ParquetError),
+
     #[error(transparent)]
     TimeParseError(#[from] time::error::Parse),
 
@@ -54,6 +60,54 @@ pub enum ApiError {
 
     #[error("request path not found")]
     NotFound,
+
+    #[error("datapoint already exists for this identifier and timestamp")]
+    DuplicateDatapoint,
+
+    #[error("datapoints of '{0}' already exist, set on_conflict of the import config to skip or overwrite them")]
+    DuplicateImport(String),
+
+    #[error("auto_create_meta requires meta_defaults in the request body")]
+    MissingMetaDefaults,
+
+    #[error("Invalid aggregate '{0}'")]
+    InvalidAggregate(String),
+
+    #[error("Either identifiers or a meta filter (carrier, local, consumption) is required")]
+    MissingSeriesSelection,
+
+    #[error("Unit '{0}' is not a power unit (W, kW, MW)")]
+    NotAPowerUnit(String),
+
+    #[error("Unknown unit '{0}'")]
+    UnknownUnit(String),
+
+    #[error("Unit '{0}' of the market price series is not a price unit (e.g. EUR/MWh)")]
+    NotAPriceUnit(String),
+
+    #[error("Cannot convert '{0}' to '{1}'")]
+    IncompatibleUnits(String, String),
+
+    #[error("Unknown timezone '{0}'")]
+    InvalidTimezone(String),
+
+    #[error("At least 3 points are required for downsampling, got {0}")]
+    TooFewPoints(i64),
+
+    #[error("Invalid percentile '{0}', expected a number between 0 and 100")]
+    InvalidPercentile(String),
+
+    #[error("Invalid histogram: {0}")]
+    InvalidHistogram(String),
+
+    #[error("Invalid parquet file: {0}")]
+    InvalidParquet(String),
+
+    #[error("Invalid line protocol in line {0}: {1}")]
+    InvalidLineProtocol(usize, String),
+
+    #[error("Partial write, {0} fields were rejected: {1}")]
+    PartialWrite(usize, String),
 }
 
 /*
@@ -72,7 +126,25 @@ impl ApiError {
             Self::ParseFloatError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::ParseIntError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::CsvError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ArrowError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ParquetError(_) => StatusCode::BAD_REQUEST,
             Self::InvalidInterval => StatusCode::BAD_REQUEST,
+            Self::DuplicateDatapoint => StatusCode::CONFLICT,
+            Self::DuplicateImport(_) => StatusCode::CONFLICT,
+            Self::MissingMetaDefaults => StatusCode::BAD_REQUEST,
+            Self::InvalidAggregate(_) => StatusCode::BAD_REQUEST,
+            Self::MissingSeriesSelection => StatusCode::BAD_REQUEST,
+            Self::NotAPowerUnit(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownUnit(_) => StatusCode::BAD_REQUEST,
+            Self::NotAPriceUnit(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::IncompatibleUnits(_, _) => StatusCode::BAD_REQUEST,
+            Self::InvalidTimezone(_) => StatusCode::BAD_REQUEST,
+            Self::TooFewPoints(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidPercentile(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidHistogram(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidParquet(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidLineProtocol(_, _) => StatusCode::BAD_REQUEST,
+            Self::PartialWrite(_, _) => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+
+/// Datapoints of a series with timestamps between `from` and `to` were written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DatapointEvent {
+    pub meta_id: i32,
+    pub from: OffsetDateTime,
+    pub to: OffsetDateTime,
+}
+
+impl DatapointEvent {
+    /// One event per series with the range of its written `(meta_id, timestamp)` pairs.
+    pub fn from_datapoints(
+        datapoints: impl IntoIterator<Item = (i32, OffsetDateTime)>,
+    ) -> Vec<Self> {
+        let mut ranges: BTreeMap<i32, (OffsetDateTime, OffsetDateTime)> = BTreeMap::new();
+        for (meta_id, timestamp) in datapoints {
+            let range = ranges.entry(meta_id).or_insert((timestamp, timestamp));
+            range.0 = range.0.min(timestamp);
+            range.1 = range.1.max(timestamp);
+        }
+        ranges
+            .into_iter()
+            .map(|(meta_id, (from, to))| Self { meta_id, from, to })
+            .collect()
+    }
+}
+
+/// In-process channel which tells subscriptions about written datapoints, see `handlers::subscription`.
+/// Events only carry ranges, subscribers read the datapoints themselves.
+#[derive(Clone)]
+pub struct Events {
+    sender: broadcast::Sender<DatapointEvent>,
+}
+
+impl Events {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Events are dropped if nobody is subscribed.
+    pub fn publish(&self, events: impl IntoIterator<Item = DatapointEvent>) {
+        for event in events {
+            let _ = self.sender.send(event);
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DatapointEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_from_datapoints() {
+        let events = DatapointEvent::from_datapoints([
+            (2, datetime!(2024-01-01 12:00 UTC)),
+            (1, datetime!(2024-01-01 13:00 UTC)),
+            (2, datetime!(2024-01-01 11:00 UTC)),
+            (2, datetime!(2024-01-01 11:30 UTC)),
+        ]);
+        assert_eq!(
+            events,
+            [
+                DatapointEvent {
+                    meta_id: 1,
+                    from: datetime!(2024-01-01 13:00 UTC),
+                    to: datetime!(2024-01-01 13:00 UTC),
+                },
+                DatapointEvent {
+                    meta_id: 2,
+                    from: datetime!(2024-01-01 11:00 UTC),
+                    to: datetime!(2024-01-01 12:00 UTC),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_without_subscribers() {
+        let events = Events::new(4);
+        events.publish(DatapointEvent::from_datapoints([(
+            1,
+            OffsetDateTime::now_utc(),
+        )]));
+
+        let mut receiver = events.subscribe();
+        let event = DatapointEvent::from_datapoints([(1, OffsetDateTime::now_utc())]);
+        events.publish(event.clone());
+        assert_eq!(receiver.recv().await.unwrap(), event[0]);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::error::ApiError;
+use crate::models::{ImportConfig, MetaInput, TimeseriesMeta};
+
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+// both are accepted by `import::CSV_TIME_FORMAT`
+const WHOLE_SECONDS_FORMAT: &[FormatItem<'static>] = format_description!(
+    "[year]-[month]-[day] [hour]:[minute]:[second][offset_hour sign:mandatory]:[offset_minute]"
+);
+const FRACTIONAL_SECONDS_FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond][offset_hour sign:mandatory]:[offset_minute]");
+
+/// Describe a csv export so it can be read again by `import::import`.
+pub fn import_config(metadata: &[TimeseriesMeta], time_column: &str) -> ImportConfig {
+    ImportConfig {
+        files: None,
+        time_column: time_column.to_string(),
+        timeseries: metadata
+            .iter()
+            .map(|meta| MetaInput {
+                identifier: meta.identifier.clone(),
+                unit: meta.unit.clone(),
+                carrier: meta.carrier.clone(),
+                consumption: meta.consumption,
+                description: meta.description.clone(),
+                local: meta.local,
+            })
+            .collect(),
+        quality: Default::default(),
+        on_conflict: Default::default(),
+    }
+}
+
+pub fn csv_header(metadata: &[TimeseriesMeta], time_column: &str) -> Result<String, ApiError> {
+    let mut fields = vec![time_column.to_string()];
+    fields.extend(metadata.iter().map(|meta| meta.identifier.clone()));
+    csv_line(fields)
+}
+
+/// One csv line per timestamp, timeseries without a value at this timestamp are left empty.
+pub fn csv_row(timestamp: OffsetDateTime, values: &[Option<f64>]) -> Result<String, ApiError> {
+    let format = match timestamp.nanosecond() {
+        0 => WHOLE_SECONDS_FORMAT,
+        _ => FRACTIONAL_SECONDS_FORMAT,
+    };
+    let time = timestamp.format(format).map_err(anyhow::Error::from)?;
+    let mut fields = vec![time];
+    fields.extend(
+        values
+            .iter()
+            .map(|x| x.map(|x| x.to_string()).unwrap_or_default()),
+    );
+    csv_line(fields)
+}
+
+fn csv_line(fields: Vec<String>) -> Result<String, ApiError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(fields)?;
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::CSV_TIME_FORMAT;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_csv_row() {
+        let row = csv_row(datetime!(2019-01-01 0:15 UTC), &[Some(1.5), None]).unwrap();
+        assert_eq!(row, "2019-01-01 00:15:00+00:00,1.5,\n");
+
+        let row = csv_row(datetime!(2019-01-01 0:15:00.25 +01:00), &[Some(-14.0)]).unwrap();
+        assert_eq!(row, "2019-01-01 00:15:00.25+01:00,-14\n");
+        assert_eq!(
+            OffsetDateTime::parse("2019-01-01 00:15:00.25+01:00", &CSV_TIME_FORMAT).unwrap(),
+            datetime!(2019-01-01 0:15:00.25 +01:00)
+        );
+    }
+}
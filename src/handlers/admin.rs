@@ -0,0 +1,178 @@
+use crate::error::ApiError;
+use crate::handlers::timeseries::fetch_timeseries_meta;
+use crate::infrastructure::AppState;
+use crate::models::{parse_interval, Result, RetentionBody, RetentionResult, SeriesRetention};
+use crate::models::{ChunkReport, ChunkStats, CompressionPolicyBody, CompressionSettings};
+use axum::extract::{Path, State};
+use axum::Json;
+use sqlx::postgres::types::PgInterval;
+use sqlx::{Pool, Postgres};
+
+fn parse_policy_interval(interval: Option<&str>) -> Result<Option<PgInterval>> {
+    interval
+        .map(|x| parse_interval(x).map_err(|_| ApiError::InvalidInterval))
+        .transpose()
+}
+
+async fn fetch_compression_settings(pool: &Pool<Postgres>) -> Result<CompressionSettings> {
+    let settings = sqlx::query_as!(
+        CompressionSettings,
+        r#"
+        select
+            exists (
+                select 1 from timescaledb_information.compression_settings
+                where hypertable_name = 'ts'
+            ) as "enabled!",
+            array(
+                select attname::text from timescaledb_information.compression_settings
+                where hypertable_name = 'ts' and segmentby_column_index is not null
+                order by segmentby_column_index
+            ) as "segment_by!",
+            array(
+                select attname::text from timescaledb_information.compression_settings
+                where hypertable_name = 'ts' and orderby_column_index is not null
+                order by orderby_column_index
+            ) as "order_by!",
+            (
+                select config ->> 'compress_after' from timescaledb_information.jobs
+                where hypertable_name = 'ts' and proc_name = 'policy_compression'
+            ) as compress_after
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(settings)
+}
+
+/// compression settings of the ts hypertable and its compression policy
+pub async fn get_compression(
+    State(app_state): State<AppState>,
+) -> Result<Json<CompressionSettings>> {
+    Ok(Json(fetch_compression_settings(&app_state.db).await?))
+}
+
+/// Replace the compression policy, chunks are compressed once all their datapoints are older than `compress_after`.
+pub async fn put_compression(
+    State(app_state): State<AppState>,
+    Json(body): Json<CompressionPolicyBody>,
+) -> Result<Json<CompressionSettings>> {
+    let compress_after = parse_policy_interval(body.compress_after.as_deref())?;
+    let mut tx = app_state.db.begin().await?;
+    sqlx::query!("select remove_compression_policy('ts', if_exists => true)")
+        .fetch_one(&mut *tx)
+        .await?;
+    if let Some(compress_after) = compress_after {
+        sqlx::query!(
+            "select add_compression_policy('ts', compress_after => $1::interval)",
+            compress_after
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(Json(fetch_compression_settings(&app_state.db).await?))
+}
+
+/// all series with a retention
+pub async fn get_retention(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<SeriesRetention>>> {
+    let retention = sqlx::query_as!(
+        SeriesRetention,
+        r#"
+        select identifier, retention::text as retention
+        from meta
+        where retention is not null
+        order by identifier
+        "#
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(retention))
+}
+
+/// Set or remove the retention of a series, expired datapoints are deleted by the hourly retention job.
+pub async fn put_retention(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Json(body): Json<RetentionBody>,
+) -> Result<Json<SeriesRetention>> {
+    let retention = parse_policy_interval(body.retention.as_deref())?;
+    let metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let retention = sqlx::query_as!(
+        SeriesRetention,
+        r#"
+        update meta set retention = $2
+        where id = $1
+        returning identifier, retention::text as retention
+        "#,
+        metadata.id,
+        retention,
+    )
+    .fetch_one(&app_state.db)
+    .await?;
+    Ok(Json(retention))
+}
+
+/// delete expired datapoints of all series right away instead of waiting for the retention job
+pub async fn apply_retention(State(app_state): State<AppState>) -> Result<Json<RetentionResult>> {
+    let deleted = sqlx::query_scalar!(r#"select apply_series_retention() as "deleted!""#)
+        .fetch_one(&app_state.db)
+        .await?;
+    Ok(Json(RetentionResult { deleted }))
+}
+
+/// Size of every chunk of the ts hypertable and the compression ratio of the compressed ones.
+pub async fn get_chunks(State(app_state): State<AppState>) -> Result<Json<ChunkReport>> {
+    let chunks = sqlx::query!(
+        r#"
+        select
+            chunks.chunk_name::text as "chunk_name!",
+            chunks.range_start,
+            chunks.range_end,
+            coalesce(chunks.is_compressed, false) as "is_compressed!",
+            pg_total_relation_size(format('%I.%I', chunks.chunk_schema, chunks.chunk_name)::regclass) as "total_bytes!",
+            stats.before_compression_total_bytes,
+            stats.after_compression_total_bytes
+        from timescaledb_information.chunks
+            left join chunk_compression_stats('ts') stats
+                on stats.chunk_schema = chunks.chunk_schema
+                and stats.chunk_name = chunks.chunk_name
+                and stats.compression_status = 'Compressed'
+        where chunks.hypertable_name = 'ts'
+        order by chunks.range_start
+        "#
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    let chunks: Vec<ChunkStats> = chunks
+        .into_iter()
+        .map(|chunk| ChunkStats {
+            chunk_name: chunk.chunk_name,
+            range_start: chunk.range_start,
+            range_end: chunk.range_end,
+            is_compressed: chunk.is_compressed,
+            // the datapoints of a compressed chunk live in its compressed chunk
+            total_bytes: chunk
+                .after_compression_total_bytes
+                .unwrap_or(chunk.total_bytes),
+            compression_ratio: chunk
+                .before_compression_total_bytes
+                .zip(chunk.after_compression_total_bytes)
+                .filter(|(_, after)| *after > 0)
+                .map(|(before, after)| before as f64 / after as f64),
+            before_compression_bytes: chunk.before_compression_total_bytes,
+            after_compression_bytes: chunk.after_compression_total_bytes,
+        })
+        .collect();
+    let compressed = chunks
+        .iter()
+        .filter_map(|x| x.before_compression_bytes.zip(x.after_compression_bytes))
+        .fold((0, 0), |(before, after), (x, y)| (before + x, after + y));
+    Ok(Json(ChunkReport {
+        total_bytes: chunks.iter().map(|x| x.total_bytes).sum(),
+        compression_ratio: (compressed.1 > 0).then(|| compressed.0 as f64 / compressed.1 as f64),
+        chunks,
+    }))
+}
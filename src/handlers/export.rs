@@ -0,0 +1,147 @@
+use crate::columnar::{aligned_batch, ColumnarFormat};
+use crate::error::ApiError;
+use crate::export::{csv_header, csv_row, import_config};
+use crate::handlers::timeseries::fetch_timeseries_metas;
+use crate::handlers::util::channel_response;
+use crate::infrastructure::AppState;
+use crate::models::{DatapointQuality, ExportFormat, ExportParams, NewDatapoint, Result};
+use crate::models::{TimeseriesMeta, TimestampFilter};
+
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use futures::{SinkExt, StreamExt};
+use sqlx::{Pool, Postgres};
+use time::OffsetDateTime;
+
+/*
+export one or more timeseries in the same layout as csv imports
+the csv can be imported again with the yaml returned by `format=import_config`
+clients accepting Arrow or Parquet get the same columns in that format, Parquet can be uploaded again
+test: curl "127.0.0.1:3000/v1/ts/export/?identifiers=total_load,production_of_pv&format=csv"
+*/
+pub async fn export_timeseries(
+    State(app_state): State<AppState>,
+    Query(params): Query<ExportParams>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let metadata = fetch_timeseries_metas(&app_state.db, &params.identifiers()).await?;
+
+    if params.format == ExportFormat::ImportConfig {
+        let config = import_config(&metadata, &params.time_column);
+        let yaml = serde_yaml::to_string(&config).map_err(anyhow::Error::from)?;
+        return Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml).into_response());
+    }
+    if let Some(format) = ColumnarFormat::from_headers(&headers) {
+        let rows = fetch_aligned_rows(&app_state.db, &metadata, &timestamp_filter).await?;
+        return format.response(&aligned_batch(&metadata, &params.time_column, &rows)?);
+    }
+
+    let content_type = match params.format {
+        ExportFormat::Ndjson => "application/x-ndjson",
+        _ => "text/csv",
+    };
+    let header = match params.format {
+        ExportFormat::Ndjson => String::new(),
+        _ => csv_header(&metadata, &params.time_column)?,
+    };
+    let pool = app_state.db;
+    Ok(channel_response(content_type, |mut sender| async move {
+        if sender.send(Ok(header)).await.is_err() {
+            return;
+        }
+        let meta_ids = metadata.iter().map(|m| m.id).collect::<Vec<_>>();
+        let mut rows = sqlx::query!(
+            r#"
+            select
+                ts.series_timestamp as "timestamp!",
+                ts.series_value as "value!",
+                ts.quality as "quality!: DatapointQuality",
+                ts.meta_id as "meta_id!"
+            from ts_as_of($1, $2, $3, $4) ts
+            order by ts.series_timestamp, ts.meta_id
+            "#,
+            &meta_ids,
+            timestamp_filter.from.unwrap(),
+            timestamp_filter.to.unwrap(),
+            timestamp_filter.as_of,
+        )
+        .fetch(&pool);
+
+        // csv rows are only complete once the next timestamp is read
+        let mut row: Option<(OffsetDateTime, Vec<Option<f64>>)> = None;
+        while let Some(record) = rows.next().await {
+            let chunk = record.map_err(ApiError::from).and_then(|record| {
+                let column = meta_ids.iter().position(|x| *x == record.meta_id).unwrap();
+                match params.format {
+                    ExportFormat::Ndjson => {
+                        let datapoint = NewDatapoint {
+                            timestamp: record.timestamp,
+                            value: record.value,
+                            identifier: metadata[column].identifier.clone(),
+                            quality: record.quality,
+                        };
+                        let json =
+                            serde_json::to_string(&datapoint).map_err(anyhow::Error::from)?;
+                        Ok(format!("{}\n", json))
+                    }
+                    _ => {
+                        let mut finished = String::new();
+                        if row.as_ref().is_some_and(|x| x.0 != record.timestamp) {
+                            let (timestamp, values) = row.take().unwrap();
+                            finished = csv_row(timestamp, &values)?;
+                        }
+                        let (_, values) = row
+                            .get_or_insert_with(|| (record.timestamp, vec![None; meta_ids.len()]));
+                        values[column] = Some(record.value);
+                        Ok(finished)
+                    }
+                }
+            });
+            let failed = chunk.is_err();
+            if sender.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+        if let Some((timestamp, values)) = row {
+            let _ = sender.send(csv_row(timestamp, &values)).await;
+        }
+    }))
+}
+
+/// One row per timestamp with the value of every series in the order of `metadata`.
+/// Arrow and Parquet are written at once, so unlike the csv export all rows are held in memory.
+async fn fetch_aligned_rows(
+    pool: &Pool<Postgres>,
+    metadata: &[TimeseriesMeta],
+    timestamp_filter: &TimestampFilter,
+) -> Result<Vec<(OffsetDateTime, Vec<Option<f64>>)>> {
+    let meta_ids = metadata.iter().map(|m| m.id).collect::<Vec<_>>();
+    let records = sqlx::query!(
+        r#"
+        select
+            ts.series_timestamp as "timestamp!",
+            ts.series_value as "value!",
+            ts.meta_id as "meta_id!"
+        from ts_as_of($1, $2, $3, $4) ts
+        order by ts.series_timestamp, ts.meta_id
+        "#,
+        &meta_ids,
+        timestamp_filter.from.unwrap(),
+        timestamp_filter.to.unwrap(),
+        timestamp_filter.as_of,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut rows: Vec<(OffsetDateTime, Vec<Option<f64>>)> = vec![];
+    for record in records {
+        let column = meta_ids.iter().position(|x| *x == record.meta_id).unwrap();
+        if rows.last().is_none_or(|x| x.0 != record.timestamp) {
+            rows.push((record.timestamp, vec![None; meta_ids.len()]));
+        }
+        rows.last_mut().unwrap().1[column] = Some(record.value);
+    }
+    Ok(rows)
+}
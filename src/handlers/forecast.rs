@@ -0,0 +1,177 @@
+use crate::handlers::timeseries::fetch_timeseries_meta;
+use crate::infrastructure::AppState;
+use crate::models::{AddForecastResponse, ForecastDatapoint, ForecastErrors, ForecastFilter};
+use crate::models::{ForecastIssue, ForecastSeries, NewForecastBody, Result, UnitConversion};
+use axum::extract::{Path, Query, State};
+use axum::Json;
+
+use std::collections::BTreeMap;
+
+/// Store a forecast run of a series, forecasts with the same issue and target time are overwritten.
+pub async fn add_forecast(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Json(body): Json<NewForecastBody>,
+) -> Result<Json<AddForecastResponse>> {
+    let metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    // a row cannot be upserted twice by one statement, the last value of a target wins
+    let datapoints = body
+        .datapoints
+        .iter()
+        .map(|x| (x.timestamp, x.value))
+        .collect::<BTreeMap<_, _>>();
+
+    let inserted = sqlx::query_scalar!(
+        r#"
+        insert into forecast (meta_id, issued_at, target_timestamp, value)
+        select $1, $2, target.timestamp, target.value
+        from unnest($3::timestamptz[], $4::float[]) as target(timestamp, value)
+        on conflict (meta_id, issued_at, target_timestamp) do update set value = excluded.value
+        returning (xmax = 0) as "inserted!"
+        "#,
+        metadata.id,
+        body.issued_at,
+        &datapoints.keys().copied().collect::<Vec<_>>(),
+        &datapoints.values().copied().collect::<Vec<_>>(),
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    let inserted_count = inserted.iter().filter(|x| **x).count() as i64;
+    Ok(Json(AddForecastResponse {
+        meta: metadata,
+        issued_at: body.issued_at,
+        inserted: inserted_count,
+        updated: inserted.len() as i64 - inserted_count,
+    }))
+}
+
+/// One forecast per target timestamp of a series, see `ForecastFilter`.
+pub async fn get_forecast_by_identifier(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(filter): Query<ForecastFilter>,
+    Query(conversion): Query<UnitConversion>,
+) -> Result<Json<ForecastSeries>> {
+    let mut metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let factor = conversion.factor(&metadata.unit, &app_state.db).await?;
+    metadata.unit = conversion.unit_or(&metadata.unit);
+
+    let datapoints = sqlx::query_as!(
+        ForecastDatapoint,
+        r#"
+        select distinct on (forecast.target_timestamp)
+            forecast.target_timestamp as "timestamp",
+            forecast.issued_at,
+            forecast.value * $6 as "value!"
+        from forecast
+        where forecast.meta_id = $1
+            and ($2::timestamptz is null or forecast.target_timestamp >= $2)
+            and ($3::timestamptz is null or forecast.target_timestamp <= $3)
+            and ($4::timestamptz is null or forecast.issued_at = $4)
+            and ($5::timestamptz is null or forecast.issued_at <= $5)
+        order by forecast.target_timestamp, forecast.issued_at desc
+        "#,
+        metadata.id,
+        filter.from,
+        filter.to,
+        filter.issued_at,
+        filter.issued_before,
+        factor,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    Ok(Json(ForecastSeries {
+        meta: metadata,
+        datapoints,
+    }))
+}
+
+/// All forecast runs of a series with target timestamps within the requested range.
+pub async fn get_forecast_issues(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(filter): Query<ForecastFilter>,
+) -> Result<Json<Vec<ForecastIssue>>> {
+    let metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let issues = sqlx::query_as!(
+        ForecastIssue,
+        r#"
+        select
+            forecast.issued_at,
+            min(forecast.target_timestamp) as "from!",
+            max(forecast.target_timestamp) as "to!",
+            count(*) as "datapoints!"
+        from forecast
+        where forecast.meta_id = $1
+            and ($2::timestamptz is null or forecast.target_timestamp >= $2)
+            and ($3::timestamptz is null or forecast.target_timestamp <= $3)
+            and ($4::timestamptz is null or forecast.issued_at = $4)
+            and ($5::timestamptz is null or forecast.issued_at <= $5)
+        group by forecast.issued_at
+        order by forecast.issued_at
+        "#,
+        metadata.id,
+        filter.from,
+        filter.to,
+        filter.issued_at,
+        filter.issued_before,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(issues))
+}
+
+/// MAE, RMSE and MAPE of the forecasts selected by `ForecastFilter` against the measured series.
+/// Only target timestamps with a datapoint at exactly the same time are compared.
+pub async fn get_forecast_errors(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(filter): Query<ForecastFilter>,
+    Query(conversion): Query<UnitConversion>,
+) -> Result<Json<ForecastErrors>> {
+    let mut metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let factor = conversion.factor(&metadata.unit, &app_state.db).await?;
+    metadata.unit = conversion.unit_or(&metadata.unit);
+
+    let errors = sqlx::query!(
+        r#"
+        with selected as (
+            select distinct on (forecast.target_timestamp)
+                forecast.target_timestamp,
+                forecast.value
+            from forecast
+            where forecast.meta_id = $1
+                and ($2::timestamptz is null or forecast.target_timestamp >= $2)
+                and ($3::timestamptz is null or forecast.target_timestamp <= $3)
+                and ($4::timestamptz is null or forecast.issued_at = $4)
+                and ($5::timestamptz is null or forecast.issued_at <= $5)
+            order by forecast.target_timestamp, forecast.issued_at desc
+        )
+        select
+            count(*) as "datapoints!",
+            avg(abs(selected.value - ts.series_value)) as mae,
+            sqrt(avg((selected.value - ts.series_value) ^ 2)) as rmse,
+            100 * avg(abs((selected.value - ts.series_value) / ts.series_value))
+                filter (where ts.series_value <> 0) as mape
+        from selected
+            join ts on ts.meta_id = $1 and ts.series_timestamp = selected.target_timestamp
+        "#,
+        metadata.id,
+        filter.from,
+        filter.to,
+        filter.issued_at,
+        filter.issued_before,
+    )
+    .fetch_one(&app_state.db)
+    .await?;
+
+    Ok(Json(ForecastErrors {
+        meta: metadata,
+        datapoints: errors.datapoints,
+        mae: errors.mae.map(|x| x * factor),
+        rmse: errors.rmse.map(|x| x * factor),
+        mape: errors.mape,
+    }))
+}
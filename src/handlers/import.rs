@@ -1,5 +1,5 @@
 use crate::error::ApiError;
-use crate::import::import;
+use crate::import::{import, import_parquet};
 
 use crate::infrastructure::AppState;
 use crate::models::Result;
@@ -12,6 +12,7 @@ use axum::Json;
 use std::string::String;
 /*
 upload a file from a form and bulk insert it into the database
+csv and parquet files are told apart by the magic bytes of parquet
 docs: https://docs.rs/axum/latest/axum/extract/multipart/struct.Field.html
 test: curl -F upload=@initdb/inno2grid_backend_test.csv 127.0.0.1:3000/v1/ts/upload
 */
@@ -24,10 +25,14 @@ pub async fn upload_timeseries(
 
     while let Some(field) = multipart.next_field().await.unwrap() {
         // whole file is read into memory, which is bad but ok for now
-        let text = field.text().await.unwrap();
-        let reader = csv::ReaderBuilder::new().from_reader(text.as_bytes());
-
-        import(&app_state.db, vec![reader], &import_config).await?;
+        let file = field.bytes().await?;
+        let events = if file.starts_with(b"PAR1") {
+            import_parquet(&app_state.db, file, &import_config).await?
+        } else {
+            let reader = csv::ReaderBuilder::new().from_reader(&file[..]);
+            import(&app_state.db, vec![reader], &import_config).await?
+        };
+        app_state.events.publish(events);
     }
     Ok(Json("File uploaded successfully".to_string()))
 }
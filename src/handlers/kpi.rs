@@ -1,9 +1,9 @@
 use crate::error::ApiError;
 use crate::infrastructure::AppState;
 use crate::models::KpiResult;
-use crate::models::TimestampFilter;
 use crate::models::{Consumption, ConsumptionByCarrier, EmissionsByCarrier, Resampling, Result};
-use crate::models::{ConsumptionByConsumer, EmissionFactorSource};
+use crate::models::{ConsumptionByConsumer, EmissionFactorSource, UnitConversion};
+use crate::models::{KpiInput, QualityFilter, Rollup, TimestampFilter, Timezone};
 
 use crate::cache::Cache;
 use axum::extract::{Query, State};
@@ -12,28 +12,59 @@ use axum::Json;
 use sqlx::{Pool, Postgres};
 use std::string::String;
 
+/// Fails with `NotAPriceUnit` if the unit of the price series `identifier` is not a price unit,
+/// the cost kpis would silently ignore the prices of the market price series otherwise.
+pub(crate) async fn validate_price_unit(pool: &Pool<Postgres>, identifier: &str) -> Result<()> {
+    let unit = sqlx::query_scalar!(
+        r#"
+        select meta.unit as "unit!"
+        from meta
+        where meta.identifier = $1 and unit_factor(meta.unit, 'price') is null
+        "#,
+        identifier
+    )
+    .fetch_optional(pool)
+    .await?;
+    match unit {
+        Some(unit) => Err(ApiError::NotAPriceUnit(unit)),
+        None => Ok(()),
+    }
+}
+
 /*
 total_load / (locally produced energy)
 */
 pub async fn get_consumption_production_ratio(
     timestamp_filter: &TimestampFilter,
+    quality_filter: &QualityFilter,
     pool: &Pool<Postgres>,
 ) -> Result<f64> {
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup =
+        Rollup::for_query(None, timestamp_filter, &Timezone::default()).map(|x| x.interval());
 
     let consumption_record = sqlx::query_file!(
         "src/sql/total_consumption.sql",
         from_timestamp,
         to_timestamp,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
     )
     .fetch_one(pool)
     .await?;
 
-    let production_record =
-        sqlx::query_file!("src/sql/total_production.sql", from_timestamp, to_timestamp,)
-            .fetch_one(pool)
-            .await?;
+    let production_record = sqlx::query_file!(
+        "src/sql/total_production.sql",
+        from_timestamp,
+        to_timestamp,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
+    )
+    .fetch_one(pool)
+    .await?;
     let consumption: f64 = consumption_record.value.unwrap_or(1.0);
     let production: f64 = production_record.value.unwrap_or(1.0);
     let mut consumption_production_ratio = consumption;
@@ -45,10 +76,11 @@ pub async fn get_consumption_production_ratio(
 
 pub async fn get_self_consumption(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     State(app_state): State<AppState>,
 ) -> Result<Json<KpiResult>> {
     let consumption_production_ratio =
-        get_consumption_production_ratio(&timestamp_filter, &app_state.db).await?;
+        get_consumption_production_ratio(&timestamp_filter, &quality_filter, &app_state.db).await?;
     let self_consumption = f64::min(consumption_production_ratio, 1.0);
     let kpi_result = KpiResult {
         value: self_consumption,
@@ -56,16 +88,24 @@ pub async fn get_self_consumption(
         unit: None,
         from_timestamp: timestamp_filter.from.unwrap(),
         to_timestamp: timestamp_filter.to.unwrap(),
+        flagged_datapoints: quality_filter
+            .flagged_datapoints(
+                &app_state.db,
+                &timestamp_filter,
+                &[KpiInput::TotalLoad, KpiInput::Production],
+            )
+            .await?,
     };
     Ok(Json(kpi_result))
 }
 
 pub async fn get_autarky(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     State(app_state): State<AppState>,
 ) -> Result<Json<KpiResult>> {
     let consumption_production_ratio =
-        get_consumption_production_ratio(&timestamp_filter, &app_state.db).await?;
+        get_consumption_production_ratio(&timestamp_filter, &quality_filter, &app_state.db).await?;
     let autarky = f64::min(1.0 / consumption_production_ratio, 1.0);
     let kpi_result = KpiResult {
         value: autarky,
@@ -73,6 +113,13 @@ pub async fn get_autarky(
         unit: None,
         from_timestamp: timestamp_filter.from.unwrap(),
         to_timestamp: timestamp_filter.to.unwrap(),
+        flagged_datapoints: quality_filter
+            .flagged_datapoints(
+                &app_state.db,
+                &timestamp_filter,
+                &[KpiInput::TotalLoad, KpiInput::Production],
+            )
+            .await?,
     };
     Ok(Json(kpi_result))
 }
@@ -83,17 +130,28 @@ return consumption for each carrier as timeseries in kwh
 pub async fn get_consumption(
     State(app_state): State<AppState>,
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     Query(resampling): Query<Resampling>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
 ) -> Result<Json<Vec<ConsumptionByCarrier>>> {
     let pg_resampling_interval = resampling.map_interval()?;
+    timezone.validate(&app_state.db).await?;
+    let factor = conversion.factor("kwh", &app_state.db).await?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup = Rollup::for_query(Some(&pg_resampling_interval), &timestamp_filter, &timezone)
+        .map(|x| x.interval());
     let grid_consumption_records: Vec<Consumption> = sqlx::query_file_as!(
         Consumption,
         "src/sql/grid_consumption.sql",
         pg_resampling_interval,
         from_timestamp,
         to_timestamp,
+        timezone.tz,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
     )
     .fetch_all(&app_state.db)
     .await?;
@@ -104,6 +162,10 @@ pub async fn get_consumption(
         from_timestamp,
         to_timestamp,
         pg_resampling_interval,
+        timezone.tz,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
     )
     .fetch_all(&app_state.db)
     .await?;
@@ -114,9 +176,9 @@ pub async fn get_consumption(
             * consumption.bucket_consumption.unwrap_or(0.0);
         let kpi_result = ConsumptionByCarrier {
             bucket: consumption.bucket.unwrap(),
-            value: kpi_value,
+            value: kpi_value * factor,
             carrier_name: consumption.carrier_name,
-            unit: String::from("kwh"),
+            unit: conversion.unit_or("kwh"),
             local: false,
         };
         kpi_results.push(kpi_result);
@@ -126,13 +188,16 @@ pub async fn get_consumption(
             * production.bucket_consumption.unwrap_or(0.0);
         let kpi_result = ConsumptionByCarrier {
             bucket: production.bucket.unwrap(),
-            value: kpi_value,
+            value: kpi_value * factor,
             carrier_name: production.carrier_name,
-            unit: String::from("kwh"),
+            unit: conversion.unit_or("kwh"),
             local: true,
         };
         kpi_results.push(kpi_result);
     }
+    timezone
+        .localize(&app_state.db, kpi_results.iter_mut().map(|x| &mut x.bucket))
+        .await?;
     Ok(Json(kpi_results))
 }
 
@@ -141,49 +206,83 @@ return consumption by local consumers as timeseries in kwh
 */
 pub async fn get_local_consumption(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     Query(resampling): Query<Resampling>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
     State(app_state): State<AppState>,
 ) -> Result<Json<Vec<ConsumptionByConsumer>>> {
     if !resampling.validate_interval() {
         return Err(ApiError::InvalidInterval);
     }
+    timezone.validate(&app_state.db).await?;
     let interval = resampling.map_interval()?;
+    let factor = conversion.factor("kwh", &app_state.db).await?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup =
+        Rollup::for_query(Some(&interval), &timestamp_filter, &timezone).map(|x| x.interval());
 
-    let consumers_consumption = sqlx::query_file_as!(
+    let mut consumers_consumption = sqlx::query_file_as!(
         ConsumptionByConsumer,
         "src/sql/local_consumption.sql",
         from_timestamp,
         to_timestamp,
-        interval
+        interval,
+        timezone.tz,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
     )
     .fetch_all(&app_state.db)
     .await?;
+    for consumption in consumers_consumption.iter_mut() {
+        consumption.value = consumption.value.map(|x| x * factor);
+        consumption.unit = Some(conversion.unit_or("kwh"));
+    }
+    timezone
+        .localize(
+            &app_state.db,
+            consumers_consumption
+                .iter_mut()
+                .filter_map(|x| x.bucket.as_mut()),
+        )
+        .await?;
     Ok(Json(consumers_consumption))
 }
 pub async fn get_total_consumption(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
+    Query(conversion): Query<UnitConversion>,
     State(app_state): State<AppState>,
 ) -> Result<Json<KpiResult>, ApiError> {
+    let factor = conversion.factor("kwh", &app_state.db).await?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup =
+        Rollup::for_query(None, &timestamp_filter, &Timezone::default()).map(|x| x.interval());
 
     let consumption_record = sqlx::query_file!(
         "src/sql/total_consumption.sql",
         from_timestamp,
         to_timestamp,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
     )
     .fetch_one(&app_state.db)
     .await?;
 
     let consumption: f64 = consumption_record.value.unwrap_or(0.0);
     let kpi_result = KpiResult {
-        value: consumption,
+        value: consumption * factor,
         name: String::from("total_consumption"),
-        unit: Some(String::from("kwh")),
+        unit: Some(conversion.unit_or("kwh")),
         from_timestamp,
         to_timestamp,
+        flagged_datapoints: quality_filter
+            .flagged_datapoints(&app_state.db, &timestamp_filter, &[KpiInput::TotalLoad])
+            .await?,
     };
     Ok(Json(kpi_result))
 }
@@ -192,38 +291,60 @@ pub async fn get_total_consumption(
 */
 pub async fn get_total_production(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
+    Query(conversion): Query<UnitConversion>,
     State(app_state): State<AppState>,
 ) -> Result<Json<KpiResult>, ApiError> {
+    let factor = conversion.factor("kwh", &app_state.db).await?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup =
+        Rollup::for_query(None, &timestamp_filter, &Timezone::default()).map(|x| x.interval());
 
-    let production_record =
-        sqlx::query_file!("src/sql/total_production.sql", from_timestamp, to_timestamp,)
-            .fetch_one(&app_state.db)
-            .await?;
+    let production_record = sqlx::query_file!(
+        "src/sql/total_production.sql",
+        from_timestamp,
+        to_timestamp,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
+    )
+    .fetch_one(&app_state.db)
+    .await?;
 
     let production: f64 = production_record.value.unwrap_or(0.0);
     let kpi_result = KpiResult {
-        value: production,
+        value: production * factor,
         name: String::from("total_production"),
-        unit: Some(String::from("kwh")),
+        unit: Some(conversion.unit_or("kwh")),
         from_timestamp,
         to_timestamp,
+        flagged_datapoints: quality_filter
+            .flagged_datapoints(&app_state.db, &timestamp_filter, &[KpiInput::Production])
+            .await?,
     };
     Ok(Json(kpi_result))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_co2_savings(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     Query(resampling): Query<Resampling>,
     Query(ef_source): Query<EmissionFactorSource>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
     State(app_state): State<AppState>,
     uri: Uri,
 ) -> Result<Json<KpiResult>> {
     let ef_source = ef_source.get_source_or_default(&app_state.db).await?;
+    timezone.validate(&app_state.db).await?;
+    let factor = conversion.factor("kgco2eq", &app_state.db).await?;
     let pg_resampling_interval = resampling.map_interval()?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup = Rollup::for_query(Some(&pg_resampling_interval), &timestamp_filter, &timezone)
+        .map(|x| x.interval());
 
     let key = format!("{}", uri);
     let mut cache = Cache::new(&app_state.config.redis_url).await.unwrap();
@@ -239,17 +360,34 @@ pub async fn get_co2_savings(
                 from_timestamp,
                 to_timestamp,
                 pg_resampling_interval,
-                ef_source
+                ef_source,
+                timezone.tz,
+                rollup,
+                quality_filter.exclude_flagged,
+                timestamp_filter.as_of,
             )
             .fetch_one(&app_state.db)
             .await?;
-            let kpi = KpiResult {
-                value: query_results.co2_savings.unwrap_or_default(),
+            let mut kpi = KpiResult {
+                value: query_results.co2_savings.unwrap_or_default() * factor,
                 name: String::from("co2_savings"),
-                unit: Some(String::from("kgco2eq")),
+                unit: Some(conversion.unit_or("kgco2eq")),
                 from_timestamp: timestamp_filter.from.unwrap(),
                 to_timestamp: timestamp_filter.to.unwrap(),
+                flagged_datapoints: quality_filter
+                    .flagged_datapoints(
+                        &app_state.db,
+                        &timestamp_filter,
+                        &[KpiInput::LocalProduction, KpiInput::GridMix],
+                    )
+                    .await?,
             };
+            timezone
+                .localize(
+                    &app_state.db,
+                    [&mut kpi.from_timestamp, &mut kpi.to_timestamp],
+                )
+                .await?;
             let serialized = serde_json::to_string(&kpi).unwrap();
             cache.set(&key, &serialized, 5 * 60).await.unwrap();
             Ok(Json(kpi))
@@ -259,37 +397,86 @@ pub async fn get_co2_savings(
 
 pub async fn get_cost_savings(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
     State(app_state): State<AppState>,
 ) -> Result<Json<KpiResult>> {
+    let factor = conversion.factor("EUR", &app_state.db).await?;
+    timezone.validate(&app_state.db).await?;
+    validate_price_unit(&app_state.db, "smard_market_price").await?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    // production is bucketed hourly to match the market prices
+    let rollup = Rollup::for_query(Some(&Rollup::Hour.interval()), &timestamp_filter, &timezone)
+        .map(|x| x.interval());
 
-    let cost_saving_query_results =
-        sqlx::query_file!("src/sql/cost_savings.sql", from_timestamp, to_timestamp,)
-            .fetch_one(&app_state.db)
-            .await?;
+    let cost_saving_query_results = sqlx::query_file!(
+        "src/sql/cost_savings.sql",
+        from_timestamp,
+        to_timestamp,
+        timezone.tz,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
+    )
+    .fetch_one(&app_state.db)
+    .await?;
 
-    let kpi = KpiResult {
-        value: cost_saving_query_results.cost_savings.unwrap_or(0.0),
+    let mut kpi = KpiResult {
+        value: cost_saving_query_results.cost_savings.unwrap_or(0.0) * factor,
         name: String::from("cost_savings"),
-        unit: Some(String::from("EUR")),
+        unit: Some(conversion.unit_or("EUR")),
         from_timestamp: timestamp_filter.to.unwrap(),
         to_timestamp: timestamp_filter.to.unwrap(),
+        flagged_datapoints: quality_filter
+            .flagged_datapoints(
+                &app_state.db,
+                &timestamp_filter,
+                &[KpiInput::Production, KpiInput::MarketPrice],
+            )
+            .await?,
     };
+    timezone
+        .localize(
+            &app_state.db,
+            [&mut kpi.from_timestamp, &mut kpi.to_timestamp],
+        )
+        .await?;
     Ok(Json(kpi))
 }
 
+/// convert emissions, which are calculated in kgco2eq, into the requested unit
+fn convert_emissions(
+    emissions: &mut [EmissionsByCarrier],
+    factor: f64,
+    conversion: &UnitConversion,
+) {
+    for emission in emissions.iter_mut() {
+        emission.value = emission.value.map(|x| x * factor);
+        emission.unit = Some(conversion.unit_or("kgco2eq"));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn get_scope_one_emissions(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     Query(resampling): Query<Resampling>,
     Query(ef_source): Query<EmissionFactorSource>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
     State(app_state): State<AppState>,
     uri: Uri,
 ) -> Result<Json<Vec<EmissionsByCarrier>>> {
     let ef_source = ef_source.get_source_or_default(&app_state.db).await?;
+    timezone.validate(&app_state.db).await?;
+    let factor = conversion.factor("kgco2eq", &app_state.db).await?;
     let interval = resampling.map_interval()?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup =
+        Rollup::for_query(Some(&interval), &timestamp_filter, &timezone).map(|x| x.interval());
     let mut cache = Cache::new(&app_state.config.redis_url).await.unwrap();
     let key = format!("{}", uri);
     let cached_result = cache.get(&key).await;
@@ -302,16 +489,29 @@ pub async fn get_scope_one_emissions(
             if !resampling.validate_interval() {
                 return Err(ApiError::InvalidInterval);
             }
-            let production_record = sqlx::query_file_as!(
+            let mut production_record = sqlx::query_file_as!(
                 EmissionsByCarrier,
                 "src/sql/scope_one_emissions.sql",
                 from_timestamp,
                 to_timestamp,
                 interval,
-                ef_source
+                ef_source,
+                timezone.tz,
+                rollup,
+                quality_filter.exclude_flagged,
+                timestamp_filter.as_of,
             )
             .fetch_all(&app_state.db)
             .await?;
+            convert_emissions(&mut production_record, factor, &conversion);
+            timezone
+                .localize(
+                    &app_state.db,
+                    production_record
+                        .iter_mut()
+                        .filter_map(|x| x.bucket.as_mut()),
+                )
+                .await?;
             let serialized = serde_json::to_string(&production_record).unwrap();
             cache.set(&key, &serialized, 5 * 60).await.unwrap();
             Ok(Json(production_record))
@@ -319,22 +519,30 @@ pub async fn get_scope_one_emissions(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_scope_two_emissions(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     Query(resampling): Query<Resampling>,
     Query(emission_factor_source): Query<EmissionFactorSource>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
     State(app_state): State<AppState>,
     uri: Uri,
 ) -> Result<Json<Vec<EmissionsByCarrier>>> {
     if !resampling.validate_interval() {
         return Err(ApiError::InvalidInterval);
     }
+    timezone.validate(&app_state.db).await?;
     let ef_source = emission_factor_source
         .get_source_or_default(&app_state.db)
         .await?;
+    let factor = conversion.factor("kgco2eq", &app_state.db).await?;
     let pg_resampling_interval = resampling.map_interval()?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup = Rollup::for_query(Some(&pg_resampling_interval), &timestamp_filter, &timezone)
+        .map(|x| x.interval());
 
     let mut cache = Cache::new(&app_state.config.redis_url).await.unwrap();
     let key = format!("{}", uri);
@@ -345,16 +553,29 @@ pub async fn get_scope_two_emissions(
             Ok(Json(deserialized))
         }
         Err(_) => {
-            let consumption_record = sqlx::query_file_as!(
+            let mut consumption_record = sqlx::query_file_as!(
                 EmissionsByCarrier,
                 "src/sql/scope_two_emissions.sql",
                 from_timestamp,
                 to_timestamp,
                 pg_resampling_interval,
-                ef_source
+                ef_source,
+                timezone.tz,
+                rollup,
+                quality_filter.exclude_flagged,
+                timestamp_filter.as_of,
             )
             .fetch_all(&app_state.db)
             .await?;
+            convert_emissions(&mut consumption_record, factor, &conversion);
+            timezone
+                .localize(
+                    &app_state.db,
+                    consumption_record
+                        .iter_mut()
+                        .filter_map(|x| x.bucket.as_mut()),
+                )
+                .await?;
             let serialized = serde_json::to_string(&consumption_record).unwrap();
             cache.set(&key, &serialized, 5 * 60).await.unwrap();
             Ok(Json(consumption_record))
@@ -366,19 +587,26 @@ pub async fn get_scope_two_emissions(
 */
 pub async fn get_total_co2_emissions(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     State(app_state): State<AppState>,
     Query(resampling): Query<Resampling>,
     Query(emission_factor_source): Query<EmissionFactorSource>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
 ) -> Result<Json<KpiResult>, ApiError> {
     if !resampling.validate_interval() {
         return Err(ApiError::InvalidInterval);
     }
+    timezone.validate(&app_state.db).await?;
     let ef_source = emission_factor_source
         .get_source_or_default(&app_state.db)
         .await?;
+    let factor = conversion.factor("kgco2eq", &app_state.db).await?;
     let pg_resampling_interval = resampling.map_interval()?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup = Rollup::for_query(Some(&pg_resampling_interval), &timestamp_filter, &timezone)
+        .map(|x| x.interval());
 
     let scope_two = sqlx::query_file_as!(
         EmissionsByCarrier,
@@ -386,7 +614,11 @@ pub async fn get_total_co2_emissions(
         from_timestamp,
         to_timestamp,
         pg_resampling_interval,
-        ef_source
+        ef_source,
+        timezone.tz,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
     )
     .fetch_all(&app_state.db)
     .await?;
@@ -397,7 +629,11 @@ pub async fn get_total_co2_emissions(
         from_timestamp,
         to_timestamp,
         pg_resampling_interval,
-        ef_source
+        ef_source,
+        timezone.tz,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
     )
     .fetch_all(&app_state.db)
     .await?;
@@ -412,42 +648,84 @@ pub async fn get_total_co2_emissions(
 
     let total_emissions = sum_scope_one + sum_scope_two;
 
-    let kpi_result = KpiResult {
-        value: total_emissions,
+    let mut kpi_result = KpiResult {
+        value: total_emissions * factor,
         name: String::from("total_co2_emissions"),
-        unit: Some(String::from("kgco2eq")),
+        unit: Some(conversion.unit_or("kgco2eq")),
         from_timestamp: timestamp_filter.from.unwrap(),
         to_timestamp: timestamp_filter.to.unwrap(),
+        flagged_datapoints: quality_filter
+            .flagged_datapoints(
+                &app_state.db,
+                &timestamp_filter,
+                &[
+                    KpiInput::LocalProduction,
+                    KpiInput::GridReference,
+                    KpiInput::GridMix,
+                ],
+            )
+            .await?,
     };
+    timezone
+        .localize(
+            &app_state.db,
+            [&mut kpi_result.from_timestamp, &mut kpi_result.to_timestamp],
+        )
+        .await?;
     Ok(Json(kpi_result))
 }
 
 pub async fn get_total_grid_electricity_cost(
     Query(timestamp_filter): Query<TimestampFilter>,
+    Query(quality_filter): Query<QualityFilter>,
     State(app_state): State<AppState>,
     Query(resampling): Query<Resampling>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
 ) -> Result<Json<KpiResult>, ApiError> {
     if !resampling.validate_interval() {
         return Err(ApiError::InvalidInterval);
     }
+    timezone.validate(&app_state.db).await?;
     let pg_resampling_interval = resampling.map_interval()?;
+    let factor = conversion.factor("EUR", &app_state.db).await?;
+    validate_price_unit(&app_state.db, "smard_market_price").await?;
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let rollup = Rollup::for_query(Some(&pg_resampling_interval), &timestamp_filter, &timezone)
+        .map(|x| x.interval());
 
     let total_cost_kpi = sqlx::query_file!(
         "src/sql/total_grid_electricity_cost.sql",
         from_timestamp,
         to_timestamp,
-        pg_resampling_interval
+        pg_resampling_interval,
+        timezone.tz,
+        rollup,
+        quality_filter.exclude_flagged,
+        timestamp_filter.as_of,
     )
     .fetch_one(&app_state.db)
     .await?;
-    let kpi_result = KpiResult {
-        value: total_cost_kpi.value.unwrap_or(0.0),
+    let mut kpi_result = KpiResult {
+        value: total_cost_kpi.value.unwrap_or(0.0) * factor,
         name: String::from("total_grid_electricity_cost"),
-        unit: Some(String::from("EUR")),
+        unit: Some(conversion.unit_or("EUR")),
         from_timestamp: timestamp_filter.from.unwrap(),
         to_timestamp: timestamp_filter.to.unwrap(),
+        flagged_datapoints: quality_filter
+            .flagged_datapoints(
+                &app_state.db,
+                &timestamp_filter,
+                &[KpiInput::GridReference, KpiInput::MarketPrice],
+            )
+            .await?,
     };
+    timezone
+        .localize(
+            &app_state.db,
+            [&mut kpi_result.from_timestamp, &mut kpi_result.to_timestamp],
+        )
+        .await?;
     Ok(Json(kpi_result))
 }
@@ -1,7 +1,7 @@
 use crate::error::ApiError;
 
 use crate::infrastructure::AppState;
-use crate::models::{MetaInput, MetaOutput, MetaRows, Pagination, Result};
+use crate::models::{MetaInput, MetaOutput, MetaRows, Pagination, Result, Unit};
 
 use axum::extract::{Path, Query, State};
 use axum::Json;
@@ -9,7 +9,7 @@ use axum_extra::extract::WithRejection;
 
 use crate::cache::Cache;
 use axum::http::Uri;
-use sqlx::Row;
+use sqlx::{Pool, Postgres, Row};
 use std::string::String;
 
 pub async fn read_meta(
@@ -117,6 +117,14 @@ pub async fn add_meta(
     State(app_state): State<AppState>,
     WithRejection(Json(meta), _): WithRejection<Json<MetaInput>, ApiError>,
 ) -> Result<Json<MetaOutput>, ApiError> {
+    let meta_output = create_meta(&app_state.db, &meta).await?;
+    Ok(Json(meta_output))
+}
+
+/// Insert a new row into `meta`, resolving the carrier name to its id.
+/// Fails with `UnknownUnit` if the unit is not in the unit registry.
+pub async fn create_meta(pool: &Pool<Postgres>, meta: &MetaInput) -> Result<MetaOutput, ApiError> {
+    Unit::fetch(&meta.unit, pool).await?;
     let meta_output: MetaOutput = sqlx::query_as!(
         MetaOutput,
         r"
@@ -149,8 +157,8 @@ pub async fn add_meta(
         meta.description,
         meta.local,
     )
-    .fetch_one(&app_state.db)
+    .fetch_one(pool)
     .await?;
 
-    Ok(Json(meta_output))
+    Ok(meta_output)
 }
@@ -1,7 +1,14 @@
+pub mod admin;
 pub mod config;
 pub mod emission_factor;
+pub mod export;
+pub mod forecast;
 pub mod import;
 pub mod kpi;
 pub mod meta;
+pub mod quality;
+pub mod subscription;
 pub mod timeseries;
+pub mod unit;
 pub mod util;
+pub mod write;
@@ -0,0 +1,153 @@
+use crate::error::ApiError;
+use crate::handlers::timeseries::{
+    fetch_filtered_timeseries_metas, fetch_timeseries_meta, fetch_timeseries_metas,
+};
+use crate::infrastructure::AppState;
+use crate::models::{parse_interval, QualityParams, QualityReport, QualitySummary, Result};
+use crate::models::{SeriesSelection, TimeseriesMeta, TimestampFilter};
+use crate::quality::{QualityAnalyzer, QualityChecks, QualityRules};
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use futures::TryStreamExt;
+use sqlx::postgres::types::PgInterval;
+use sqlx::{Pool, Postgres};
+use time::Duration;
+
+/// fixed length of an interval, months are counted as 30 days
+fn interval_duration(interval: &PgInterval) -> Duration {
+    Duration::days(interval.months as i64 * 30 + interval.days as i64)
+        + Duration::microseconds(interval.microseconds)
+}
+
+fn parse_duration(interval: &str) -> Result<Duration> {
+    let interval = parse_interval(interval).map_err(|_| ApiError::InvalidInterval)?;
+    let duration = interval_duration(&interval);
+    if duration <= Duration::ZERO {
+        return Err(ApiError::InvalidInterval);
+    }
+    Ok(duration)
+}
+
+/// Stream the datapoints of a series within the range through the quality checks.
+async fn check_series(
+    pool: &Pool<Postgres>,
+    metadata: TimeseriesMeta,
+    params: &QualityParams,
+    timestamp_filter: &TimestampFilter,
+) -> Result<QualityReport> {
+    let from_timestamp = timestamp_filter.from.unwrap();
+    let to_timestamp = timestamp_filter.to.unwrap();
+    let stats = sqlx::query!(
+        r#"
+        with neighbours as (
+            select
+                ts.series_timestamp,
+                ts.series_value - lag(ts.series_value) over series as difference,
+                extract(epoch from ts.series_timestamp - lag(ts.series_timestamp) over series)::double precision as distance
+            from ts_as_of(array[$1::integer], $2, $3, $5) ts
+            window series as (order by ts.series_timestamp)
+        )
+        select
+            min(neighbours.series_timestamp) as first_timestamp,
+            max(neighbours.series_timestamp) as last_timestamp,
+            percentile_cont(0.5) within group (order by neighbours.distance) as median_distance,
+            stddev_samp(neighbours.difference) as difference_stddev,
+            (select unit.dimension from unit where unit.name = $4) as dimension
+        from neighbours
+        "#,
+        metadata.id,
+        from_timestamp,
+        to_timestamp,
+        metadata.unit,
+        timestamp_filter.as_of,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let expected_interval = match &params.expected_interval {
+        Some(interval) => Some(parse_duration(interval)?),
+        None => stats
+            .median_distance
+            .filter(|x| *x > 0.0)
+            .map(Duration::seconds_f64),
+    };
+    let Some(expected_interval) = expected_interval else {
+        // fewer than two datapoints, nothing to check against
+        return Ok(QualityReport {
+            meta: metadata,
+            from: stats.first_timestamp,
+            to: stats.last_timestamp,
+            expected_interval_seconds: None,
+            checks: QualityChecks {
+                datapoints: stats.first_timestamp.map(|_| 1).unwrap_or(0),
+                ..Default::default()
+            },
+        });
+    };
+    let physical = matches!(stats.dimension.as_deref(), Some("power") | Some("energy"));
+    let mut analyzer = QualityAnalyzer::new(QualityRules {
+        expected_interval,
+        min: params.min.or(physical.then_some(0.0)),
+        max: params.max,
+        flatline: parse_duration(&params.flatline)?,
+        spike_threshold: stats
+            .difference_stddev
+            .filter(|x| *x > 0.0)
+            .map(|x| x * params.spike_threshold),
+    });
+
+    let mut rows = sqlx::query!(
+        r#"
+        select ts.series_timestamp as "series_timestamp!", ts.series_value as "series_value!"
+        from ts_as_of(array[$1::integer], $2, $3, $4) ts
+        order by ts.series_timestamp
+        "#,
+        metadata.id,
+        from_timestamp,
+        to_timestamp,
+        timestamp_filter.as_of,
+    )
+    .fetch(pool);
+    while let Some(row) = rows.try_next().await? {
+        analyzer.push(row.series_timestamp, row.series_value);
+    }
+
+    Ok(QualityReport {
+        meta: metadata,
+        from: stats.first_timestamp,
+        to: stats.last_timestamp,
+        expected_interval_seconds: Some(expected_interval.as_seconds_f64()),
+        checks: analyzer.finish(),
+    })
+}
+
+/// Report coverage, gaps, duplicates, out of range values, flatlines and spikes of a series.
+pub async fn get_quality_by_identifier(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(params): Query<QualityParams>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+) -> Result<Json<QualityReport>> {
+    let metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let report = check_series(&app_state.db, metadata, &params, &timestamp_filter).await?;
+    Ok(Json(report))
+}
+
+/// Number of quality issues of every series, optionally narrowed down by identifiers or a meta filter.
+pub async fn get_quality_summary(
+    State(app_state): State<AppState>,
+    Query(selection): Query<SeriesSelection>,
+    Query(params): Query<QualityParams>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+) -> Result<Json<Vec<QualitySummary>>> {
+    let metadata = match selection.identifiers() {
+        Some(identifiers) => fetch_timeseries_metas(&app_state.db, &identifiers).await?,
+        None => fetch_filtered_timeseries_metas(&app_state.db, &selection).await?,
+    };
+    let mut summary = vec![];
+    for meta in metadata {
+        let report = check_series(&app_state.db, meta, &params, &timestamp_filter).await?;
+        summary.push(QualitySummary::from(&report));
+    }
+    Ok(Json(summary))
+}
@@ -0,0 +1,179 @@
+use crate::error::ApiError;
+use crate::events::DatapointEvent;
+use crate::handlers::timeseries::{fetch_filtered_timeseries_metas, fetch_timeseries_metas};
+use crate::infrastructure::AppState;
+use crate::models::{DatapointQuality, Result, SeriesSelection, SubscribedDatapoint};
+use crate::models::{SubscriptionParams, TimeseriesMeta};
+
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::{Stream, TryStreamExt};
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+
+/// Sends datapoints of the subscribed series as server-sent events.
+/// The event id is the latest time a sent datapoint was written at, i.e. its `updated_at`, so a
+/// reconnecting client also gets datapoints which were backfilled or corrected in the meantime.
+struct Subscriber {
+    pool: Pool<Postgres>,
+    identifiers: HashMap<i32, String>,
+    sender: mpsc::Sender<Result<Event, Infallible>>,
+    last_written_at: Option<OffsetDateTime>,
+}
+
+impl Subscriber {
+    /// Send the datapoints of `meta_ids` written at or after `written_since`, after `after` and
+    /// within `from` and `to`, in the order they were written. Fails once the client went away.
+    async fn send(
+        &mut self,
+        meta_ids: &[i32],
+        written_since: Option<OffsetDateTime>,
+        after: Option<OffsetDateTime>,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+    ) -> Result<()> {
+        let mut rows = sqlx::query!(
+            r#"
+            select
+                ts.meta_id as "meta_id!",
+                ts.series_timestamp,
+                ts.series_value,
+                ts.quality as "quality: DatapointQuality",
+                ts.updated_at
+            from ts
+            where ts.meta_id = any($1)
+                and ($2::timestamptz is null or ts.updated_at >= $2)
+                and ($3::timestamptz is null or ts.series_timestamp > $3)
+                and ($4::timestamptz is null or ts.series_timestamp >= $4)
+                and ($5::timestamptz is null or ts.series_timestamp <= $5)
+            order by ts.updated_at, ts.series_timestamp, ts.meta_id
+            "#,
+            meta_ids,
+            written_since,
+            after,
+            from,
+            to,
+        )
+        .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            let last_written_at = self
+                .last_written_at
+                .map_or(row.updated_at, |x| x.max(row.updated_at));
+            self.last_written_at = Some(last_written_at);
+            let datapoint = SubscribedDatapoint {
+                identifier: self.identifiers[&row.meta_id].clone(),
+                timestamp: row.series_timestamp,
+                value: row.series_value,
+                quality: row.quality,
+            };
+            let event = Event::default()
+                .event("datapoint")
+                .id(last_written_at
+                    .format(&Rfc3339)
+                    .map_err(anyhow::Error::from)?)
+                .json_data(datapoint)
+                .map_err(anyhow::Error::from)?;
+            self.sender
+                .send(Ok(event))
+                .await
+                .map_err(anyhow::Error::from)?;
+        }
+        Ok(())
+    }
+
+    /// Replay the datapoints written at or after `written_since`, or else the ones after `since`,
+    /// and push new ones as they are written.
+    async fn run(
+        mut self,
+        mut receiver: broadcast::Receiver<DatapointEvent>,
+        written_since: Option<OffsetDateTime>,
+        since: Option<OffsetDateTime>,
+    ) -> Result<()> {
+        let meta_ids = self.identifiers.keys().copied().collect::<Vec<_>>();
+        if written_since.is_some() {
+            self.send(&meta_ids, written_since, None, None, None)
+                .await?;
+        } else if since.is_some() {
+            self.send(&meta_ids, None, since, None, None).await?;
+        }
+        // events published while replaying are still queued in the receiver
+        let subscribed_at = OffsetDateTime::now_utc();
+        loop {
+            let received = tokio::select! {
+                _ = self.sender.closed() => return Ok(()),
+                received = receiver.recv() => received,
+            };
+            match received {
+                Ok(event) if self.identifiers.contains_key(&event.meta_id) => {
+                    self.send(
+                        &[event.meta_id],
+                        None,
+                        None,
+                        Some(event.from),
+                        Some(event.to),
+                    )
+                    .await?;
+                }
+                Ok(_) => {}
+                // missed events are caught up with by everything written since the last datapoint sent
+                Err(RecvError::Lagged(_)) => {
+                    let written_since = self.last_written_at.unwrap_or(subscribed_at);
+                    self.send(&meta_ids, Some(written_since), None, None, None)
+                        .await?;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Push datapoints of the series selected by identifiers or a meta filter as server-sent events
+/// once they are written by `add_timeseries` or an import.
+/// Datapoints after `since`, or the ones written since the `Last-Event-ID` header of a reconnecting
+/// client, are sent first. Datapoints written at that very time are sent again, so none is lost.
+pub async fn subscribe_timeseries(
+    State(app_state): State<AppState>,
+    Query(selection): Query<SeriesSelection>,
+    Query(params): Query<SubscriptionParams>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let metadata: Vec<TimeseriesMeta> = match selection.identifiers() {
+        Some(identifiers) => fetch_timeseries_metas(&app_state.db, &identifiers).await?,
+        None if selection.has_filter() => {
+            fetch_filtered_timeseries_metas(&app_state.db, &selection).await?
+        }
+        None => return Err(ApiError::MissingSeriesSelection),
+    };
+    // ids are only ever set by this endpoint, anything else is ignored
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| OffsetDateTime::parse(x, &Rfc3339).ok());
+
+    // subscribe before replaying, so datapoints written in between are not missed
+    let receiver = app_state.events.subscribe();
+    let (sender, events) = mpsc::channel(64);
+    let subscriber = Subscriber {
+        pool: app_state.db,
+        identifiers: metadata.into_iter().map(|m| (m.id, m.identifier)).collect(),
+        sender,
+        last_written_at: None,
+    };
+    tokio::spawn(async move {
+        // the client going away ends the subscription as well
+        if let Err(e) = subscriber.run(receiver, last_event_id, params.since).await {
+            tracing::debug!("subscription ended: {}", e);
+        }
+    });
+    let stream = futures::stream::unfold(events, |mut events| async move {
+        events.recv().await.map(|event| (event, events))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -1,23 +1,41 @@
+use crate::cache::Cache;
+use crate::columnar::{datapoints_batch, ColumnarFormat};
+use crate::downsample::Downsampler;
+use crate::error::ApiError;
+use crate::events::DatapointEvent;
+use crate::handlers::meta::create_meta;
 use crate::infrastructure::AppState;
+use crate::models::DatapointQuality;
 use crate::models::TimeseriesMeta;
+use crate::models::{AddTimeseriesResponse, IngestionOptions, IngestionSummary, OnConflict};
+use crate::models::{Aggregate, AlignedRow, AlignedTimeseries, Fill, Rollup, SeriesSelection};
+use crate::models::{CorrectTimeseriesBody, CorrectTimeseriesResponse, DeleteTimeseriesResponse};
+use crate::models::{Cursor, StreamMode, Timeseries, TimestampFilter, Timezone};
 use crate::models::{Datapoint, ResampledDatapoint, ResampledTimeseries, Resampling, Result};
-use crate::models::{NewDatapoint, TimeseriesBody};
-use crate::models::{Timeseries, TimestampFilter};
+use crate::models::{DownsampleParams, EnergyDatapoint};
+use crate::models::{EnergyParams, EnergyTimeseries, IntegrationMethod, LatestDatapoint};
+use crate::models::{Histogram, HistogramBin, Percentile, StatsParams, TimeseriesStats};
+use crate::models::{MetaDefaults, NewDatapoint, NewTimeseriesBody, UpsertedDatapoint};
+use crate::models::{RejectedDatapoint, RejectionReason, UnitConversion};
 
+use crate::handlers::util::channel_response;
 use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use sqlx::{Pool, Postgres, Row};
+use time::OffsetDateTime;
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::string::String;
 
-/// timeseries values for specific metadata and a given interval
-pub async fn resample_timeseries_by_identifier(
-    State(app_state): State<AppState>,
-    Path(identifier): Path<String>,
-    Query(resampling): Query<Resampling>,
-    Query(timestamp_filter): Query<TimestampFilter>,
-) -> Result<Json<ResampledTimeseries>> {
-    let pg_resampling_interval = resampling.map_interval()?;
-    let metadata = sqlx::query_as::<_, TimeseriesMeta>(
+/// Look up the metadata of a single timeseries, failing with `NotFound` for unknown identifiers.
+pub async fn fetch_timeseries_meta(
+    pool: &Pool<Postgres>,
+    identifier: &str,
+) -> Result<TimeseriesMeta> {
+    sqlx::query_as::<_, TimeseriesMeta>(
         r#"
         select
             meta.id as id,
@@ -32,98 +50,896 @@ pub async fn resample_timeseries_by_identifier(
         where meta.identifier = $1"#,
     )
     .bind(identifier)
-    .fetch_one(&app_state.db)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+/// Look up the metadata of several timeseries in the order of `identifiers`.
+/// Fails with `NotFound` if any of the identifiers is unknown.
+pub async fn fetch_timeseries_metas(
+    pool: &Pool<Postgres>,
+    identifiers: &[String],
+) -> Result<Vec<TimeseriesMeta>> {
+    let metadata = sqlx::query_as!(
+        TimeseriesMeta,
+        r#"
+        select meta.id as id, identifier, unit, energy_carrier.name as carrier, consumption, description, local
+        from meta left join energy_carrier on meta.carrier = energy_carrier.id
+        where meta.identifier IN (select * from unnest($1::text[]))"#,
+        identifiers,
+    )
+    .fetch_all(pool)
     .await?;
 
+    identifiers
+        .iter()
+        .map(|identifier| {
+            metadata
+                .iter()
+                .find(|m| m.identifier.to_lowercase() == identifier.to_lowercase())
+                .cloned()
+                .ok_or(ApiError::NotFound)
+        })
+        .collect()
+}
+
+/// Look up the metadata of all timeseries matching the meta filter of `selection`, ordered by id.
+pub async fn fetch_filtered_timeseries_metas(
+    pool: &Pool<Postgres>,
+    selection: &SeriesSelection,
+) -> Result<Vec<TimeseriesMeta>> {
+    let metadata = sqlx::query_as!(
+        TimeseriesMeta,
+        r#"
+        select meta.id as id, identifier, unit, energy_carrier.name as carrier, consumption, description, local
+        from meta left join energy_carrier on meta.carrier = energy_carrier.id
+        where ($1::text is null or energy_carrier.name = $1)
+            and ($2::bool is null or meta.local = $2)
+            and ($3::bool is null or meta.consumption = $3)
+        order by meta.id"#,
+        selection.carrier,
+        selection.local,
+        selection.consumption,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(metadata)
+}
+
+/// timeseries values for specific metadata and a given interval
+/// every aggregate requested with `agg` is computed per interval
+pub async fn resample_timeseries_by_identifier(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(resampling): Query<Resampling>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
+) -> Result<Json<ResampledTimeseries>> {
+    let aggregates = resampling.aggregates()?;
+    timezone.validate(&app_state.db).await?;
+    let mut metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let factor = conversion.factor(&metadata.unit, &app_state.db).await?;
+    metadata.unit = conversion.unit_or(&metadata.unit);
+
+    let mut datapoints: Vec<ResampledDatapoint> = resample_series(
+        &app_state.db,
+        &[(metadata.id, factor)],
+        &resampling,
+        &aggregates,
+        &timestamp_filter,
+        &timezone,
+    )
+    .await?
+    .into_iter()
+    .map(|(_, datapoint)| datapoint)
+    .collect();
+    timezone
+        .localize(
+            &app_state.db,
+            datapoints.iter_mut().filter_map(|x| x.bucket.as_mut()),
+        )
+        .await?;
+
+    let response = ResampledTimeseries {
+        datapoints,
+        meta: metadata,
+    };
+    Ok(Json(response))
+}
+
+/// Resample several timeseries into the same buckets.
+/// `series` holds the meta id of every series with the factor its values are converted with.
+/// Returns the meta id of the series with each resampled datapoint, ordered by bucket.
+/// Buckets are computed from a rollup instead of the raw datapoints if `Rollup::for_query` finds one
+/// and all aggregates can be combined from rollups, the edges of the range outside of its complete
+/// buckets are read from the raw datapoints.
+async fn resample_series(
+    pool: &Pool<Postgres>,
+    series: &[(i32, f64)],
+    resampling: &Resampling,
+    aggregates: &[Aggregate],
+    timestamp_filter: &TimestampFilter,
+    timezone: &Timezone,
+) -> Result<Vec<(i32, ResampledDatapoint)>> {
+    let pg_resampling_interval = resampling.map_interval()?;
+    let (meta_ids, factors): (Vec<i32>, Vec<f64>) = series.iter().copied().unzip();
     let timestamp_from = timestamp_filter.from.unwrap();
     let timestamp_to = timestamp_filter.to.unwrap();
+    let mut fill_from = timestamp_from;
+    let mut fill_to = timestamp_to;
+
+    if resampling.fill != Fill::None {
+        // only fill gaps within the series, otherwise the default range would produce buckets since 1970
+        let bounds = sqlx::query!(
+            r#"
+            select
+                min(ts.series_timestamp) as first_timestamp,
+                max(ts.series_timestamp) as last_timestamp
+            from ts_as_of($1, $2, $3, $4) ts
+            "#,
+            &meta_ids,
+            timestamp_from,
+            timestamp_to,
+            timestamp_filter.as_of,
+        )
+        .fetch_one(pool)
+        .await?;
+        match (bounds.first_timestamp, bounds.last_timestamp) {
+            (Some(first), Some(last)) => {
+                fill_from = first;
+                fill_to = last;
+            }
+            _ => return Ok(vec![]),
+        }
+    }
 
-    let datapoints = sqlx::query_as!(
-        ResampledDatapoint,
+    // aggregates are parsed from a fixed set, so their sql can be formatted into the query
+    let rollup_columns = aggregates
+        .iter()
+        .map(|x| x.rollup_sql("source", "conversion.factor"))
+        .collect::<Option<Vec<_>>>();
+    let rollup = Rollup::for_query(Some(&pg_resampling_interval), timestamp_filter, timezone);
+    let interior = rollup.and_then(|x| x.interior(timestamp_filter));
+    // rollup buckets are named like raw datapoints, so `bucket` in the query only refers to its alias
+    let (source, measured_count, aggregate_columns) = match (rollup, rollup_columns) {
+        // the complete rollup buckets and the raw datapoints before and after them
+        (Some(rollup), Some(columns)) => (
+            format!(
+                r#"(
+                select r.meta_id, r.bucket as series_timestamp, r.sum_value, r.count_value, r.min_value, r.max_value
+                from {} r
+                where r.meta_id = any($1) and r.bucket >= $10 and r.bucket < $11
+                union all
+                select ts.meta_id, ts.series_timestamp, ts.series_value, 1, ts.series_value, ts.series_value
+                from ts
+                where ts.meta_id = any($1)
+                    and ts.series_timestamp between $3 and $4
+                    and (ts.series_timestamp < $10 or ts.series_timestamp >= $11)
+                )"#,
+                rollup.view()
+            ),
+            "sum(source.count_value)::bigint",
+            columns,
+        ),
+        _ => (
+            String::from("ts_as_of($1::integer[], $3, $4, $9)"),
+            "count(source.series_value)",
+            aggregates
+                .iter()
+                .map(|x| {
+                    x.sql(
+                        "source.series_value * conversion.factor",
+                        "source.series_timestamp",
+                    )
+                })
+                .collect(),
+        ),
+    };
+    let aggregate_columns = aggregates
+        .iter()
+        .zip(aggregate_columns)
+        .map(|(x, sql)| format!("{} as {}", resampling.fill.apply(&sql), x.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let rows = sqlx::query(&format!(
         r#"
         select
-            time_bucket($2::interval, ts.series_timestamp) as bucket,
-            avg(ts.series_value) as mean_value
-        from ts
-        where ts.meta_id = $1
-            and ts.series_timestamp >= $3
-            and ts.series_timestamp <= $4
-        group by bucket
-        order by bucket
+            {} as bucket,
+            source.meta_id,
+            {} as measured_count,
+            {}
+        from {} as source
+            join unnest($1::integer[], $5::double precision[]) as conversion(meta_id, factor)
+                on source.meta_id = conversion.meta_id
+        where source.series_timestamp >= $3
+            and source.series_timestamp <= $4
+        group by bucket, source.meta_id
+        order by bucket, source.meta_id
+        "#,
+        resampling.fill.bucket_sql(
+            "$2::interval",
+            "source.series_timestamp",
+            "$6::text",
+            "$7",
+            "$8"
+        ),
+        measured_count,
+        aggregate_columns,
+        source,
+    ))
+    .bind(&meta_ids)
+    .bind(pg_resampling_interval)
+    .bind(timestamp_from)
+    .bind(timestamp_to)
+    .bind(&factors)
+    .bind(&timezone.tz)
+    .bind(fill_from)
+    .bind(fill_to)
+    .bind(timestamp_filter.as_of)
+    .bind(interior.map(|x| x.0))
+    .bind(interior.map(|x| x.1))
+    .fetch_all(pool)
+    .await?;
+
+    let datapoints = rows
+        .iter()
+        .map(|row| {
+            let values = aggregates
+                .iter()
+                .enumerate()
+                .map(|(i, x)| Ok((x.name(), row.try_get::<Option<f64>, _>(i + 3)?)))
+                .collect::<Result<BTreeMap<_, _>, sqlx::Error>>()?;
+            // gap filled buckets have no count at all
+            let measured_count: Option<i64> = row.try_get("measured_count")?;
+            let datapoint = ResampledDatapoint {
+                bucket: row.try_get("bucket")?,
+                mean_value: values.get("mean").copied().flatten(),
+                values,
+                filled: measured_count.unwrap_or(0) == 0,
+            };
+            Ok((row.try_get("meta_id")?, datapoint))
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()?;
+    Ok(datapoints)
+}
+
+/// Resample several timeseries, selected by identifiers or a meta filter, into one table.
+/// Every row holds a bucket and one value per series in the order of `meta`.
+pub async fn get_aligned_timeseries(
+    State(app_state): State<AppState>,
+    Query(selection): Query<SeriesSelection>,
+    Query(resampling): Query<Resampling>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+    Query(conversion): Query<UnitConversion>,
+    Query(timezone): Query<Timezone>,
+) -> Result<Json<AlignedTimeseries>> {
+    timezone.validate(&app_state.db).await?;
+    let aggregate = match resampling.aggregates()?.as_slice() {
+        [aggregate] => *aggregate,
+        _ => {
+            return Err(ApiError::InvalidAggregate(
+                resampling.agg.clone().unwrap_or_default(),
+            ))
+        }
+    };
+    let mut metadata = match selection.identifiers() {
+        Some(identifiers) => fetch_timeseries_metas(&app_state.db, &identifiers).await?,
+        None if selection.has_filter() => {
+            fetch_filtered_timeseries_metas(&app_state.db, &selection).await?
+        }
+        None => return Err(ApiError::MissingSeriesSelection),
+    };
+
+    let mut series = vec![];
+    for meta in metadata.iter_mut() {
+        series.push((meta.id, conversion.factor(&meta.unit, &app_state.db).await?));
+        meta.unit = conversion.unit_or(&meta.unit);
+    }
+    let datapoints = resample_series(
+        &app_state.db,
+        &series,
+        &resampling,
+        std::slice::from_ref(&aggregate),
+        &timestamp_filter,
+        &timezone,
+    )
+    .await?;
+
+    // datapoints are ordered by bucket, so every bucket is a contiguous run
+    let mut rows: Vec<AlignedRow> = vec![];
+    for (meta_id, datapoint) in datapoints {
+        let Some(bucket) = datapoint.bucket else {
+            continue;
+        };
+        if rows.last().map(|r| r.bucket) != Some(bucket) {
+            rows.push(AlignedRow {
+                bucket,
+                values: vec![None; series.len()],
+            });
+        }
+        // only a single aggregate was requested
+        let value = datapoint.values.into_values().next().flatten();
+        let row = rows.last_mut().unwrap();
+        for (i, (id, _)) in series.iter().enumerate() {
+            if *id == meta_id {
+                row.values[i] = value;
+            }
+        }
+    }
+
+    timezone
+        .localize(&app_state.db, rows.iter_mut().map(|x| &mut x.bucket))
+        .await?;
+
+    Ok(Json(AlignedTimeseries {
+        meta: metadata,
+        rows,
+    }))
+}
+
+/// The latest datapoint of every series selected by identifiers or a meta filter, all series without a selection.
+/// Each series is looked up with a backward scan of the `(meta_id, series_timestamp)` index instead of reading its datapoints.
+pub async fn get_latest_datapoints(
+    State(app_state): State<AppState>,
+    Query(selection): Query<SeriesSelection>,
+) -> Result<Json<Vec<LatestDatapoint>>> {
+    let metadata = match selection.identifiers() {
+        Some(identifiers) => fetch_timeseries_metas(&app_state.db, &identifiers).await?,
+        None => fetch_filtered_timeseries_metas(&app_state.db, &selection).await?,
+    };
+    let meta_ids = metadata.iter().map(|m| m.id).collect::<Vec<_>>();
+    let rows = sqlx::query!(
+        r#"
+        select
+            series.meta_id as "meta_id!",
+            latest.id,
+            latest.series_timestamp,
+            latest.series_value,
+            latest.quality as "quality: DatapointQuality",
+            latest.created_at,
+            latest.updated_at,
+            extract(epoch from now() - latest.series_timestamp)::double precision as "age_seconds!"
+        from unnest($1::integer[]) as series(meta_id)
+            cross join lateral (
+                select * from ts
+                where ts.meta_id = series.meta_id
+                order by ts.series_timestamp desc
+                limit 1
+            ) as latest
+        "#,
+        &meta_ids,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    let latest = metadata
+        .into_iter()
+        .map(|meta| {
+            let row = rows.iter().find(|x| x.meta_id == meta.id);
+            LatestDatapoint {
+                meta,
+                datapoint: row.map(|x| Datapoint {
+                    id: x.id,
+                    timestamp: x.series_timestamp,
+                    value: x.series_value,
+                    quality: x.quality,
+                    created_at: x.created_at,
+                    updated_at: x.updated_at,
+                }),
+                age_seconds: row.map(|x| x.age_seconds),
+            }
+        })
+        .collect();
+    Ok(Json(latest))
+}
+
+/// energy in kWh of a power series per interval
+/// the integration is done by the `ts_energy` database function which the kpis are built on as well
+pub async fn get_energy_by_identifier(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(resampling): Query<Resampling>,
+    Query(params): Query<EnergyParams>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+    Query(timezone): Query<Timezone>,
+) -> Result<Json<EnergyTimeseries>> {
+    let pg_resampling_interval = resampling.map_interval()?;
+    timezone.validate(&app_state.db).await?;
+    let metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+
+    let factor = sqlx::query_scalar!("select power_unit_factor($1)", metadata.unit)
+        .fetch_one(&app_state.db)
+        .await?;
+    if factor.is_none() {
+        return Err(ApiError::NotAPowerUnit(metadata.unit));
+    }
+    // rollups only hold the rectangle integration
+    let rollup = match params.method {
+        IntegrationMethod::Rectangle => {
+            Rollup::for_query(Some(&pg_resampling_interval), &timestamp_filter, &timezone)
+        }
+        IntegrationMethod::Trapezoid => None,
+    };
+
+    let mut datapoints = sqlx::query_as!(
+        EnergyDatapoint,
+        r#"
+        select
+            time_bucket($2::interval, energy.series_timestamp, $6::text) as "bucket!",
+            coalesce(sum(energy.energy), 0) as "value!"
+        from ts_energy_rollup(array[$1::integer], $3, $4, $7, $5, as_of => $8) energy
+        group by 1
+        order by 1
         "#,
         metadata.id,
         pg_resampling_interval,
-        timestamp_from,
-        timestamp_to,
+        timestamp_filter.from.unwrap(),
+        timestamp_filter.to.unwrap(),
+        params.method.name(),
+        timezone.tz,
+        rollup.map(|x| x.interval()),
+        timestamp_filter.as_of,
     )
     .fetch_all(&app_state.db)
     .await?;
+    timezone
+        .localize(&app_state.db, datapoints.iter_mut().map(|x| &mut x.bucket))
+        .await?;
 
-    let response = ResampledTimeseries {
+    Ok(Json(EnergyTimeseries {
+        total: datapoints.iter().map(|x| x.value).sum(),
         datapoints,
         meta: metadata,
-    };
-    Ok(Json(response))
+        method: params.method,
+        unit: String::from("kWh"),
+    }))
 }
 
 /// Get all timeseries values for specific metadata
+/// Large ranges can either be paginated with `after` and `limit` or streamed with `stream=true`.
+/// Clients accepting Arrow or Parquet get the datapoints of the page in that format, see `columnar`.
 pub async fn get_timeseries_by_identifier(
     State(app_state): State<AppState>,
     Path(identifier): Path<String>,
     Query(timestamp_filter): Query<TimestampFilter>,
-) -> Result<Json<Timeseries>> {
+    Query(cursor): Query<Cursor>,
+    Query(stream_mode): Query<StreamMode>,
+    Query(conversion): Query<UnitConversion>,
+    headers: HeaderMap,
+) -> Result<Response> {
     let from_timestamp = timestamp_filter.from.unwrap();
     let to_timestamp = timestamp_filter.to.unwrap();
+    let columnar = ColumnarFormat::from_headers(&headers);
     // we do the join in the backend here
     // this hits the database twice, but we avoid a branch and can simplify the code
     // additionally we can always return matching metadata even if query param filters lead to empty result set
-    let metadata = sqlx::query_as::<_,TimeseriesMeta>(
-        r#"
-        select meta.id as id, identifier, unit, energy_carrier.name as carrier, consumption, description, local
-        from meta left join energy_carrier on meta.carrier = energy_carrier.id
-        where meta.identifier = $1"#,
-    ).bind(identifier)
-    .fetch_one(&app_state.db)
-    .await?;
+    let mut metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let factor = conversion.factor(&metadata.unit, &app_state.db).await?;
+    metadata.unit = conversion.unit_or(&metadata.unit);
+
+    if stream_mode.stream && columnar.is_none() {
+        return stream_timeseries(app_state.db, metadata, factor, timestamp_filter, cursor);
+    }
+
     let rows = sqlx::query_as!(
         Datapoint,
         r#"
         select
-            ts.id,
-            ts.series_timestamp as "timestamp",
-            ts.series_value as "value",
-            ts.created_at created_at,
-            ts.updated_at updated_at
-        from ts
-        where ts.meta_id = $1
-        and ts.series_timestamp >= $2
-        and ts.series_timestamp <= $3
+            ts.id as "id!",
+            ts.series_timestamp as "timestamp!",
+            ts.series_value * $6 as "value!",
+            ts.quality as "quality!: DatapointQuality",
+            ts.created_at as "created_at!",
+            ts.updated_at as "updated_at!"
+        from ts_as_of(array[$1::integer], $2, $3, $7) ts
+        where ($4::timestamptz is null or ts.series_timestamp > $4)
+        order by ts.series_timestamp
+        limit $5
         "#,
         metadata.id,
         from_timestamp,
         to_timestamp,
+        cursor.after,
+        cursor.limit,
+        factor,
+        timestamp_filter.as_of,
     )
     .fetch_all(&app_state.db)
     .await?;
+    if let Some(format) = columnar {
+        return format.response(&datapoints_batch(&metadata, &rows)?);
+    }
+    let next_cursor = match cursor.limit {
+        Some(limit) if rows.len() as i64 == limit => rows.last().map(|x| x.timestamp),
+        _ => None,
+    };
     let response = Timeseries {
         datapoints: rows,
         meta: metadata,
+        next_cursor,
     };
-    Ok(Json(response))
+    Ok(Json(response).into_response())
 }
 
+/// Write the same json document as `get_timeseries_by_identifier` without collecting all rows in memory.
+fn stream_timeseries(
+    pool: Pool<Postgres>,
+    metadata: TimeseriesMeta,
+    factor: f64,
+    timestamp_filter: TimestampFilter,
+    cursor: Cursor,
+) -> Result<Response> {
+    let header = format!(
+        r#"{{"meta":{},"datapoints":["#,
+        serde_json::to_string(&metadata).map_err(anyhow::Error::from)?
+    );
+    Ok(channel_response(
+        "application/json",
+        |mut sender| async move {
+            if sender.send(Ok(header)).await.is_err() {
+                return;
+            }
+            let mut rows = sqlx::query_as!(
+                Datapoint,
+                r#"
+                select
+                    ts.id as "id!",
+                    ts.series_timestamp as "timestamp!",
+                    ts.series_value * $6 as "value!",
+                    ts.quality as "quality!: DatapointQuality",
+                    ts.created_at as "created_at!",
+                    ts.updated_at as "updated_at!"
+                from ts_as_of(array[$1::integer], $2, $3, $7) ts
+                where ($4::timestamptz is null or ts.series_timestamp > $4)
+                order by ts.series_timestamp
+                limit $5
+                "#,
+                metadata.id,
+                timestamp_filter.from.unwrap(),
+                timestamp_filter.to.unwrap(),
+                cursor.after,
+                cursor.limit,
+                factor,
+                timestamp_filter.as_of,
+            )
+            .fetch(&pool);
+
+            let mut separator = "";
+            while let Some(row) = rows.next().await {
+                let chunk = row.map_err(ApiError::from).and_then(|datapoint| {
+                    let json = serde_json::to_string(&datapoint).map_err(anyhow::Error::from)?;
+                    Ok(format!("{}{}", separator, json))
+                });
+                let failed = chunk.is_err();
+                if sender.send(chunk).await.is_err() || failed {
+                    return;
+                }
+                separator = ",";
+            }
+            let _ = sender.send(Ok(String::from("]}"))).await;
+        },
+    ))
+}
+
+/// Reduce a timeseries to at most `points` datapoints while keeping its visual shape.
+/// Rows are streamed from the database, only the datapoints of the current buckets are held in memory.
+pub async fn downsample_timeseries_by_identifier(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(params): Query<DownsampleParams>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+    Query(conversion): Query<UnitConversion>,
+) -> Result<Json<Timeseries>> {
+    if params.points < 3 {
+        return Err(ApiError::TooFewPoints(params.points));
+    }
+    let from_timestamp = timestamp_filter.from.unwrap();
+    let to_timestamp = timestamp_filter.to.unwrap();
+    let mut metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let factor = conversion.factor(&metadata.unit, &app_state.db).await?;
+    metadata.unit = conversion.unit_or(&metadata.unit);
+
+    // bucket sizes depend on the number of rows, rows inserted in the meantime are cut off by the limit
+    let total = sqlx::query_scalar!(
+        r#"
+        select count(*) as "count!"
+        from ts_as_of(array[$1::integer], $2, $3, $4) ts
+        "#,
+        metadata.id,
+        from_timestamp,
+        to_timestamp,
+        timestamp_filter.as_of,
+    )
+    .fetch_one(&app_state.db)
+    .await?;
+
+    let mut rows = sqlx::query_as!(
+        Datapoint,
+        r#"
+        select
+            ts.id as "id!",
+            ts.series_timestamp as "timestamp!",
+            ts.series_value * $5 as "value!",
+            ts.quality as "quality!: DatapointQuality",
+            ts.created_at as "created_at!",
+            ts.updated_at as "updated_at!"
+        from ts_as_of(array[$1::integer], $2, $3, $6) ts
+        order by ts.series_timestamp
+        limit $4
+        "#,
+        metadata.id,
+        from_timestamp,
+        to_timestamp,
+        total,
+        factor,
+        timestamp_filter.as_of,
+    )
+    .fetch(&app_state.db);
+
+    let mut downsampler = Downsampler::new(params.method, total as usize, params.points as usize);
+    while let Some(datapoint) = rows.try_next().await? {
+        downsampler.push(datapoint);
+    }
+    Ok(Json(Timeseries {
+        datapoints: downsampler.finish(),
+        meta: metadata,
+        next_cursor: None,
+    }))
+}
+
+/// Count, extremes, mean, standard deviation, percentiles and optionally a histogram of a series.
+/// Everything is computed by the database, values are converted into the requested unit first.
+pub async fn get_stats_by_identifier(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(params): Query<StatsParams>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+    Query(conversion): Query<UnitConversion>,
+) -> Result<Json<TimeseriesStats>> {
+    let percentiles = params.percentiles()?;
+    let from_timestamp = timestamp_filter.from.unwrap();
+    let to_timestamp = timestamp_filter.to.unwrap();
+    let mut metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let factor = conversion.factor(&metadata.unit, &app_state.db).await?;
+    metadata.unit = conversion.unit_or(&metadata.unit);
+
+    let stats = sqlx::query!(
+        r#"
+        select
+            min(ts.series_timestamp) as first_timestamp,
+            max(ts.series_timestamp) as last_timestamp,
+            count(*) as "count!",
+            min(ts.series_value * $4) as min,
+            max(ts.series_value * $4) as max,
+            avg(ts.series_value * $4) as mean,
+            stddev_samp(ts.series_value * $4) as stddev,
+            percentile_cont($5::float[]) within group (order by ts.series_value * $4) as percentiles
+        from ts_as_of(array[$1::integer], $2, $3, $6) ts
+        "#,
+        metadata.id,
+        from_timestamp,
+        to_timestamp,
+        factor,
+        &percentiles.iter().map(|x| x / 100.0).collect::<Vec<_>>(),
+        timestamp_filter.as_of,
+    )
+    .fetch_one(&app_state.db)
+    .await?;
+
+    let histogram = match params.histogram_range(stats.min, stats.max)? {
+        Some((lower, upper)) => {
+            let bins = params.bins.unwrap();
+            // width_bucket puts values on the upper edge above the range, they belong to the last bin
+            let rows = sqlx::query!(
+                r#"
+                select
+                    case
+                        when ts.series_value * $4 = $6 then $7
+                        else width_bucket(ts.series_value * $4, $5, $6, $7)
+                    end as "bin!",
+                    count(*) as "count!"
+                from ts_as_of(array[$1::integer], $2, $3, $8) ts
+                group by 1
+                "#,
+                metadata.id,
+                from_timestamp,
+                to_timestamp,
+                factor,
+                lower,
+                upper,
+                bins as i32,
+                timestamp_filter.as_of,
+            )
+            .fetch_all(&app_state.db)
+            .await?;
+            let width = (upper - lower) / bins as f64;
+            let mut histogram = Histogram {
+                bins: (0..bins)
+                    .map(|i| HistogramBin {
+                        from: lower + width * i as f64,
+                        to: lower + width * (i + 1) as f64,
+                        count: 0,
+                    })
+                    .collect(),
+                below: 0,
+                above: 0,
+            };
+            for row in rows {
+                match row.bin as i64 {
+                    0 => histogram.below += row.count,
+                    bin if bin > bins => histogram.above += row.count,
+                    bin => histogram.bins[bin as usize - 1].count += row.count,
+                }
+            }
+            Some(histogram)
+        }
+        None => None,
+    };
+
+    let values = stats.percentiles.unwrap_or_default();
+    Ok(Json(TimeseriesStats {
+        meta: metadata,
+        from: stats.first_timestamp,
+        to: stats.last_timestamp,
+        count: stats.count,
+        min: stats.min,
+        max: stats.max,
+        mean: stats.mean,
+        stddev: stats.stddev,
+        percentiles: percentiles
+            .into_iter()
+            .enumerate()
+            .map(|(i, percentile)| Percentile {
+                percentile,
+                value: values.get(i).copied(),
+            })
+            .collect(),
+        histogram,
+    }))
+}
+
+/// Delete all datapoints of a timeseries within the given time range.
+pub async fn delete_timeseries_by_identifier(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Query(timestamp_filter): Query<TimestampFilter>,
+) -> Result<Json<DeleteTimeseriesResponse>> {
+    let from_timestamp = timestamp_filter.from.unwrap();
+    let to_timestamp = timestamp_filter.to.unwrap();
+    let metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+
+    let deleted = sqlx::query!(
+        r#"
+        delete from ts
+        where ts.meta_id = $1
+            and ts.series_timestamp >= $2
+            and ts.series_timestamp <= $3
+        "#,
+        metadata.id,
+        from_timestamp,
+        to_timestamp,
+    )
+    .execute(&app_state.db)
+    .await?
+    .rows_affected();
+
+    if deleted > 0 {
+        invalidate_cached_kpis(&app_state.config.redis_url, from_timestamp, to_timestamp).await;
+    }
+
+    Ok(Json(DeleteTimeseriesResponse {
+        identifier: metadata.identifier,
+        deleted,
+        from_timestamp,
+        to_timestamp,
+    }))
+}
+
+/// Remove cached kpis overlapping a range whose datapoints changed.
+/// The change is already committed, so an unavailable or slow redis is only logged instead of failing the request.
+async fn invalidate_cached_kpis(redis_url: &str, from: OffsetDateTime, to: OffsetDateTime) {
+    let invalidate = async {
+        let mut cache = Cache::new(redis_url).await?;
+        cache.invalidate_kpis(from, to).await
+    };
+    match tokio::time::timeout(std::time::Duration::from_secs(2), invalidate).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => tracing::warn!("Failed to invalidate cached kpis: {}", e),
+        Err(_) => tracing::warn!("Timed out invalidating cached kpis"),
+    }
+}
+
+/// Replace the values of already stored datapoints of a timeseries, matched by timestamp.
+pub async fn correct_timeseries_by_identifier(
+    State(app_state): State<AppState>,
+    Path(identifier): Path<String>,
+    Json(corrections): Json<CorrectTimeseriesBody>,
+) -> Result<Json<CorrectTimeseriesResponse>> {
+    let metadata = fetch_timeseries_meta(&app_state.db, &identifier).await?;
+    let timestamps = corrections
+        .datapoints
+        .iter()
+        .map(|x| x.timestamp)
+        .collect::<Vec<_>>();
+
+    let corrected = sqlx::query_scalar!(
+        r#"
+        update ts
+            set series_value = correction.series_value, quality = correction.quality
+        from unnest($2::timestamptz[], $3::float[], $4::text[]::datapoint_quality[])
+            as correction(series_timestamp, series_value, quality)
+        where ts.meta_id = $1
+            and ts.series_timestamp = correction.series_timestamp
+        returning ts.series_timestamp
+        "#,
+        metadata.id,
+        &timestamps,
+        &corrections
+            .datapoints
+            .iter()
+            .map(|x| x.value)
+            .collect::<Vec<_>>(),
+        &corrections
+            .datapoints
+            .iter()
+            .map(|x| x.quality.as_str().to_string())
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    if let (Some(from), Some(to)) = (corrected.iter().min(), corrected.iter().max()) {
+        invalidate_cached_kpis(&app_state.config.redis_url, *from, *to).await;
+    }
+
+    let missing = timestamps
+        .into_iter()
+        .filter(|x| !corrected.contains(x))
+        .collect();
+    Ok(Json(CorrectTimeseriesResponse {
+        identifier: metadata.identifier,
+        updated: corrected.len() as u64,
+        missing,
+    }))
+}
+
+/// Bulk insert datapoints.
+/// Every datapoint is either stored or reported back with the reason it was rejected.
+/// Datapoints colliding with stored ones are handled according to the requested `on_conflict` policy.
 pub async fn add_timeseries(
     State(app_state): State<AppState>,
-    req: Json<TimeseriesBody<NewDatapoint>>,
-) -> Result<Json<TimeseriesBody<Datapoint>>> {
-    let mut identifiers = req
-        .timeseries
+    Query(options): Query<IngestionOptions>,
+    req: Json<NewTimeseriesBody>,
+) -> Result<Json<AddTimeseriesResponse>> {
+    let response = ingest_datapoints(&app_state, &req.timeseries, &options, |_| {
+        req.meta_defaults
+            .as_ref()
+            .map(Some)
+            .ok_or(ApiError::MissingMetaDefaults)
+    })
+    .await?;
+    Ok(Json(response))
+}
+
+/// Validate and insert datapoints of any ingestion endpoint, rejecting invalid ones individually.
+/// With `auto_create_meta`, unknown identifiers are registered with the defaults `meta_defaults` returns for them,
+/// identifiers without defaults are rejected as unknown.
+pub async fn ingest_datapoints<'a>(
+    app_state: &AppState,
+    datapoints: &[NewDatapoint],
+    options: &IngestionOptions,
+    meta_defaults: impl Fn(&str) -> Result<Option<&'a MetaDefaults>>,
+) -> Result<AddTimeseriesResponse> {
+    let mut identifiers = datapoints
         .iter()
         .map(|x: &NewDatapoint| x.identifier.clone())
         .collect::<Vec<_>>();
+    identifiers.sort();
     identifiers.dedup();
 
-    let metadata = sqlx::query_as!(
+    let mut metadata = sqlx::query_as!(
         TimeseriesMeta,
         r#"
         select meta.id as id, identifier, unit, energy_carrier.name as carrier, consumption, description, local
@@ -134,28 +950,218 @@ pub async fn add_timeseries(
     .fetch_all(&app_state.db)
     .await?;
 
-    let entries = req
-        .timeseries
+    // identifiers are case-insensitive in the database
+    let find_meta = |metadata: &[TimeseriesMeta], identifier: &str| {
+        metadata
+            .iter()
+            .position(|m| m.identifier.to_lowercase() == identifier.to_lowercase())
+    };
+
+    let mut created_meta = vec![];
+    if options.auto_create_meta {
+        let mut unknown = datapoints
+            .iter()
+            .filter(|x| x.validate().is_ok() && find_meta(&metadata, &x.identifier).is_none())
+            .map(|x| x.identifier.to_lowercase())
+            .collect::<Vec<_>>();
+        unknown.sort();
+        unknown.dedup();
+        for identifier in unknown {
+            let Some(defaults) = meta_defaults(&identifier)? else {
+                continue;
+            };
+            let meta = create_meta(&app_state.db, &defaults.for_identifier(&identifier)).await?;
+            metadata.push(TimeseriesMeta {
+                id: meta.id,
+                identifier: meta.identifier.clone(),
+                unit: meta.unit.clone(),
+                carrier: meta.carrier.clone(),
+                consumption: meta.consumption,
+                description: meta.description.clone(),
+                local: meta.local,
+            });
+            created_meta.push(meta);
+        }
+    }
+
+    let mut summary: Vec<IngestionSummary> = vec![];
+    let mut rejected = vec![];
+    let mut entries = vec![];
+    for (index, datapoint) in datapoints.iter().enumerate() {
+        let meta = find_meta(&metadata, &datapoint.identifier).map(|i| &metadata[i]);
+        let identifier = meta.map_or(&datapoint.identifier, |m| &m.identifier);
+        let summary_index = summary
+            .iter()
+            .position(|x| &x.identifier == identifier)
+            .unwrap_or_else(|| {
+                summary.push(IngestionSummary {
+                    identifier: identifier.clone(),
+                    ..Default::default()
+                });
+                summary.len() - 1
+            });
+
+        let reason = match (datapoint.validate(), meta) {
+            (Err(reason), _) => reason,
+            (Ok(()), None) => RejectionReason::UnknownIdentifier,
+            (Ok(()), Some(meta)) => {
+                entries.push((
+                    datapoint.timestamp,
+                    datapoint.value,
+                    meta.id,
+                    datapoint.quality,
+                ));
+                continue;
+            }
+        };
+        summary[summary_index].rejected += 1;
+        rejected.push(RejectedDatapoint {
+            index,
+            identifier: datapoint.identifier.clone(),
+            timestamp: datapoint.timestamp,
+            value: Some(datapoint.value).filter(|x| x.is_finite()),
+            reason,
+        });
+    }
+    let mut submitted: HashMap<i32, usize> = HashMap::new();
+    for entry in &entries {
+        *submitted.entry(entry.2).or_default() += 1;
+    }
+
+    if options.on_conflict == OnConflict::Overwrite {
+        // postgres refuses to update the same row twice within one statement, so the last value wins
+        let mut seen = HashSet::new();
+        entries.reverse();
+        entries.retain(|x| seen.insert((x.2, x.0)));
+        entries.reverse();
+    }
+
+    let rows = insert_datapoints(&app_state.db, &entries, options.on_conflict).await?;
+    app_state.events.publish(DatapointEvent::from_datapoints(
+        rows.iter().map(|x| (x.meta_id, x.timestamp)),
+    ));
+
+    for m in metadata.iter().filter(|m| submitted.contains_key(&m.id)) {
+        let inserted = rows
+            .iter()
+            .filter(|x| x.meta_id == m.id && x.inserted)
+            .count();
+        let updated = rows
+            .iter()
+            .filter(|x| x.meta_id == m.id && !x.inserted)
+            .count();
+        if let Some(x) = summary.iter_mut().find(|x| x.identifier == m.identifier) {
+            x.inserted = inserted;
+            x.updated = updated;
+            x.skipped = submitted[&m.id] - inserted - updated;
+        }
+    }
+
+    let timeseries = rows.into_iter().map(Datapoint::from).collect();
+    Ok(AddTimeseriesResponse {
+        timeseries,
+        summary,
+        rejected,
+        created_meta,
+    })
+}
+
+/// Insert `(timestamp, value, meta_id, quality)` tuples into `ts` in a single statement.
+/// Only rows which were actually inserted or changed are returned.
+pub async fn insert_datapoints(
+    pool: &Pool<Postgres>,
+    entries: &[(OffsetDateTime, f64, i32, DatapointQuality)],
+    on_conflict: OnConflict,
+) -> Result<Vec<UpsertedDatapoint>> {
+    let timestamps = entries.iter().map(|x| x.0).collect::<Vec<_>>();
+    let values = entries.iter().map(|x| x.1).collect::<Vec<_>>();
+    let meta_ids = entries.iter().map(|x| x.2).collect::<Vec<_>>();
+    let qualities = entries
         .iter()
-        .filter_map(|x| {
-            let meta = metadata.iter().find(|m| m.identifier == x.identifier);
-            meta.map(|m| (x.timestamp, x.value, m))
-        })
+        .map(|x| x.3.as_str().to_string())
         .collect::<Vec<_>>();
 
     // https://klotzandrew.com/blog/postgres-passing-65535-parameter-limit
-    let timeseries = sqlx::query_as!(
-        Datapoint,
-        r#"
-            insert into ts (series_timestamp, series_value, meta_id)
-            (select * from unnest($1::timestamptz[], $2::float[], $3::int[]))
-            returning id, series_timestamp as timestamp, series_value as value, created_at, updated_at
-        "#,
-        &entries.iter().map(|x| x.0).collect::<Vec<_>>(),
-        &entries.iter().map(|x| x.1).collect::<Vec<_>>(),
-        &entries.iter().map(|x| x.2.id).collect::<Vec<_>>(),
-    )
-    .fetch_all(&app_state.db)
-    .await?;
-    Ok(Json(TimeseriesBody { timeseries }))
+    let rows = match on_conflict {
+        OnConflict::Reject => sqlx::query_as!(
+            UpsertedDatapoint,
+            r#"
+            insert into ts (series_timestamp, series_value, meta_id, quality)
+            (select * from unnest($1::timestamptz[], $2::float[], $3::int[], $4::text[]::datapoint_quality[]))
+            returning
+                id,
+                series_timestamp as timestamp,
+                series_value as value,
+                quality as "quality: DatapointQuality",
+                created_at,
+                updated_at,
+                meta_id as "meta_id!",
+                true as "inserted!"
+            "#,
+            &timestamps,
+            &values,
+            &meta_ids,
+            &qualities,
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_unique_violation() => ApiError::DuplicateDatapoint,
+            e => e.into(),
+        })?,
+        OnConflict::Skip => {
+            sqlx::query_as!(
+                UpsertedDatapoint,
+                r#"
+            insert into ts (series_timestamp, series_value, meta_id, quality)
+            (select * from unnest($1::timestamptz[], $2::float[], $3::int[], $4::text[]::datapoint_quality[]))
+            on conflict (meta_id, series_timestamp) do nothing
+            returning
+                id,
+                series_timestamp as timestamp,
+                series_value as value,
+                quality as "quality: DatapointQuality",
+                created_at,
+                updated_at,
+                meta_id as "meta_id!",
+                true as "inserted!"
+            "#,
+                &timestamps,
+                &values,
+                &meta_ids,
+                &qualities,
+            )
+            .fetch_all(pool)
+            .await?
+        }
+        // xmax is only set for rows which existed before the statement, i.e. were updated
+        OnConflict::Overwrite => {
+            sqlx::query_as!(
+                UpsertedDatapoint,
+                r#"
+            insert into ts (series_timestamp, series_value, meta_id, quality)
+            (select * from unnest($1::timestamptz[], $2::float[], $3::int[], $4::text[]::datapoint_quality[]))
+            on conflict (meta_id, series_timestamp) do update
+                set series_value = excluded.series_value, quality = excluded.quality
+                where (ts.series_value, ts.quality) is distinct from (excluded.series_value, excluded.quality)
+            returning
+                id,
+                series_timestamp as timestamp,
+                series_value as value,
+                quality as "quality: DatapointQuality",
+                created_at,
+                updated_at,
+                meta_id as "meta_id!",
+                (xmax = 0) as "inserted!"
+            "#,
+                &timestamps,
+                &values,
+                &meta_ids,
+                &qualities,
+            )
+            .fetch_all(pool)
+            .await?
+        }
+    };
+    Ok(rows)
 }
@@ -0,0 +1,16 @@
+use crate::error::ApiError;
+use crate::infrastructure::AppState;
+use crate::models::Unit;
+use axum::extract::State;
+use axum::Json;
+
+/// all units of the registry grouped by dimension
+pub async fn get_units(State(app_state): State<AppState>) -> Result<Json<Vec<Unit>>, ApiError> {
+    let units = sqlx::query_as!(
+        Unit,
+        "select name, dimension, factor from unit order by dimension, factor"
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(units))
+}
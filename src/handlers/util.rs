@@ -1,8 +1,30 @@
+use crate::error::ApiError;
 use crate::{infrastructure::AppState, models::PingResponse};
 
+use axum::body::StreamBody;
 use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use futures::channel::mpsc;
+use std::future::Future;
 
 pub async fn ping(State(_app_state): State<AppState>) -> Json<PingResponse> {
     Json(PingResponse::default())
 }
+
+/// Respond with a body that is produced chunk by chunk on a separate task.
+/// Sending fails once the client went away, the producer should stop reading from the database then.
+pub fn channel_response<F, Fut>(content_type: &'static str, producer: F) -> Response
+where
+    F: FnOnce(mpsc::Sender<Result<String, ApiError>>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(64);
+    tokio::spawn(producer(sender));
+    (
+        [(header::CONTENT_TYPE, content_type)],
+        StreamBody::new(receiver),
+    )
+        .into_response()
+}
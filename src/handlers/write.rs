@@ -0,0 +1,143 @@
+use crate::error::ApiError;
+use crate::handlers::timeseries::ingest_datapoints;
+use crate::infrastructure::AppState;
+use crate::line_protocol;
+use crate::models::{DatapointQuality, IngestionOptions, MetaDefaults, NewDatapoint};
+use crate::models::{Result, WriteMapping, WriteParams};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+/*
+Write InfluxDB line protocol, e.g. from Telegraf with `urls = ["http://backend:3000/v1"]`.
+Every numeric field is mapped to a meta identifier by the write mappings, string fields are ignored.
+Like InfluxDB, 204 is returned if all fields were stored, otherwise the valid ones are stored and 400 is returned.
+test: curl -X POST "127.0.0.1:3000/v1/write?precision=s" --data-binary "energy,device=plug1 power=12.5 1704067200"
+*/
+pub async fn write_line_protocol(
+    State(app_state): State<AppState>,
+    Query(options): Query<IngestionOptions>,
+    Query(params): Query<WriteParams>,
+    body: String,
+) -> Result<StatusCode> {
+    let points = line_protocol::parse(&body, params.precision, OffsetDateTime::now_utc())?;
+    let mappings = fetch_write_mappings(&app_state.db).await?;
+
+    let mut datapoints = vec![];
+    let mut meta_defaults: HashMap<String, &MetaDefaults> = HashMap::new();
+    let mut unmapped = vec![];
+    for point in &points {
+        for (field, value) in &point.fields {
+            let Some(value) = value.as_f64() else {
+                continue;
+            };
+            let Some((identifier, mapping)) = point.map_field(&mappings, field) else {
+                unmapped.push(format!(
+                    "no mapping for field '{}' of measurement '{}'",
+                    field, point.measurement
+                ));
+                continue;
+            };
+            if let Some(defaults) = &mapping.meta_defaults {
+                meta_defaults
+                    .entry(identifier.to_lowercase())
+                    .or_insert(defaults);
+            }
+            datapoints.push(NewDatapoint {
+                timestamp: point.timestamp,
+                value,
+                identifier,
+                quality: DatapointQuality::default(),
+            });
+        }
+    }
+
+    let response = ingest_datapoints(&app_state, &datapoints, &options, |identifier| {
+        Ok(meta_defaults.get(identifier).copied())
+    })
+    .await?;
+
+    let rejected = response.rejected.iter().map(|x| {
+        format!(
+            "'{}' at {}: {}",
+            x.identifier,
+            x.timestamp,
+            x.reason.as_str()
+        )
+    });
+    let mut errors = unmapped.into_iter().chain(rejected);
+    match errors.next() {
+        None => Ok(StatusCode::NO_CONTENT),
+        Some(first) => Err(ApiError::PartialWrite(errors.count() + 1, first)),
+    }
+}
+
+pub async fn get_write_mappings(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<WriteMapping>>> {
+    Ok(Json(fetch_write_mappings(&app_state.db).await?))
+}
+
+/// Replace all write mappings, mappings matching equally specific are tried in the given order.
+pub async fn put_write_mappings(
+    State(app_state): State<AppState>,
+    Json(mappings): Json<Vec<WriteMapping>>,
+) -> Result<Json<Vec<WriteMapping>>> {
+    let mut tx = app_state.db.begin().await?;
+    sqlx::query!("delete from write_mapping")
+        .execute(&mut *tx)
+        .await?;
+    for mapping in &mappings {
+        sqlx::query!(
+            r#"
+            insert into write_mapping (measurement, field, tags, identifier, meta_defaults)
+            values ($1, $2, $3, $4, $5)
+            "#,
+            mapping.measurement,
+            mapping.field,
+            serde_json::to_value(&mapping.tags).map_err(anyhow::Error::from)?,
+            mapping.identifier,
+            mapping
+                .meta_defaults
+                .as_ref()
+                .map(serde_json::to_value)
+                .transpose()
+                .map_err(anyhow::Error::from)?,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(Json(mappings))
+}
+
+async fn fetch_write_mappings(pool: &Pool<Postgres>) -> Result<Vec<WriteMapping>> {
+    let rows = sqlx::query!(
+        r#"
+        select measurement, field, tags, identifier, meta_defaults
+        from write_mapping
+        order by id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(WriteMapping {
+                measurement: row.measurement,
+                field: row.field,
+                tags: serde_json::from_value(row.tags).map_err(anyhow::Error::from)?,
+                identifier: row.identifier,
+                meta_defaults: row
+                    .meta_defaults
+                    .map(serde_json::from_value)
+                    .transpose()
+                    .map_err(anyhow::Error::from)?,
+            })
+        })
+        .collect()
+}
@@ -1,15 +1,28 @@
+use crate::columnar::read_parquet;
 use crate::error::ApiError;
+use crate::events::DatapointEvent;
 
-use crate::models::{ImportConfig, MetaInput, NewDatapoint, TimeseriesMeta};
+use crate::handlers::meta::create_meta;
+use crate::handlers::timeseries::insert_datapoints;
+use crate::models::{ImportConfig, MetaInput, NewDatapoint, OnConflict, TimeseriesMeta};
 
+use axum::body::Bytes;
 use sqlx::{Pool, Postgres};
+use std::collections::HashSet;
+use time::format_description::FormatItem;
+use time::macros::format_description;
 use time::OffsetDateTime;
 
+/// Format of the time column in imported and exported csv files.
+/// Fractional seconds are optional, so whole second timestamps look like the ones in `assets/`.
+pub const CSV_TIME_FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]][offset_hour sign:mandatory]:[offset_minute]");
+
 pub async fn import<T: std::io::Read>(
     pool: &Pool<Postgres>,
     readers: Vec<csv::Reader<T>>,
     import_config: &ImportConfig,
-) -> Result<(), ApiError> {
+) -> Result<Vec<DatapointEvent>, ApiError> {
+    let mut events = vec![];
     for mut reader in readers {
         let records = reader.records().collect::<Vec<_>>();
         let headers = reader.headers()?.iter().enumerate().collect::<Vec<_>>();
@@ -36,46 +49,13 @@ pub async fn import<T: std::io::Read>(
 
         for (meta_input, i) in mapped_meta_input {
             if i.is_none() {
-                println!("Column '{}' in not found", meta_input.identifier);
+                tracing::warn!("column '{}' is not found", meta_input.identifier);
                 continue;
             }
             let i = i.unwrap();
 
-            let mut meta_output: Result<TimeseriesMeta, sqlx::Error> = sqlx::query_as!(
-                TimeseriesMeta,
-                r"
-                insert into meta (identifier, unit, carrier, consumption, description, local)
-                select $1, $2, energy_carrier.id, $4, $5, $6
-                from energy_carrier
-                where energy_carrier.name = $3
-                returning id, identifier, unit, $3 as carrier, consumption, description, local",
-                &meta_input.identifier.to_lowercase(),
-                &meta_input.unit.to_lowercase(),
-                meta_input.carrier.as_deref().unwrap(),
-                meta_input.consumption.unwrap(),
-                meta_input.description.as_deref().unwrap(),
-                meta_input.local.unwrap_or(false),
-            )
-            .fetch_one(pool)
-            .await;
-
-            if meta_output.is_err() {
-                meta_output = sqlx::query_as!(
-                    TimeseriesMeta,
-                    r"
-                        select meta.id, identifier, unit, energy_carrier.name as carrier, consumption, description, local
-                        from meta
-                        inner join energy_carrier on energy_carrier.id = meta.carrier
-                        where identifier = $1 and unit = $2
-                        ",
-                    &meta_input.identifier.to_lowercase(),
-                    &meta_input.unit.to_lowercase(),
-                )
-                .fetch_one(pool)
-                .await;
-            };
-
-            let meta_output = meta_output?;
+            let meta_id = import_meta_id(pool, meta_input).await?;
+            let identifier = meta_input.identifier.to_lowercase();
 
             let mut entries = vec![];
             // create timeseries for each row
@@ -84,40 +64,139 @@ pub async fn import<T: std::io::Read>(
                 let value = record.get(i).unwrap();
                 let time = record.get(time_header.0).unwrap();
 
-                use time::macros::format_description;
-                let format = format_description!("[year]-[month]-[day] [hour]:[minute]:[second][offset_hour sign:mandatory]:[offset_minute]");
-
+                // empty cells mark timestamps without a value for this column
+                if value.is_empty() {
+                    continue;
+                }
+                let Ok(value) = value.parse::<f64>() else {
+                    tracing::warn!(
+                        "value '{}' of column '{}' at {} is not a number, skipping it",
+                        value,
+                        meta_input.identifier,
+                        time
+                    );
+                    continue;
+                };
                 entries.push(NewDatapoint {
-                    timestamp: OffsetDateTime::parse(time, &format).unwrap(),
-                    value: value.parse::<f64>().unwrap_or(0.0),
-                    identifier: meta_output.identifier.clone(),
+                    timestamp: OffsetDateTime::parse(time, &CSV_TIME_FORMAT).unwrap(),
+                    value,
+                    identifier: identifier.clone(),
+                    quality: import_config.quality,
                 });
             }
 
-            // wow this is ultra smart
-            // https://klotzandrew.com/blog/postgres-passing-65535-parameter-limit
-            sqlx::query!(
-                r#"
-                insert into ts (series_timestamp, series_value, meta_id)
-                (select * from unnest($1::timestamptz[], $2::float[], $3::int[]))
-                "#,
-                &entries.iter().map(|x| x.timestamp).collect::<Vec<_>>(),
-                &entries.iter().map(|x| x.value).collect::<Vec<_>>(),
-                &std::iter::repeat(meta_output.id)
-                    .take(entries.len())
-                    .collect::<Vec<_>>(),
-            )
-            .execute(pool)
-            .await?;
+            events.extend(insert_column(pool, meta_id, &entries, import_config.on_conflict).await?);
         }
     }
 
-    Ok(())
+    Ok(events)
+}
+
+/// Import a parquet file in the layout of the csv files, see `columnar::read_parquet`.
+pub async fn import_parquet(
+    pool: &Pool<Postgres>,
+    file: Bytes,
+    import_config: &ImportConfig,
+) -> Result<Vec<DatapointEvent>, ApiError> {
+    let identifiers = import_config
+        .timeseries
+        .iter()
+        .map(|x| x.identifier.clone())
+        .collect::<Vec<_>>();
+    let columns = read_parquet(file, &import_config.time_column, &identifiers)?;
+
+    let mut events = vec![];
+    for meta_input in &import_config.timeseries {
+        let Some(values) = columns.values.get(&meta_input.identifier) else {
+            tracing::warn!("column '{}' is not found", meta_input.identifier);
+            continue;
+        };
+        let meta_id = import_meta_id(pool, meta_input).await?;
+        let entries = columns
+            .timestamps
+            .iter()
+            .zip(values)
+            .filter_map(|(timestamp, value)| {
+                value.map(|value| NewDatapoint {
+                    timestamp: *timestamp,
+                    value,
+                    identifier: meta_input.identifier.to_lowercase(),
+                    quality: import_config.quality,
+                })
+            })
+            .collect::<Vec<_>>();
+        events.extend(insert_column(pool, meta_id, &entries, import_config.on_conflict).await?);
+    }
+    Ok(events)
+}
+
+/// Id of the series of an imported column, it is created from `meta_input` if it does not exist yet.
+async fn import_meta_id(pool: &Pool<Postgres>, meta_input: &MetaInput) -> Result<i32, ApiError> {
+    let identifier = meta_input.identifier.to_lowercase();
+    let unit = meta_input.unit.to_lowercase();
+    let existing_meta = sqlx::query_as!(
+        TimeseriesMeta,
+        r#"
+            select meta.id, identifier, unit, energy_carrier.name as "carrier?", consumption, description, local
+            from meta
+            left join energy_carrier on energy_carrier.id = meta.carrier
+            where identifier = $1 and unit = $2
+            "#,
+        &identifier,
+        &unit,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match existing_meta {
+        Some(meta) => Ok(meta.id),
+        None => {
+            let meta = MetaInput {
+                identifier,
+                unit,
+                local: Some(meta_input.local.unwrap_or(false)),
+                ..meta_input.clone()
+            };
+            Ok(create_meta(pool, &meta).await?.id)
+        }
+    }
+}
+
+/// Bulk insert the datapoints of one imported column, stored ones are handled by `on_conflict`.
+async fn insert_column(
+    pool: &Pool<Postgres>,
+    meta_id: i32,
+    entries: &[NewDatapoint],
+    on_conflict: OnConflict,
+) -> Result<Vec<DatapointEvent>, ApiError> {
+    let mut rows = entries
+        .iter()
+        .map(|x| (x.timestamp, x.value, meta_id, x.quality))
+        .collect::<Vec<_>>();
+    if on_conflict == OnConflict::Overwrite {
+        // postgres refuses to update the same row twice within one statement, so the last value wins
+        let mut seen = HashSet::new();
+        rows.reverse();
+        rows.retain(|x| seen.insert(x.0));
+        rows.reverse();
+    }
+    let rows = insert_datapoints(pool, &rows, on_conflict)
+        .await
+        .map_err(|e| match e {
+            ApiError::DuplicateDatapoint => {
+                ApiError::DuplicateImport(entries[0].identifier.clone())
+            }
+            e => e,
+        })?;
+    Ok(DatapointEvent::from_datapoints(
+        rows.iter().map(|x| (x.meta_id, x.timestamp)),
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::test_util::get_random_string;
     use crate::{app_config::AppConfig, infrastructure::create_connection_pool, models::MetaInput};
     use csv::Reader;
 
@@ -145,9 +224,104 @@ mod tests {
                     local: Some(true),
                 },
             ],
+            quality: Default::default(),
+            on_conflict: Default::default(),
         };
         let mock_csv = r"id,Time,Production,Consumption,2023-01-01 00:00:00+00:00,1.0,2.0";
         let reader = Reader::from_reader(mock_csv.as_bytes());
         import(&pool, vec![reader], &import_config).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_import_skips_invalid_values() {
+        let pool = create_connection_pool(&AppConfig::new()).await;
+        let identifier = get_random_string(10);
+        let import_config = ImportConfig {
+            files: None,
+            time_column: "Time".to_string(),
+            timeseries: vec![MetaInput {
+                identifier: identifier.clone(),
+                unit: "kW".to_string(),
+                carrier: None,
+                consumption: None,
+                description: None,
+                local: None,
+            }],
+            quality: Default::default(),
+            on_conflict: Default::default(),
+        };
+        let csv = format!(
+            "Time,{}\n2023-01-01 00:00:00+00:00,1.5\n2023-01-01 00:15:00+00:00,n/a\n2023-01-01 00:30:00+00:00,\n2023-01-01 00:45:00+00:00,3\n",
+            identifier
+        );
+        let reader = Reader::from_reader(csv.as_bytes());
+        let events = import(&pool, vec![reader], &import_config).await.unwrap();
+        assert_eq!(events.len(), 1);
+
+        let values = sqlx::query_scalar!(
+            "select series_value from ts join meta on meta.id = ts.meta_id where meta.identifier = $1 order by series_timestamp",
+            identifier.to_lowercase()
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(values, [1.5, 3.0]);
+    }
+
+    #[tokio::test]
+    async fn test_import_again() {
+        let pool = create_connection_pool(&AppConfig::new()).await;
+        let identifier = get_random_string(10);
+        let mut import_config = ImportConfig {
+            files: None,
+            time_column: "Time".to_string(),
+            timeseries: vec![MetaInput {
+                identifier: identifier.clone(),
+                unit: "kW".to_string(),
+                carrier: None,
+                consumption: None,
+                description: None,
+                local: None,
+            }],
+            quality: Default::default(),
+            on_conflict: Default::default(),
+        };
+        let csv = |value: f64| {
+            format!(
+                "Time,{}\n2023-01-01 00:00:00+00:00,{}\n2023-01-01 00:15:00+00:00,2\n",
+                identifier, value
+            )
+        };
+        let reader = |csv: String| Reader::from_reader(std::io::Cursor::new(csv));
+        import(&pool, vec![reader(csv(1.0))], &import_config)
+            .await
+            .unwrap();
+
+        let result = import(&pool, vec![reader(csv(1.0))], &import_config).await;
+        assert!(
+            matches!(result, Err(ApiError::DuplicateImport(x)) if x == identifier.to_lowercase())
+        );
+
+        import_config.on_conflict = OnConflict::Skip;
+        let events = import(&pool, vec![reader(csv(5.0))], &import_config)
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+
+        import_config.on_conflict = OnConflict::Overwrite;
+        let events = import(&pool, vec![reader(csv(5.0))], &import_config)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].from, events[0].to);
+
+        let values = sqlx::query_scalar!(
+            "select series_value from ts join meta on meta.id = ts.meta_id where meta.identifier = $1 order by series_timestamp",
+            identifier.to_lowercase()
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(values, [5.0, 2.0]);
+    }
 }
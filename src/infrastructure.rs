@@ -1,8 +1,14 @@
 use crate::app_config::AppConfig;
 
 use crate::error::ApiError;
+use crate::events::Events;
+use crate::handlers::admin::{apply_retention, get_chunks, get_compression, get_retention};
+use crate::handlers::admin::{put_compression, put_retention};
 use crate::handlers::config::{get_config, put_config};
 use crate::handlers::emission_factor::{add_emission_factor, get_emission_factor};
+use crate::handlers::export::export_timeseries;
+use crate::handlers::forecast::{add_forecast, get_forecast_by_identifier};
+use crate::handlers::forecast::{get_forecast_errors, get_forecast_issues};
 use crate::handlers::import::upload_timeseries;
 use crate::handlers::kpi::{
     get_autarky, get_co2_savings, get_consumption, get_cost_savings, get_local_consumption,
@@ -11,13 +17,20 @@ use crate::handlers::kpi::{
     get_total_production,
 };
 use crate::handlers::meta::{add_meta, get_meta_by_identifier, read_meta};
+use crate::handlers::quality::{get_quality_by_identifier, get_quality_summary};
+use crate::handlers::subscription::subscribe_timeseries;
 use crate::handlers::timeseries::{
-    add_timeseries, get_timeseries_by_identifier, resample_timeseries_by_identifier,
+    add_timeseries, correct_timeseries_by_identifier, delete_timeseries_by_identifier,
+    downsample_timeseries_by_identifier, get_aligned_timeseries, get_energy_by_identifier,
+    get_latest_datapoints, get_stats_by_identifier, get_timeseries_by_identifier,
+    resample_timeseries_by_identifier,
 };
+use crate::handlers::unit::get_units;
 use crate::handlers::util::ping;
+use crate::handlers::write::{get_write_mappings, put_write_mappings, write_line_protocol};
 use crate::models::Result;
 use axum::extract::DefaultBodyLimit;
-use axum::routing::post;
+use axum::routing::{delete, patch, post, put};
 use axum::{routing::get, Router};
 use dotenv::dotenv;
 use sqlx::Postgres;
@@ -46,15 +59,23 @@ async fn fallback_handler() -> Result<ApiError> {
 pub struct AppState {
     pub db: Pool<Postgres>,
     pub config: AppConfig,
+    pub events: Events,
 }
 
-pub fn create_router(pool: Pool<Postgres>, app_config: &AppConfig) -> Router {
+impl AppState {
+    pub fn new(pool: Pool<Postgres>, app_config: &AppConfig) -> Self {
+        AppState {
+            db: pool,
+            config: app_config.clone(),
+            events: Events::default(),
+        }
+    }
+}
+
+/// Routes of the api, `app_state` can be shared with tasks running beside the server like the MQTT bridge.
+pub fn create_router(app_state: AppState) -> Router {
     // for swagger-ui and mitigating common errors for development
     let cors = CorsLayer::new().allow_origin(Any).allow_headers(Any);
-    let app_state = AppState {
-        db: pool,
-        config: app_config.clone(),
-    };
 
     Router::new()
         .route("/", get(ping))
@@ -81,13 +102,52 @@ pub fn create_router(pool: Pool<Postgres>, app_config: &AppConfig) -> Router {
         .route("/v1/meta/", get(read_meta))
         .route("/v1/ts/", post(add_timeseries))
         .route("/v1/ts/upload/", post(upload_timeseries))
+        .route("/v1/ts/export/", get(export_timeseries))
+        .route("/v1/ts/export", get(export_timeseries))
+        .route("/v1/ts/aligned/", get(get_aligned_timeseries))
+        .route("/v1/ts/quality/", get(get_quality_summary))
+        .route("/v1/ts/latest/", get(get_latest_datapoints))
+        .route("/v1/ts/latest", get(get_latest_datapoints))
+        .route("/v1/ts/subscribe/", get(subscribe_timeseries))
         .route("/v1/ts/:identifier/", get(get_timeseries_by_identifier))
+        .route(
+            "/v1/ts/:identifier/",
+            delete(delete_timeseries_by_identifier),
+        )
+        .route(
+            "/v1/ts/:identifier/",
+            patch(correct_timeseries_by_identifier),
+        )
         .route(
             "/v1/ts/:identifier/resample/",
             get(resample_timeseries_by_identifier),
         )
+        .route("/v1/ts/:identifier/energy/", get(get_energy_by_identifier))
+        .route(
+            "/v1/ts/:identifier/downsample/",
+            get(downsample_timeseries_by_identifier),
+        )
+        .route("/v1/ts/:identifier/stats/", get(get_stats_by_identifier))
+        .route(
+            "/v1/ts/:identifier/quality/",
+            get(get_quality_by_identifier),
+        )
+        .route("/v1/write", post(write_line_protocol))
+        .route("/v1/write/mappings/", get(get_write_mappings))
+        .route("/v1/write/mappings/", put(put_write_mappings))
+        .route("/v1/forecast/:identifier/", post(add_forecast))
+        .route("/v1/forecast/:identifier/", get(get_forecast_by_identifier))
+        .route("/v1/forecast/:identifier/issues/", get(get_forecast_issues))
+        .route("/v1/forecast/:identifier/errors/", get(get_forecast_errors))
         .route("/v1/emission_factors/", get(get_emission_factor))
         .route("/v1/emission_factors/", post(add_emission_factor))
+        .route("/v1/units/", get(get_units))
+        .route("/v1/admin/compression/", get(get_compression))
+        .route("/v1/admin/compression/", put(put_compression))
+        .route("/v1/admin/retention/", get(get_retention))
+        .route("/v1/admin/retention/apply/", post(apply_retention))
+        .route("/v1/admin/retention/:identifier/", put(put_retention))
+        .route("/v1/admin/chunks/", get(get_chunks))
         .fallback(get(fallback_handler))
         .layer(cors)
         // limit file size to 10MB
@@ -100,6 +160,7 @@ mod tests {
     use axum::http::header;
     use axum_test_helper::TestClient;
 
+    use crate::infrastructure::AppState;
     use crate::{app_config::AppConfig, create_connection_pool};
     #[tokio::test]
     async fn test_create_pool_connection() {
@@ -115,7 +176,7 @@ mod tests {
     async fn test_cors() {
         let config = AppConfig::new();
         let pool = create_connection_pool(&config).await;
-        let router = crate::create_router(pool, &config);
+        let router = crate::create_router(AppState::new(pool, &config));
         let client = TestClient::new(router);
 
         let response = client
@@ -0,0 +1,354 @@
+use crate::error::ApiError;
+use crate::models::WriteMapping;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+
+/// Unit of the timestamps in a write request, InfluxDB 1.x and 2.x names are both accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum Precision {
+    #[default]
+    #[serde(rename = "ns", alias = "n")]
+    Nanoseconds,
+    #[serde(rename = "us", alias = "u")]
+    Microseconds,
+    #[serde(rename = "ms")]
+    Milliseconds,
+    #[serde(rename = "s")]
+    Seconds,
+    #[serde(rename = "m")]
+    Minutes,
+    #[serde(rename = "h")]
+    Hours,
+}
+
+impl Precision {
+    fn nanoseconds(self) -> i128 {
+        match self {
+            Self::Nanoseconds => 1,
+            Self::Microseconds => 1_000,
+            Self::Milliseconds => 1_000_000,
+            Self::Seconds => 1_000_000_000,
+            Self::Minutes => 60_000_000_000,
+            Self::Hours => 3_600_000_000_000,
+        }
+    }
+
+    pub fn timestamp(self, value: i64) -> Option<OffsetDateTime> {
+        OffsetDateTime::from_unix_timestamp_nanos(value as i128 * self.nanoseconds()).ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    UInteger(u64),
+    Boolean(bool),
+    String(String),
+}
+
+impl FieldValue {
+    /// Numeric value of the field, booleans are stored as 0 and 1 and strings can't be stored.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(x) => Some(*x),
+            Self::Integer(x) => Some(*x as f64),
+            Self::UInteger(x) => Some(*x as f64),
+            Self::Boolean(x) => Some(if *x { 1.0 } else { 0.0 }),
+            Self::String(_) => None,
+        }
+    }
+}
+
+/// A single line of InfluxDB line protocol, `measurement,tag=value field=value timestamp`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    pub tags: BTreeMap<String, String>,
+    pub fields: Vec<(String, FieldValue)>,
+    pub timestamp: OffsetDateTime,
+}
+
+impl Point {
+    /// Identifier of `field` according to the most specific matching mapping.
+    /// Mappings matching the same number of tags and fields are tried in their order.
+    pub fn map_field<'a>(
+        &self,
+        mappings: &'a [WriteMapping],
+        field: &str,
+    ) -> Option<(String, &'a WriteMapping)> {
+        let mut matching = mappings
+            .iter()
+            .filter(|m| {
+                m.measurement == self.measurement
+                    && m.field.as_ref().is_none_or(|x| x == field)
+                    && m.tags.iter().all(|(k, v)| self.tags.get(k) == Some(v))
+            })
+            .collect::<Vec<_>>();
+        matching.sort_by_key(|m| std::cmp::Reverse(m.tags.len() + m.field.is_some() as usize));
+        matching
+            .into_iter()
+            .find_map(|m| Some((self.render(&m.identifier, field)?, m)))
+    }
+
+    /// Replace `{measurement}`, `{field}` and `{<tag>}` in an identifier template.
+    /// Templates referencing tags the point doesn't have are not rendered.
+    fn render(&self, template: &str, field: &str) -> Option<String> {
+        let mut identifier = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = start + rest[start..].find('}')?;
+            identifier.push_str(&rest[..start]);
+            let value = match &rest[start + 1..end] {
+                "measurement" => &self.measurement,
+                "field" => field,
+                tag => self.tags.get(tag)?,
+            };
+            identifier.push_str(value);
+            rest = &rest[end + 1..];
+        }
+        identifier.push_str(rest);
+        Some(identifier)
+    }
+}
+
+/// Parse a line protocol body, points without a timestamp are taken at `now`.
+/// Empty lines and comments are skipped, the first invalid line fails the whole body.
+pub fn parse(
+    body: &str,
+    precision: Precision,
+    now: OffsetDateTime,
+) -> Result<Vec<Point>, ApiError> {
+    body.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(number, line)| {
+            parse_line(line.trim(), precision, now)
+                .map_err(|reason| ApiError::InvalidLineProtocol(number + 1, reason))
+        })
+        .collect()
+}
+
+fn parse_line(line: &str, precision: Precision, now: OffsetDateTime) -> Result<Point, String> {
+    let (key, rest) = split_unescaped(line, ' ', false)
+        .split_first()
+        .map(|(key, rest)| (*key, rest.join(" ")))
+        .ok_or("missing measurement")?;
+    let mut key = split_unescaped(key, ',', false).into_iter();
+    let measurement = unescape(key.next().unwrap_or_default());
+    if measurement.is_empty() {
+        return Err(String::from("missing measurement"));
+    }
+    let tags = key
+        .map(|tag| {
+            let (name, value) = split_pair(tag).ok_or(format!("invalid tag '{}'", tag))?;
+            Ok((unescape(name), unescape(value)))
+        })
+        .collect::<Result<BTreeMap<_, _>, String>>()?;
+
+    let sections = split_unescaped(&rest, ' ', true);
+    let (fields, timestamp) = match sections[..] {
+        [fields] => (fields, None),
+        [fields, timestamp] => (fields, Some(timestamp)),
+        _ => return Err(String::from("expected fields and an optional timestamp")),
+    };
+    if fields.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    let fields = split_unescaped(fields, ',', true)
+        .into_iter()
+        .map(|field| {
+            let (name, value) = split_pair(field).ok_or(format!("invalid field '{}'", field))?;
+            let value = parse_field_value(value)
+                .ok_or(format!("invalid value of field '{}'", unescape(name)))?;
+            Ok((unescape(name), value))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let timestamp = match timestamp {
+        None => now,
+        Some(timestamp) => timestamp
+            .parse::<i64>()
+            .ok()
+            .and_then(|x| precision.timestamp(x))
+            .ok_or(format!("invalid timestamp '{}'", timestamp))?,
+    };
+    Ok(Point {
+        measurement,
+        tags,
+        fields,
+        timestamp,
+    })
+}
+
+fn parse_field_value(value: &str) -> Option<FieldValue> {
+    let value = match value {
+        "t" | "T" | "true" | "True" | "TRUE" => FieldValue::Boolean(true),
+        "f" | "F" | "false" | "False" | "FALSE" => FieldValue::Boolean(false),
+        _ if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') => {
+            FieldValue::String(unescape(&value[1..value.len() - 1]))
+        }
+        _ if value.ends_with('i') => FieldValue::Integer(value[..value.len() - 1].parse().ok()?),
+        _ if value.ends_with('u') => FieldValue::UInteger(value[..value.len() - 1].parse().ok()?),
+        _ => FieldValue::Float(value.parse().ok()?),
+    };
+    Some(value)
+}
+
+/// Split at `separator` unless it is escaped by a backslash or, with `quotes`, inside a string value.
+fn split_unescaped(s: &str, separator: char, quotes: bool) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    let mut quoted = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' if quotes => quoted = !quoted,
+            _ if c == separator && !quoted => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// `key=value` split at the first unescaped equals sign.
+fn split_pair(s: &str) -> Option<(&str, &str)> {
+    let key = split_unescaped(s, '=', false)[0];
+    let value = s.get(key.len() + 1..)?;
+    (!key.is_empty() && !value.is_empty()).then_some((key, value))
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next @ (',' | '=' | ' ' | '"' | '\\'))) => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn mapping(
+        measurement: &str,
+        field: Option<&str>,
+        tags: &[(&str, &str)],
+        identifier: &str,
+    ) -> WriteMapping {
+        WriteMapping {
+            measurement: measurement.to_string(),
+            field: field.map(String::from),
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            identifier: identifier.to_string(),
+            meta_defaults: None,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let now = datetime!(2024-01-01 12:00 UTC);
+        let body = "# comment\n\
+            energy,device=plug\\ 1,phase=L1 power=12.5,total=1034i,on=t,state=\"on, \\\"really\\\"\" 1704067200000000000\n\
+            \n\
+            weather temperature=-1.5e1";
+        let points = parse(body, Precision::Nanoseconds, now).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].measurement, "energy");
+        assert_eq!(points[0].tags["device"], "plug 1");
+        assert_eq!(points[0].tags["phase"], "L1");
+        assert_eq!(
+            points[0].fields,
+            [
+                (String::from("power"), FieldValue::Float(12.5)),
+                (String::from("total"), FieldValue::Integer(1034)),
+                (String::from("on"), FieldValue::Boolean(true)),
+                (
+                    String::from("state"),
+                    FieldValue::String(String::from("on, \"really\""))
+                ),
+            ]
+        );
+        assert_eq!(points[0].timestamp, datetime!(2024-01-01 0:00 UTC));
+        assert_eq!(points[1].fields[0].1.as_f64(), Some(-15.0));
+        assert_eq!(points[1].timestamp, now);
+
+        let points = parse("m v=1u 1704067200", Precision::Seconds, now).unwrap();
+        assert_eq!(points[0].fields[0].1, FieldValue::UInteger(1));
+        assert_eq!(points[0].timestamp, datetime!(2024-01-01 0:00 UTC));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let now = OffsetDateTime::now_utc();
+        for body in [
+            "m",
+            "m v=",
+            "m v=abc",
+            "m,tag v=1",
+            "m v=1 soon",
+            "m v=1 1 2",
+            ",t=1 v=1",
+        ] {
+            assert!(
+                matches!(
+                    parse(&format!("m v=1\n{}", body), Precision::Nanoseconds, now),
+                    Err(ApiError::InvalidLineProtocol(2, _))
+                ),
+                "{}",
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn test_map_field() {
+        let now = OffsetDateTime::now_utc();
+        let point = &parse(
+            "energy,device=plug,phase=L1 power=1,voltage=230",
+            Precision::Nanoseconds,
+            now,
+        )
+        .unwrap()[0];
+        let mappings = [
+            mapping("energy", None, &[], "{device}_{field}"),
+            mapping(
+                "energy",
+                Some("power"),
+                &[("phase", "L1")],
+                "{device}_l1_power",
+            ),
+            mapping(
+                "energy",
+                Some("power"),
+                &[("phase", "L2")],
+                "{device}_l2_power",
+            ),
+            mapping("energy", Some("voltage"), &[], "{missing}_voltage"),
+        ];
+        let identifier = |field| point.map_field(&mappings, field).map(|x| x.0);
+        assert_eq!(identifier("power").as_deref(), Some("plug_l1_power"));
+        assert_eq!(identifier("voltage").as_deref(), Some("plug_voltage"));
+        assert_eq!(identifier("current").as_deref(), Some("plug_current"));
+        assert!(point.map_field(&mappings[1..], "current").is_none());
+    }
+}
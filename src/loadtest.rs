@@ -85,11 +85,6 @@ async fn loadtest_post_random_meta(user: &mut GooseUser) -> TransactionResult {
         .take(32)
         .map(char::from)
         .collect();
-    let unit: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(32)
-        .map(char::from)
-        .collect();
     let description: String = rand::thread_rng()
         .sample_iter(&Alphanumeric)
         .take(32)
@@ -98,7 +93,7 @@ async fn loadtest_post_random_meta(user: &mut GooseUser) -> TransactionResult {
 
     let meta = &serde_json::json!({
                 "identifier": identifier,
-                "unit": unit,
+                "unit": "kW",
                 "description": description,
     });
     let _response = user.post_json("/v1/meta/", meta).await?;
@@ -4,20 +4,28 @@ use std::{
 };
 
 use crate::{
-    infrastructure::{create_connection_pool, create_router},
-    models::ImportConfig,
+    infrastructure::{create_connection_pool, create_router, AppState},
+    models::{ImportConfig, MqttConfig},
 };
 use app_config::AppConfig;
 
 use tracing_subscriber::fmt;
 
 mod app_config;
+mod columnar;
+mod downsample;
 mod error;
+mod events;
+mod export;
 mod handlers;
 mod import;
 mod infrastructure;
+mod line_protocol;
 mod loadtest;
 mod models;
+mod mqtt;
+mod period;
+mod quality;
 mod tests;
 
 mod cache;
@@ -68,7 +76,15 @@ async fn main() {
         return;
     }
 
-    let app = create_router(pool, &config);
+    let app_state = AppState::new(pool, &config);
+    if let Some(path) = &config.mqtt_config_path {
+        tracing::info!("Starting MQTT bridge from {}", path);
+        let mqtt_reader = std::fs::File::open(path).unwrap();
+        let mqtt_config: MqttConfig = serde_yaml::from_reader(&mqtt_reader).unwrap();
+        tokio::spawn(mqtt::run_bridge(app_state.clone(), mqtt_config));
+    }
+
+    let app = create_router(app_state);
 
     tracing::info!("Listening on port {}", config.port);
     // run it with hyper on localhost:3000
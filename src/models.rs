@@ -1,13 +1,18 @@
 use crate::error::ApiError;
+use crate::line_protocol::Precision;
+use crate::period;
+use crate::quality::QualityChecks;
 use anyhow::anyhow;
 use regex::Regex;
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sqlx::postgres::types::PgInterval;
 use sqlx::{Pool, Postgres};
+use std::collections::BTreeMap;
 use std::fmt::Formatter;
+use std::str::FromStr;
 use time::format_description::well_known::Rfc3339;
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime, UtcOffset};
 
 /// wrap postgres timestamptz to achieve human-readable serialization
 #[derive(sqlx::Type)]
@@ -62,12 +67,38 @@ pub struct TimeseriesMeta {
     pub local: Option<bool>,
 }
 
-#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+/// Where the value of a datapoint comes from, every quality but `Measured` counts as flagged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "datapoint_quality", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DatapointQuality {
+    #[default]
+    Measured,
+    Estimated,
+    Substituted,
+    /// set on values replaced through the correction endpoint
+    Corrected,
+}
+
+impl DatapointQuality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Measured => "measured",
+            Self::Estimated => "estimated",
+            Self::Substituted => "substituted",
+            Self::Corrected => "corrected",
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
 pub struct Datapoint {
     pub id: i64,
     #[serde(with = "time::serde::rfc3339")]
     pub timestamp: OffsetDateTime,
     pub value: f64,
+    #[serde(default)]
+    pub quality: DatapointQuality,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
@@ -76,16 +107,27 @@ pub struct Datapoint {
 
 #[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
 /// TimescaleDB's `time_bucket` function returns a nullable column
+/// `values` holds every requested aggregate by name, `mean_value` is kept for clients which only know the mean
 pub struct ResampledDatapoint {
     #[serde(with = "time::serde::rfc3339::option")]
     pub bucket: Option<OffsetDateTime>,
     pub mean_value: Option<f64>,
+    #[serde(default)]
+    #[sqlx(skip)]
+    pub values: BTreeMap<String, Option<f64>>,
+    /// true if the bucket contains no measured values and was added by gap filling
+    #[serde(default)]
+    #[sqlx(skip)]
+    pub filled: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Timeseries {
     pub datapoints: Vec<Datapoint>,
     pub meta: TimeseriesMeta,
+    /// pass as `after` to fetch the next page, only set if the page is full
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub next_cursor: Option<OffsetDateTime>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -94,6 +136,69 @@ pub struct ResampledTimeseries {
     pub meta: TimeseriesMeta,
 }
 
+/// Several timeseries resampled into the same buckets.
+/// `values` of every row hold one value per series in the order of `meta`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlignedTimeseries {
+    pub meta: Vec<TimeseriesMeta>,
+    pub rows: Vec<AlignedRow>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlignedRow {
+    #[serde(with = "time::serde::rfc3339")]
+    pub bucket: OffsetDateTime,
+    pub values: Vec<Option<f64>>,
+}
+
+/// The most recent datapoint of a series, `age_seconds` is the time since its timestamp.
+/// Both are None if the series has no datapoints.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LatestDatapoint {
+    pub meta: TimeseriesMeta,
+    pub datapoint: Option<Datapoint>,
+    pub age_seconds: Option<f64>,
+}
+
+/// `since` replays the datapoints after that timestamp before new ones are pushed,
+/// reconnecting clients send the `Last-Event-ID` header instead, which replays by write time.
+#[derive(Debug, Default, Deserialize)]
+pub struct SubscriptionParams {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub since: Option<OffsetDateTime>,
+}
+
+/// Maps fields of line protocol points to a meta identifier, see `line_protocol::Point::map_field`.
+/// A mapping without `field` applies to all fields of the measurement, all `tags` have to match.
+/// `meta_defaults` register unknown identifiers if the write request sets `auto_create_meta`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteMapping {
+    pub measurement: String,
+    #[serde(default)]
+    pub field: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    pub identifier: String,
+    #[serde(default)]
+    pub meta_defaults: Option<MetaDefaults>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WriteParams {
+    #[serde(default)]
+    pub precision: Precision,
+}
+
+/// Data of a server-sent event of a subscription.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscribedDatapoint {
+    pub identifier: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub value: f64,
+    pub quality: DatapointQuality,
+}
+
 /// Intermediate representation for join tables from the database.
 #[derive(Debug, Serialize)]
 pub struct DatapointWithMetadata {
@@ -120,12 +225,229 @@ pub struct NewDatapoint {
     pub timestamp: OffsetDateTime,
     pub value: f64,
     pub identifier: String,
+    #[serde(default)]
+    pub quality: DatapointQuality,
+}
+
+impl NewDatapoint {
+    /// Datapoints ahead of now by more than this are considered faulty.
+    const MAX_FUTURE_OFFSET: Duration = Duration::days(1);
+
+    /// Check value and timestamp of the datapoint, independent of its identifier.
+    /// Timestamps before the unix epoch are rejected because reads never return them by default.
+    pub fn validate(&self) -> std::result::Result<(), RejectionReason> {
+        if !self.value.is_finite() {
+            return Err(RejectionReason::NonFiniteValue);
+        }
+        if self.timestamp < OffsetDateTime::UNIX_EPOCH
+            || self.timestamp > OffsetDateTime::now_utc() + Self::MAX_FUTURE_OFFSET
+        {
+            return Err(RejectionReason::TimestampOutOfRange);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TimeseriesBody<T = Timeseries> {
     pub timeseries: Vec<T>,
 }
+
+/// Row returned by bulk inserts into `ts`.
+/// `inserted` is false if an already existing row was overwritten.
+pub struct UpsertedDatapoint {
+    pub id: i64,
+    pub timestamp: OffsetDateTime,
+    pub value: f64,
+    pub quality: DatapointQuality,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+    pub meta_id: i32,
+    pub inserted: bool,
+}
+
+impl From<UpsertedDatapoint> for Datapoint {
+    fn from(row: UpsertedDatapoint) -> Self {
+        Self {
+            id: row.id,
+            timestamp: row.timestamp,
+            value: row.value,
+            quality: row.quality,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// What to do with datapoints whose `(identifier, timestamp)` pair is already stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    /// fail the whole request
+    #[default]
+    Reject,
+    /// keep the stored value and drop the new one
+    Skip,
+    /// replace the stored value with the new one
+    Overwrite,
+}
+
+/// `IngestionOptions` is passed as a query parameter to endpoints that insert datapoints.
+#[derive(Debug, Default, Deserialize)]
+pub struct IngestionOptions {
+    #[serde(default)]
+    pub on_conflict: OnConflict,
+    /// register metadata for unknown identifiers using the `meta_defaults` of the request
+    #[serde(default)]
+    pub auto_create_meta: bool,
+}
+
+/// Metadata used to register unknown identifiers during ingestion.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MetaDefaults {
+    pub unit: String,
+    pub carrier: Option<String>,
+    pub consumption: Option<bool>,
+    pub description: Option<String>,
+    pub local: Option<bool>,
+}
+
+impl MetaDefaults {
+    pub fn for_identifier(&self, identifier: &str) -> MetaInput {
+        MetaInput {
+            identifier: identifier.to_string(),
+            unit: self.unit.clone(),
+            carrier: self.carrier.clone(),
+            consumption: self.consumption,
+            description: self.description.clone(),
+            local: self.local,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewTimeseriesBody {
+    pub timeseries: Vec<NewDatapoint>,
+    #[serde(default)]
+    pub meta_defaults: Option<MetaDefaults>,
+}
+
+/// Number of datapoints inserted, skipped, updated and rejected for a single identifier.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IngestionSummary {
+    pub identifier: String,
+    pub inserted: usize,
+    pub skipped: usize,
+    pub updated: usize,
+    pub rejected: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    UnknownIdentifier,
+    NonFiniteValue,
+    TimestampOutOfRange,
+}
+
+impl RejectionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnknownIdentifier => "unknown_identifier",
+            Self::NonFiniteValue => "non_finite_value",
+            Self::TimestampOutOfRange => "timestamp_out_of_range",
+        }
+    }
+}
+
+/// A datapoint of an ingestion request which was not stored.
+/// `index` is the position of the datapoint in the request body.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RejectedDatapoint {
+    pub index: usize,
+    pub identifier: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub value: Option<f64>,
+    pub reason: RejectionReason,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteTimeseriesResponse {
+    pub identifier: String,
+    pub deleted: u64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub from_timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub to_timestamp: OffsetDateTime,
+}
+
+/// Replacement value for the datapoint stored at `timestamp`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatapointCorrection {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub value: f64,
+    #[serde(default = "DatapointCorrection::default_quality")]
+    pub quality: DatapointQuality,
+}
+
+impl DatapointCorrection {
+    fn default_quality() -> DatapointQuality {
+        DatapointQuality::Corrected
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CorrectTimeseriesBody {
+    pub datapoints: Vec<DatapointCorrection>,
+}
+
+/// `missing` lists the requested timestamps for which no datapoint is stored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CorrectTimeseriesResponse {
+    pub identifier: String,
+    pub updated: u64,
+    #[serde(with = "rfc3339_vec")]
+    pub missing: Vec<OffsetDateTime>,
+}
+
+/// serde helper for lists of rfc3339 timestamps
+mod rfc3339_vec {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use time::format_description::well_known::Rfc3339;
+    use time::OffsetDateTime;
+
+    pub fn serialize<S>(values: &[OffsetDateTime], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let formatted = values
+            .iter()
+            .map(|x| x.format(&Rfc3339))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(serde::ser::Error::custom)?;
+        serializer.collect_seq(formatted)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<OffsetDateTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|x| OffsetDateTime::parse(x, &Rfc3339).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddTimeseriesResponse {
+    pub timeseries: Vec<Datapoint>,
+    pub summary: Vec<IngestionSummary>,
+    pub rejected: Vec<RejectedDatapoint>,
+    pub created_meta: Vec<MetaOutput>,
+}
 #[derive(Debug, Clone, Serialize)]
 pub struct SingleMetaResponse {
     pub metadata: TimeseriesMeta,
@@ -152,7 +474,7 @@ impl Default for PingResponse {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MetaInput {
     pub identifier: String,
     pub unit: String,
@@ -226,6 +548,139 @@ pub struct ImportConfig {
     pub files: Option<Vec<String>>,
     pub time_column: String,
     pub timeseries: Vec<MetaInput>,
+    /// quality of every imported datapoint
+    #[serde(default)]
+    pub quality: DatapointQuality,
+    /// what to do with imported datapoints which are already stored, e.g. when a file is imported again
+    #[serde(default)]
+    pub on_conflict: OnConflict,
+}
+
+/// Broker and topics of the MQTT bridge, read from the yaml file at `MQTT_CONFIG_PATH`, see `mqtt::run_bridge`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "MqttConfig::default_port")]
+    pub port: u16,
+    #[serde(default = "MqttConfig::default_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// datapoints are inserted once this many are buffered or `flush_interval_ms` passed
+    #[serde(default = "MqttConfig::default_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "MqttConfig::default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// brokers deliver retained and unacknowledged messages again after reconnecting, so stored datapoints are skipped by default
+    #[serde(default = "MqttConfig::default_on_conflict")]
+    pub on_conflict: OnConflict,
+    pub subscriptions: Vec<MqttSubscription>,
+}
+
+impl MqttConfig {
+    fn default_port() -> u16 {
+        1883
+    }
+
+    fn default_client_id() -> String {
+        String::from("inno2grid-backend")
+    }
+
+    fn default_batch_size() -> usize {
+        500
+    }
+
+    fn default_flush_interval_ms() -> u64 {
+        1000
+    }
+
+    fn default_on_conflict() -> OnConflict {
+        OnConflict::Skip
+    }
+}
+
+/// Values read from the messages of a topic filter, `+` and `#` wildcards are allowed.
+/// `timestamp` is the path of an Rfc3339 string or unix seconds in the payload, the time of receipt is used without it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MqttSubscription {
+    pub topic: String,
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub quality: DatapointQuality,
+    pub values: Vec<MqttValue>,
+}
+
+/// `path` of a value in the json payload, dot separated keys and array indices like `ENERGY.Power`.
+/// Without a path, the whole payload is the value.
+/// `{1}`, `{2}`, ... in the identifier are replaced by the topic levels matched by the wildcards of the filter.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MqttValue {
+    pub path: Option<String>,
+    pub identifier: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// one column per identifier, re-importable with the matching `import_config`
+    #[default]
+    Csv,
+    /// one `NewDatapoint` per line
+    Ndjson,
+    /// the `ImportConfig` yaml describing the csv export
+    ImportConfig,
+}
+
+/// `ExportParams` selects the timeseries and format returned by the export endpoint.
+/// `identifiers` is a comma separated list, columns are written in the same order.
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    pub identifiers: String,
+    #[serde(default)]
+    pub format: ExportFormat,
+    #[serde(default = "ExportParams::default_time_column")]
+    pub time_column: String,
+}
+
+impl ExportParams {
+    fn default_time_column() -> String {
+        String::from("Time")
+    }
+
+    pub fn identifiers(&self) -> Vec<String> {
+        self.identifiers
+            .split(',')
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect()
+    }
+}
+
+/// Selects timeseries either by a comma separated list of `identifiers` or by a meta filter.
+/// If `identifiers` is given, the filter fields are ignored.
+#[derive(Debug, Default, Deserialize)]
+pub struct SeriesSelection {
+    pub identifiers: Option<String>,
+    pub carrier: Option<String>,
+    pub local: Option<bool>,
+    pub consumption: Option<bool>,
+}
+
+impl SeriesSelection {
+    pub fn identifiers(&self) -> Option<Vec<String>> {
+        let identifiers: Vec<String> = self
+            .identifiers
+            .as_deref()?
+            .split(',')
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect();
+        (!identifiers.is_empty()).then_some(identifiers)
+    }
+
+    pub fn has_filter(&self) -> bool {
+        self.carrier.is_some() || self.local.is_some() || self.consumption.is_some()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -255,13 +710,240 @@ impl Pagination {
         self.get_page_or_default() * self.get_per_page_or_default()
     }
 }
+/// Aggregate function applied to the values within a resampling bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Mean,
+    Sum,
+    Min,
+    Max,
+    First,
+    Last,
+    Count,
+    Stddev,
+    /// continuous percentile between 0 and 100
+    Percentile(u8),
+}
+
+impl FromStr for Aggregate {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let aggregate = match s {
+            "mean" | "avg" => Self::Mean,
+            "sum" => Self::Sum,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "first" => Self::First,
+            "last" => Self::Last,
+            "count" => Self::Count,
+            "stddev" => Self::Stddev,
+            _ => match s.strip_prefix('p').map(str::parse::<u8>) {
+                Some(Ok(p)) if p <= 100 => Self::Percentile(p),
+                _ => return Err(ApiError::InvalidAggregate(s.to_string())),
+            },
+        };
+        Ok(aggregate)
+    }
+}
+
+impl Aggregate {
+    /// name of the aggregate in responses, also used as column alias
+    pub fn name(&self) -> String {
+        match self {
+            Self::Mean => String::from("mean"),
+            Self::Sum => String::from("sum"),
+            Self::Min => String::from("min"),
+            Self::Max => String::from("max"),
+            Self::First => String::from("first"),
+            Self::Last => String::from("last"),
+            Self::Count => String::from("count"),
+            Self::Stddev => String::from("stddev"),
+            Self::Percentile(p) => format!("p{}", p),
+        }
+    }
+
+    /// sql expression aggregating `column` within a group, ordered by `time_column` where relevant
+    pub fn sql(&self, column: &str, time_column: &str) -> String {
+        match self {
+            Self::Mean => format!("avg({})", column),
+            Self::Sum => format!("sum({})", column),
+            Self::Min => format!("min({})", column),
+            Self::Max => format!("max({})", column),
+            Self::First => format!("first({}, {})", column, time_column),
+            Self::Last => format!("last({}, {})", column, time_column),
+            Self::Count => format!("count({})::double precision", column),
+            Self::Stddev => format!("stddev_samp({})", column),
+            Self::Percentile(p) => format!(
+                "percentile_cont({}) within group (order by {})",
+                *p as f64 / 100.0,
+                column
+            ),
+        }
+    }
+
+    /// sql expression combining the `rollup` columns of a `Rollup` view, values are scaled by `factor`.
+    /// None if the aggregate cannot be computed from rollups.
+    pub fn rollup_sql(&self, rollup: &str, factor: &str) -> Option<String> {
+        let sql = match self {
+            Self::Mean => format!(
+                "sum({0}.sum_value * {1}) / nullif(sum({0}.count_value), 0)",
+                rollup, factor
+            ),
+            Self::Sum => format!("sum({}.sum_value * {})", rollup, factor),
+            Self::Min => format!("min({}.min_value * {})", rollup, factor),
+            Self::Max => format!("max({}.max_value * {})", rollup, factor),
+            Self::Count => format!("sum({}.count_value)::double precision", rollup),
+            _ => return None,
+        };
+        Some(sql)
+    }
+}
+
+/// How buckets without any values are treated when resampling.
+/// Gaps are only filled between the first and the last datapoint within the requested range.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fill {
+    /// omit empty buckets
+    #[default]
+    None,
+    /// return empty buckets without values
+    Null,
+    /// carry the last value forward
+    Previous,
+    /// interpolate linearly between the surrounding values
+    Linear,
+    Zero,
+}
+
+impl Fill {
+    /// sql expression bucketing `time_column` in `timezone`, gap filled buckets are generated between `start` and `finish`
+    pub fn bucket_sql(
+        &self,
+        interval: &str,
+        time_column: &str,
+        timezone: &str,
+        start: &str,
+        finish: &str,
+    ) -> String {
+        match self {
+            Self::None => format!("time_bucket({}, {}, {})", interval, time_column, timezone),
+            _ => format!(
+                "time_bucket_gapfill({}, {}, {}, {}, {})",
+                interval, time_column, timezone, start, finish
+            ),
+        }
+    }
+
+    /// wrap an aggregate expression so its value is filled in empty buckets
+    pub fn apply(&self, aggregate: &str) -> String {
+        match self {
+            Self::None | Self::Null => aggregate.to_string(),
+            Self::Previous => format!("locf({})", aggregate),
+            Self::Linear => format!("interpolate({})", aggregate),
+            Self::Zero => format!("coalesce({}, 0)", aggregate),
+        }
+    }
+}
+
+/// Continuous aggregates of `ts` per series, see the `create_continuous_aggregates` migration.
+/// Queries read the coarsest rollup whose buckets exactly tile their buckets, see `Rollup::interior`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rollup {
+    Quarter,
+    Hour,
+    Day,
+}
+
+impl Rollup {
+    /// view holding the buckets of this rollup
+    pub fn view(&self) -> &'static str {
+        match self {
+            Self::Quarter => "ts_15min",
+            Self::Hour => "ts_1h",
+            Self::Day => "ts_1d",
+        }
+    }
+
+    fn width(&self) -> Duration {
+        match self {
+            Self::Quarter => Duration::minutes(15),
+            Self::Hour => Duration::hours(1),
+            Self::Day => Duration::days(1),
+        }
+    }
+
+    /// bucket width, passed to `ts_energy_rollup`
+    pub fn interval(&self) -> PgInterval {
+        PgInterval {
+            months: 0,
+            days: 0,
+            microseconds: self.width().whole_microseconds() as i64,
+        }
+    }
+
+    /// Start of the first and end of the last bucket of this rollup which lie completely within the range.
+    /// Datapoints before and after them have to be read from `ts`. None if there is no such bucket.
+    pub fn interior(
+        &self,
+        timestamp_filter: &TimestampFilter,
+    ) -> Option<(OffsetDateTime, OffsetDateTime)> {
+        let width = self.width().whole_microseconds();
+        let micros = |timestamp: OffsetDateTime| timestamp.unix_timestamp_nanos() / 1000;
+        let timestamp =
+            |micros: i128| OffsetDateTime::from_unix_timestamp_nanos(micros * 1000).ok();
+        let from = micros(timestamp_filter.from?);
+        let to = micros(timestamp_filter.to?) + 1;
+        let start = (from + width - 1).div_euclid(width) * width;
+        let end = to.div_euclid(width) * width;
+        (start < end).then_some((timestamp(start)?, timestamp(end)?))
+    }
+
+    /// The coarsest rollup which can answer a query bucketed by `interval` in `timezone`, None if
+    /// only the raw datapoints can. Queries without buckets pass None as `interval`.
+    /// The range has to contain at least one complete bucket of the rollup, see `Rollup::interior`.
+    /// Local days may start at any quarter hour, so only 15 minute rollups are used outside of UTC.
+    pub fn for_query(
+        interval: Option<&PgInterval>,
+        timestamp_filter: &TimestampFilter,
+        timezone: &Timezone,
+    ) -> Option<Self> {
+        // rollups only know the current datapoints
+        if timestamp_filter.as_of.is_some() {
+            return None;
+        }
+        [Self::Day, Self::Hour, Self::Quarter]
+            .into_iter()
+            .filter(|rollup| timezone.is_utc() || *rollup == Self::Quarter)
+            .find(|rollup| {
+                let width = rollup.width().whole_microseconds() as i64;
+                let fits = match interval {
+                    None => true,
+                    // months start at midnight
+                    Some(interval) if interval.months != 0 => true,
+                    Some(interval) => {
+                        let length = interval.days as i64 * 86_400_000_000 + interval.microseconds;
+                        length > 0 && length % width == 0
+                    }
+                };
+                fits && rollup.interior(timestamp_filter).is_some()
+            })
+    }
+}
+
 /// `Resampling` is a struct that represents the resampling configuration which is passed as a query parameter
 /// to endpoints that return resampled timeseries data.
 /// It contains an `interval` field which is a string that specifies the resampling interval.
-/// We assume that the resampling method is always taking the mean.
+/// `agg` is a comma separated list of aggregates computed per interval, e.g. "mean,min,max,p95".
+/// Endpoints which do not support other aggregates or `fill` always take the mean and omit empty buckets.
 #[derive(Debug, Deserialize)]
 pub struct Resampling {
     pub interval: String,
+    #[serde(default)]
+    pub agg: Option<String>,
+    #[serde(default)]
+    pub fill: Fill,
 }
 
 /// Provides a default instance of `Resampling`.
@@ -270,95 +952,476 @@ impl Default for Resampling {
     fn default() -> Self {
         Self {
             interval: String::from("1hour"),
+            agg: None,
+            fill: Fill::None,
         }
     }
 }
 
 impl Resampling {
-    pub fn map_interval(&self) -> std::result::Result<PgInterval, anyhow::Error> {
-        let re = Regex::new(r"(\d+)(\w+)").unwrap();
-        let caps = re
-            .captures(&self.interval)
-            .ok_or_else(|| anyhow!("Invalid interval format"))?;
-        let num_part = caps.get(1).map_or("", |m| m.as_str()).parse::<i32>()?;
-        let unit_part = caps.get(2).map_or("", |m| m.as_str());
-
-        let duration = match unit_part {
-            "month" => PgInterval {
-                months: num_part,
-                microseconds: 0,
-                days: 0,
-            },
-            "hour" => PgInterval {
-                months: 0,
-                microseconds: num_part as i64 * 60 * 60 * 1000000,
-                days: 0,
-            },
-            "year" => PgInterval {
-                months: 12 * num_part,
-                microseconds: 0,
-                days: 0,
-            },
-            "day" => PgInterval {
-                months: 0,
-                microseconds: 0,
-                days: num_part,
-            },
-            "week" => PgInterval {
-                months: 0,
-                microseconds: 0,
-                days: 7 * num_part,
-            },
-            "min" => PgInterval {
-                months: 0,
-                microseconds: num_part as i64 * 60 * 1000000,
-                days: 0,
-            },
-            _ => return Err(anyhow!("invalid interval format")),
-        };
-        Ok(duration)
+    /// The requested aggregates without duplicates, only the mean if none were requested.
+    pub fn aggregates(&self) -> Result<Vec<Aggregate>, ApiError> {
+        let mut aggregates: Vec<Aggregate> = vec![];
+        for name in self.agg.as_deref().unwrap_or("mean").split(',') {
+            let aggregate = name.trim().parse()?;
+            if !aggregates.contains(&aggregate) {
+                aggregates.push(aggregate);
+            }
+        }
+        Ok(aggregates)
+    }
+
+    /// Map `interval` to a postgres interval, see `parse_interval`.
+    /// Invalid intervals, also ones too long for a postgres interval, fail with `InvalidInterval`.
+    pub fn map_interval(&self) -> std::result::Result<PgInterval, ApiError> {
+        parse_interval(&self.interval).map_err(|_| ApiError::InvalidInterval)
     }
 
     pub fn validate_interval(&self) -> bool {
-        let pattern = Regex::new(r"^\d+(min|day|week|month|year|hour)$").expect("Invalid interval");
-        pattern.is_match(&self.interval)
+        self.map_interval().is_ok()
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct TimestampFilter {
-    #[serde(
-        with = "time::serde::rfc3339::option",
-        default = "TimestampFilter::default_from"
-    )]
-    pub from: Option<OffsetDateTime>,
-    #[serde(
-        with = "time::serde::rfc3339::option",
-        default = "TimestampFilter::default_to"
-    )]
-    pub to: Option<OffsetDateTime>,
+/// Parse an interval like "1hour" or "P1D" into a postgres interval.
+/// Accepts `<n><unit>` with the units sec, min, hour, day, week, month, quarter and year
+/// or an ISO 8601 duration such as "PT15M", "P1D" or "P3M".
+pub fn parse_interval(interval: &str) -> std::result::Result<PgInterval, anyhow::Error> {
+    let duration = if interval.starts_with('P') {
+        parse_iso_duration(interval)?
+    } else {
+        parse_unit_interval(interval)?
+    };
+    // time_bucket only supports intervals which are either a number of months or a fixed length
+    if duration.months != 0 && (duration.days != 0 || duration.microseconds != 0) {
+        return Err(anyhow!(
+            "intervals of months or years cannot have a day or time component"
+        ));
+    }
+    Ok(duration)
 }
 
-impl TimestampFilter {
-    fn default_from() -> Option<OffsetDateTime> {
-        Some(OffsetDateTime::UNIX_EPOCH)
-    }
+fn parse_unit_interval(interval: &str) -> std::result::Result<PgInterval, anyhow::Error> {
+    let re = Regex::new(r"(\d+)(\w+)").unwrap();
+    let caps = re
+        .captures(interval)
+        .ok_or_else(|| anyhow!("Invalid interval format"))?;
+    let num_part = caps.get(1).map_or("", |m| m.as_str()).parse::<i32>()?;
+    let unit_part = caps.get(2).map_or("", |m| m.as_str());
+    let too_long = || anyhow!("interval '{}' is too long", interval);
+    let months = |factor: i32| num_part.checked_mul(factor).ok_or_else(too_long);
+    let microseconds = |factor: i64| (num_part as i64).checked_mul(factor).ok_or_else(too_long);
 
-    fn default_to() -> Option<OffsetDateTime> {
-        Some(OffsetDateTime::now_utc())
-    }
+    let duration = match unit_part {
+        "month" => PgInterval {
+            months: num_part,
+            microseconds: 0,
+            days: 0,
+        },
+        "hour" => PgInterval {
+            months: 0,
+            microseconds: microseconds(60 * 60 * 1000000)?,
+            days: 0,
+        },
+        "quarter" => PgInterval {
+            months: months(3)?,
+            microseconds: 0,
+            days: 0,
+        },
+        "year" => PgInterval {
+            months: months(12)?,
+            microseconds: 0,
+            days: 0,
+        },
+        "day" => PgInterval {
+            months: 0,
+            microseconds: 0,
+            days: num_part,
+        },
+        "week" => PgInterval {
+            months: 0,
+            microseconds: 0,
+            days: num_part.checked_mul(7).ok_or_else(too_long)?,
+        },
+        "min" => PgInterval {
+            months: 0,
+            microseconds: microseconds(60 * 1000000)?,
+            days: 0,
+        },
+        "sec" => PgInterval {
+            months: 0,
+            microseconds: microseconds(1000000)?,
+            days: 0,
+        },
+        _ => return Err(anyhow!("invalid interval format")),
+    };
+    Ok(duration)
 }
 
-impl Default for TimestampFilter {
-    fn default() -> Self {
-        Self {
-            from: Self::default_from(),
-            to: Some(OffsetDateTime::now_utc()),
-        }
+/// Parse an ISO 8601 duration like "P1Y2M10DT2H30M" or "PT0.5S" into a postgres interval.
+fn parse_iso_duration(duration: &str) -> std::result::Result<PgInterval, anyhow::Error> {
+    let re = Regex::new(
+        r"^P(?:(\d+)Y)?(?:(\d+)M)?(?:(\d+)W)?(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+(?:\.\d+)?)S)?)?$",
+    )
+    .unwrap();
+    let caps = re
+        .captures(duration)
+        .filter(|_| duration != "P" && !duration.ends_with('T'))
+        .ok_or_else(|| anyhow!("Invalid ISO 8601 duration"))?;
+    let part = |i: usize| -> std::result::Result<i64, anyhow::Error> {
+        Ok(caps
+            .get(i)
+            .map(|x| x.as_str().parse())
+            .transpose()?
+            .unwrap_or(0))
+    };
+    let seconds: f64 = caps
+        .get(7)
+        .map(|x| x.as_str().parse())
+        .transpose()?
+        .unwrap_or(0.0);
+    let too_long = || anyhow!("ISO 8601 duration '{}' is too long", duration);
+    // `a * factor + b` of parts given by the user
+    let combine = |a: i64, factor: i64, b: i64| {
+        a.checked_mul(factor)
+            .and_then(|x| x.checked_add(b))
+            .ok_or_else(too_long)
+    };
+    let second_microseconds = (seconds * 1e6).round();
+    if second_microseconds >= i64::MAX as f64 {
+        return Err(too_long());
     }
+    let minutes = combine(part(5)?, 60, part(6)?)?;
+    Ok(PgInterval {
+        months: i32::try_from(combine(part(1)?, 12, part(2)?)?)?,
+        days: i32::try_from(combine(part(3)?, 7, part(4)?)?)?,
+        microseconds: combine(minutes, 60 * 1000000, second_microseconds as i64)?,
+    })
 }
 
-// intermediate struct to hold results for local consumption of grid electricity
+/// How power readings are integrated into energy, see the `ts_energy` database function.
+/// `rectangle` holds every reading until the next one, `trapezoid` interpolates linearly between readings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrationMethod {
+    #[default]
+    Rectangle,
+    Trapezoid,
+}
+
+impl IntegrationMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rectangle => "rectangle",
+            Self::Trapezoid => "trapezoid",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EnergyParams {
+    #[serde(default)]
+    pub method: IntegrationMethod,
+}
+
+/// Energy in kWh of a power series per resampling interval.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnergyTimeseries {
+    pub datapoints: Vec<EnergyDatapoint>,
+    pub meta: TimeseriesMeta,
+    pub method: IntegrationMethod,
+    pub unit: String,
+    pub total: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnergyDatapoint {
+    #[serde(with = "time::serde::rfc3339")]
+    pub bucket: OffsetDateTime,
+    pub value: f64,
+}
+
+/// How a series is reduced to a point budget, see the `downsample` module.
+/// `lttb` keeps the visually most significant datapoints, `minmax` keeps the extremes of each pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownsampleMethod {
+    #[default]
+    Lttb,
+    MinMax,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownsampleParams {
+    /// maximum number of datapoints returned
+    pub points: i64,
+    #[serde(default)]
+    pub method: DownsampleMethod,
+}
+
+/// `StatsParams` selects the percentiles and the optional histogram of the stats endpoint.
+#[derive(Debug, Deserialize)]
+pub struct StatsParams {
+    /// comma separated percentiles between 0 and 100
+    #[serde(default = "StatsParams::default_percentiles")]
+    pub percentiles: String,
+    /// number of equally wide histogram bins, the histogram is left out if not set
+    pub bins: Option<i64>,
+    /// range of the histogram, defaults to the smallest and largest value
+    pub bins_from: Option<f64>,
+    pub bins_to: Option<f64>,
+}
+
+impl StatsParams {
+    pub const MAX_BINS: i64 = 1000;
+
+    fn default_percentiles() -> String {
+        String::from("5,25,50,75,95")
+    }
+
+    pub fn percentiles(&self) -> Result<Vec<f64>> {
+        self.percentiles
+            .split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(|x| match x.parse::<f64>() {
+                Ok(percentile) if (0.0..=100.0).contains(&percentile) => Ok(percentile),
+                _ => Err(ApiError::InvalidPercentile(x.to_string())),
+            })
+            .collect()
+    }
+
+    /// Lower and upper edge of the histogram, `None` if there are neither values nor a configured range.
+    /// A single value gets a range of width 1 around it, like numpy does.
+    pub fn histogram_range(
+        &self,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Result<Option<(f64, f64)>> {
+        match self.bins {
+            None => return Ok(None),
+            Some(bins) if (1..=Self::MAX_BINS).contains(&bins) => {}
+            Some(bins) => {
+                return Err(ApiError::InvalidHistogram(format!(
+                    "bins must be between 1 and {}, got {}",
+                    Self::MAX_BINS,
+                    bins
+                )))
+            }
+        }
+        let (Some(from), Some(to)) = (self.bins_from.or(min), self.bins_to.or(max)) else {
+            return Ok(None);
+        };
+        if !from.is_finite() || !to.is_finite() || from > to {
+            return Err(ApiError::InvalidHistogram(format!(
+                "bins_from must not be greater than bins_to, got {} and {}",
+                from, to
+            )));
+        }
+        if from == to {
+            return Ok(Some((from - 0.5, to + 0.5)));
+        }
+        Ok(Some((from, to)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Percentile {
+    pub percentile: f64,
+    pub value: Option<f64>,
+}
+
+/// Bins include their lower edge, the last bin includes its upper edge as well.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistogramBin {
+    pub from: f64,
+    pub to: f64,
+    pub count: i64,
+}
+
+/// `below` and `above` count the values outside of a configured histogram range.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Histogram {
+    pub bins: Vec<HistogramBin>,
+    pub below: i64,
+    pub above: i64,
+}
+
+/// Descriptive statistics of the datapoints of a series within a time range.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeseriesStats {
+    pub meta: TimeseriesMeta,
+    /// first and last datapoint within the requested range
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    pub count: i64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    /// sample standard deviation
+    pub stddev: Option<f64>,
+    pub percentiles: Vec<Percentile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<Histogram>,
+}
+
+/// `Cursor` is used for keyset pagination of timeseries values ordered by timestamp.
+/// Only datapoints strictly after `after` are returned, at most `limit` of them.
+#[derive(Debug, Default, Deserialize)]
+pub struct Cursor {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub after: Option<OffsetDateTime>,
+    pub limit: Option<i64>,
+}
+
+/// If `stream` is set, rows are written to the response body as they are read from the database.
+#[derive(Debug, Default, Deserialize)]
+pub struct StreamMode {
+    #[serde(default)]
+    pub stream: bool,
+}
+
+/// Time range of a query, `from` defaults to the unix epoch and `to` to now.
+/// Instead of `from` and `to` a named `period` like "2019-05" or "last_30d" can be given, see `period::resolve`.
+/// With `as_of` datapoints are read as they were stored at that time, see `ts_revision`.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "TimestampFilterParams")]
+pub struct TimestampFilter {
+    pub from: Option<OffsetDateTime>,
+    pub to: Option<OffsetDateTime>,
+    pub as_of: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimestampFilterParams {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    to: Option<OffsetDateTime>,
+    period: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    as_of: Option<OffsetDateTime>,
+}
+
+impl TryFrom<TimestampFilterParams> for TimestampFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(params: TimestampFilterParams) -> std::result::Result<Self, Self::Error> {
+        match params.period {
+            Some(_) if params.from.is_some() || params.to.is_some() => {
+                Err(anyhow!("period cannot be combined with from or to"))
+            }
+            Some(period) => {
+                let (from, to) = period::resolve(&period, OffsetDateTime::now_utc())?;
+                Ok(Self {
+                    from: Some(from),
+                    to: Some(to),
+                    as_of: params.as_of,
+                })
+            }
+            None => Ok(Self {
+                from: params.from.or_else(Self::default_from),
+                to: params.to.or_else(Self::default_to),
+                as_of: params.as_of,
+            }),
+        }
+    }
+}
+
+impl TimestampFilter {
+    fn default_from() -> Option<OffsetDateTime> {
+        Some(OffsetDateTime::UNIX_EPOCH)
+    }
+
+    fn default_to() -> Option<OffsetDateTime> {
+        Some(OffsetDateTime::now_utc())
+    }
+}
+
+impl Default for TimestampFilter {
+    fn default() -> Self {
+        Self {
+            from: Self::default_from(),
+            to: Some(OffsetDateTime::now_utc()),
+            as_of: None,
+        }
+    }
+}
+
+/// `tz` is the timezone buckets of a day or longer are aligned to, e.g. "Europe/Berlin".
+/// Bucket timestamps in responses carry the offset of this timezone.
+#[derive(Debug, Deserialize)]
+pub struct Timezone {
+    #[serde(default = "Timezone::default_tz")]
+    pub tz: String,
+}
+
+impl Default for Timezone {
+    fn default() -> Self {
+        Self {
+            tz: Self::default_tz(),
+        }
+    }
+}
+
+impl Timezone {
+    fn default_tz() -> String {
+        String::from("UTC")
+    }
+
+    pub fn is_utc(&self) -> bool {
+        self.tz.eq_ignore_ascii_case("utc")
+    }
+
+    /// Fails with `InvalidTimezone` if postgres does not know the timezone.
+    pub async fn validate(&self, pool: &Pool<Postgres>) -> Result<(), ApiError> {
+        if self.is_utc() {
+            return Ok(());
+        }
+        let exists = sqlx::query_scalar!(
+            r#"select exists (select 1 from pg_timezone_names where name = $1) as "exists!""#,
+            self.tz
+        )
+        .fetch_one(pool)
+        .await?;
+        if !exists {
+            return Err(ApiError::InvalidTimezone(self.tz.clone()));
+        }
+        Ok(())
+    }
+
+    /// Shift every timestamp to the offset the timezone has at that instant.
+    pub async fn localize<'a>(
+        &self,
+        pool: &Pool<Postgres>,
+        timestamps: impl IntoIterator<Item = &'a mut OffsetDateTime>,
+    ) -> Result<(), ApiError> {
+        if self.is_utc() {
+            return Ok(());
+        }
+        let mut timestamps: Vec<&mut OffsetDateTime> = timestamps.into_iter().collect();
+        let instants: Vec<OffsetDateTime> = timestamps.iter().map(|x| **x).collect();
+        let offsets = sqlx::query_scalar!(
+            r#"
+            select extract(epoch from (instant at time zone $2) - (instant at time zone 'UTC'))::integer as "offset!"
+            from unnest($1::timestamptz[]) with ordinality as instants(instant, position)
+            order by position
+            "#,
+            &instants,
+            self.tz
+        )
+        .fetch_all(pool)
+        .await?;
+        for (timestamp, offset) in timestamps.iter_mut().zip(offsets) {
+            let offset = UtcOffset::from_whole_seconds(offset).map_err(anyhow::Error::from)?;
+            **timestamp = timestamp.to_offset(offset);
+        }
+        Ok(())
+    }
+}
+
+// intermediate struct to hold results for local consumption of grid electricity
 pub struct Consumption {
     pub bucket: Option<OffsetDateTime>,
     pub bucket_consumption: Option<f64>,
@@ -387,6 +1450,94 @@ pub struct KpiResult {
     pub from_timestamp: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
     pub to_timestamp: OffsetDateTime,
+    /// only set if requested by `count_flagged`, see `QualityFilter`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flagged_datapoints: Option<i64>,
+}
+
+/// `QualityFilter` is passed as a query parameter to kpi endpoints.
+#[derive(Debug, Default, Deserialize)]
+pub struct QualityFilter {
+    /// only use measured datapoints of all kpi inputs, this bypasses the rollups
+    #[serde(default)]
+    pub exclude_flagged: bool,
+    /// report the number of flagged datapoints of the series the kpi reads within the range
+    #[serde(default)]
+    pub count_flagged: bool,
+}
+
+/// Series a kpi reads, selected the same way as in the queries of `src/sql`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KpiInput {
+    /// `total_load`
+    TotalLoad,
+    /// all producers
+    Production,
+    /// local producers
+    LocalProduction,
+    /// `grid_reference_smard`
+    GridReference,
+    /// non-local consumers, the carrier mix of the grid
+    GridMix,
+    /// `smard_market_price`
+    MarketPrice,
+}
+
+impl KpiInput {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::TotalLoad => "total_load",
+            Self::Production => "production",
+            Self::LocalProduction => "local_production",
+            Self::GridReference => "grid_reference",
+            Self::GridMix => "grid_mix",
+            Self::MarketPrice => "market_price",
+        }
+    }
+}
+
+impl QualityFilter {
+    pub async fn flagged_datapoints(
+        &self,
+        pool: &Pool<Postgres>,
+        timestamp_filter: &TimestampFilter,
+        inputs: &[KpiInput],
+    ) -> Result<Option<i64>> {
+        if !self.count_flagged {
+            return Ok(None);
+        }
+        let inputs = inputs
+            .iter()
+            .map(|x| x.as_str().to_string())
+            .collect::<Vec<_>>();
+        let count = sqlx::query_scalar!(
+            r#"
+            select count(*) as "count!"
+            from ts_as_of(
+                array(
+                    select meta.id from meta
+                    where ('total_load' = any($4) and meta.identifier = 'total_load')
+                        or ('production' = any($4) and meta.consumption = false)
+                        or ('local_production' = any($4) and meta.consumption = false and meta.local = true)
+                        or ('grid_reference' = any($4) and meta.identifier = 'grid_reference_smard')
+                        or ('grid_mix' = any($4) and meta.consumption = true and meta.local = false)
+                        or ('market_price' = any($4) and meta.identifier = 'smard_market_price')
+                ),
+                $1,
+                $2,
+                $3
+            ) ts
+            where ts.quality <> 'measured'
+            "#,
+            timestamp_filter.from.unwrap(),
+            timestamp_filter.to.unwrap(),
+            timestamp_filter.as_of,
+            &inputs,
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(Some(count))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -448,10 +1599,296 @@ impl EmissionFactorSource {
     }
 }
 
+/// Options of the data quality checks, see `quality::QualityRules`.
+/// `expected_interval` defaults to the median distance between datapoints.
+/// `min` defaults to 0 for power and energy series, `flatline` is the shortest reported run of repeated values.
+/// Spikes jump from both neighbours by more than `spike_threshold` standard deviations of the differences between datapoints.
+#[derive(Debug, Deserialize)]
+pub struct QualityParams {
+    pub expected_interval: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    #[serde(default = "QualityParams::default_flatline")]
+    pub flatline: String,
+    #[serde(default = "QualityParams::default_spike_threshold")]
+    pub spike_threshold: f64,
+}
+
+impl QualityParams {
+    fn default_flatline() -> String {
+        String::from("1hour")
+    }
+
+    fn default_spike_threshold() -> f64 {
+        5.0
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QualityReport {
+    pub meta: TimeseriesMeta,
+    /// first and last datapoint within the requested range
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    pub expected_interval_seconds: Option<f64>,
+    #[serde(flatten)]
+    pub checks: QualityChecks,
+}
+
+/// Number of issues of a series, see `QualityReport`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QualitySummary {
+    pub identifier: String,
+    pub datapoints: i64,
+    pub coverage: Option<f64>,
+    pub gaps: usize,
+    pub gap_seconds: f64,
+    pub duplicates: i64,
+    pub out_of_range: usize,
+    pub flatlines: usize,
+    pub spikes: usize,
+}
+
+impl From<&QualityReport> for QualitySummary {
+    fn from(report: &QualityReport) -> Self {
+        Self {
+            identifier: report.meta.identifier.clone(),
+            datapoints: report.checks.datapoints,
+            coverage: report.checks.coverage,
+            gaps: report.checks.gaps.len(),
+            gap_seconds: report.checks.gaps.iter().map(|x| x.duration_seconds).sum(),
+            duplicates: report.checks.duplicates,
+            out_of_range: report.checks.out_of_range.len(),
+            flatlines: report.checks.flatlines.len(),
+            spikes: report.checks.spikes.len(),
+        }
+    }
+}
+
+/// Native compression of the `ts` hypertable, chunks older than `compress_after` are compressed by a policy.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompressionSettings {
+    pub enabled: bool,
+    pub segment_by: Vec<String>,
+    pub order_by: Vec<String>,
+    /// None if there is no compression policy
+    pub compress_after: Option<String>,
+}
+
+/// `compress_after` is an interval like "30day" or "P1M", the policy is removed if it is not set.
+#[derive(Debug, Deserialize)]
+pub struct CompressionPolicyBody {
+    pub compress_after: Option<String>,
+}
+
+/// Datapoints of a series older than `retention` are deleted by a job, they are kept forever without retention.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SeriesRetention {
+    pub identifier: String,
+    pub retention: Option<String>,
+}
+
+/// `retention` is an interval like "365day" or "P1Y", the retention is removed if it is not set.
+#[derive(Debug, Deserialize)]
+pub struct RetentionBody {
+    pub retention: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RetentionResult {
+    pub deleted: i64,
+}
+
+/// Size of a chunk of the `ts` hypertable, compressed chunks report their size before and after compression.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkStats {
+    pub chunk_name: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub range_start: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub range_end: Option<OffsetDateTime>,
+    pub is_compressed: bool,
+    pub total_bytes: i64,
+    pub before_compression_bytes: Option<i64>,
+    pub after_compression_bytes: Option<i64>,
+    pub compression_ratio: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkReport {
+    pub chunks: Vec<ChunkStats>,
+    pub total_bytes: i64,
+    /// uncompressed size of compressed chunks divided by their compressed size
+    pub compression_ratio: Option<f64>,
+}
+
+/// A unit of the registry, `factor` converts a value into the base unit of its `dimension`.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Unit {
+    pub name: String,
+    pub dimension: String,
+    pub factor: f64,
+}
+
+impl Unit {
+    pub async fn fetch(name: &str, pool: &Pool<Postgres>) -> Result<Unit, ApiError> {
+        sqlx::query_as!(
+            Unit,
+            "select name, dimension, factor from unit where unit.name = $1",
+            name
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::UnknownUnit(name.to_string()))
+    }
+
+    /// factor which converts a value in this unit into `target`
+    pub fn conversion_factor(&self, target: &Unit) -> Result<f64, ApiError> {
+        if self.dimension != target.dimension {
+            return Err(ApiError::IncompatibleUnits(
+                self.name.clone(),
+                target.name.clone(),
+            ));
+        }
+        Ok(self.factor / target.factor)
+    }
+}
+
+/// Target unit for timeseries reads and kpis, values keep their unit if `unit` is not set.
+#[derive(Debug, Default, Deserialize)]
+pub struct UnitConversion {
+    pub unit: Option<String>,
+}
+
+impl UnitConversion {
+    /// factor which converts values in `unit` into the requested unit
+    pub async fn factor(&self, unit: &str, pool: &Pool<Postgres>) -> Result<f64, ApiError> {
+        match &self.unit {
+            Some(target) if target.to_lowercase() != unit.to_lowercase() => {
+                let source = Unit::fetch(unit, pool).await?;
+                source.conversion_factor(&Unit::fetch(target, pool).await?)
+            }
+            _ => Ok(1.0),
+        }
+    }
+
+    /// the unit values are returned in
+    pub fn unit_or(&self, unit: &str) -> String {
+        self.unit.clone().unwrap_or_else(|| unit.to_string())
+    }
+}
+
+/// A forecast value for `timestamp` which was issued at `issued_at`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForecastDatapoint {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub issued_at: OffsetDateTime,
+    pub value: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForecastSeries {
+    pub meta: TimeseriesMeta,
+    pub datapoints: Vec<ForecastDatapoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewForecastDatapoint {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub value: f64,
+}
+
+/// One forecast run of a series, values are in the unit of the series.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewForecastBody {
+    #[serde(with = "time::serde::rfc3339")]
+    pub issued_at: OffsetDateTime,
+    pub datapoints: Vec<NewForecastDatapoint>,
+}
+
+/// `updated` counts forecasts of the same issue and target time which were overwritten.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddForecastResponse {
+    pub meta: TimeseriesMeta,
+    #[serde(with = "time::serde::rfc3339")]
+    pub issued_at: OffsetDateTime,
+    pub inserted: i64,
+    pub updated: i64,
+}
+
+/// A forecast run with the range of its target timestamps.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForecastIssue {
+    #[serde(with = "time::serde::rfc3339")]
+    pub issued_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub to: OffsetDateTime,
+    pub datapoints: i64,
+}
+
+/// Selects forecasts by their target timestamp within `from` and `to`, both are unbounded by default.
+/// Of every target timestamp the latest forecast is returned, `issued_at` selects a single forecast run
+/// and `issued_before` the latest forecast known at that time instead.
+#[derive(Debug, Default, Deserialize)]
+#[serde(try_from = "ForecastFilterParams")]
+pub struct ForecastFilter {
+    pub from: Option<OffsetDateTime>,
+    pub to: Option<OffsetDateTime>,
+    pub issued_at: Option<OffsetDateTime>,
+    pub issued_before: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastFilterParams {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    to: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    issued_at: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    issued_before: Option<OffsetDateTime>,
+}
+
+impl TryFrom<ForecastFilterParams> for ForecastFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(params: ForecastFilterParams) -> std::result::Result<Self, Self::Error> {
+        if params.issued_at.is_some() && params.issued_before.is_some() {
+            return Err(anyhow!("issued_at cannot be combined with issued_before"));
+        }
+        Ok(Self {
+            from: params.from,
+            to: params.to,
+            issued_at: params.issued_at,
+            issued_before: params.issued_before,
+        })
+    }
+}
+
+/// Errors of forecasts against the measured datapoints of the same series and timestamp.
+/// `mape` is in percent and skips measurements of 0, it is None if there are none left.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForecastErrors {
+    pub meta: TimeseriesMeta,
+    pub datapoints: i64,
+    pub mae: Option<f64>,
+    pub rmse: Option<f64>,
+    pub mape: Option<f64>,
+}
+
 #[test]
 fn test_map_interval() {
     let resample = Resampling {
         interval: String::from("1hour"),
+        ..Default::default()
     };
 
     assert_eq!(
@@ -465,6 +1902,7 @@ fn test_map_interval() {
 
     let resample = Resampling {
         interval: String::from("30min"),
+        ..Default::default()
     };
 
     assert_eq!(
@@ -478,7 +1916,204 @@ fn test_map_interval() {
 
     let resample = Resampling {
         interval: String::from("invalid"),
+        ..Default::default()
     };
 
     assert!(resample.map_interval().is_err());
+
+    // too long for a postgres interval instead of overflowing
+    for interval in [
+        "2147483647year",
+        "2147483647quarter",
+        "2147483647week",
+        "P9223372036854775807W",
+        "PT9223372036854775807H",
+        "PT99999999999999999999S",
+    ] {
+        let resample = Resampling {
+            interval: String::from(interval),
+            ..Default::default()
+        };
+        assert!(
+            matches!(resample.map_interval(), Err(ApiError::InvalidInterval)),
+            "{}",
+            interval
+        );
+    }
+
+    let interval = |interval: &str| {
+        Resampling {
+            interval: interval.to_string(),
+            ..Default::default()
+        }
+        .map_interval()
+    };
+    assert_eq!(interval("PT15M").unwrap(), interval("15min").unwrap());
+    assert_eq!(interval("P1D").unwrap(), interval("1day").unwrap());
+    assert_eq!(interval("P3M").unwrap(), interval("1quarter").unwrap());
+    assert_eq!(interval("P1Y").unwrap(), interval("4quarter").unwrap());
+    assert_eq!(interval("P2W").unwrap(), interval("2week").unwrap());
+    assert_eq!(interval("PT30S").unwrap(), interval("30sec").unwrap());
+    assert_eq!(
+        interval("P1DT1H0.5S").unwrap(),
+        PgInterval {
+            months: 0,
+            days: 1,
+            microseconds: 3600 * 1000000 + 500000
+        }
+    );
+    assert!(interval("P").is_err());
+    assert!(interval("PT").is_err());
+    assert!(interval("P1H").is_err());
+    assert!(interval("P1M1D").is_err());
+}
+
+#[test]
+fn test_validate_new_datapoint() {
+    let datapoint = NewDatapoint {
+        timestamp: OffsetDateTime::now_utc(),
+        value: 42.0,
+        identifier: String::from("test"),
+        quality: Default::default(),
+    };
+    assert!(datapoint.validate().is_ok());
+
+    let datapoint = NewDatapoint {
+        value: f64::NAN,
+        ..datapoint
+    };
+    assert_eq!(datapoint.validate(), Err(RejectionReason::NonFiniteValue));
+
+    let datapoint = NewDatapoint {
+        timestamp: OffsetDateTime::now_utc() + Duration::days(2),
+        value: 42.0,
+        ..datapoint
+    };
+    assert_eq!(
+        datapoint.validate(),
+        Err(RejectionReason::TimestampOutOfRange)
+    );
+}
+
+#[test]
+fn test_aggregates() {
+    let resample = Resampling::default();
+    assert_eq!(resample.aggregates().unwrap(), vec![Aggregate::Mean]);
+
+    let resample = Resampling {
+        agg: Some(String::from("sum,p95,avg,mean,p5")),
+        ..Default::default()
+    };
+    assert_eq!(
+        resample.aggregates().unwrap(),
+        vec![
+            Aggregate::Sum,
+            Aggregate::Percentile(95),
+            Aggregate::Mean,
+            Aggregate::Percentile(5)
+        ]
+    );
+
+    let resample = Resampling {
+        agg: Some(String::from("p101")),
+        ..Default::default()
+    };
+    assert!(resample.aggregates().is_err());
+}
+
+#[test]
+fn test_fill() {
+    assert_eq!(
+        Fill::None.bucket_sql("$2", "ts.series_timestamp", "$5", "$3", "$4"),
+        "time_bucket($2, ts.series_timestamp, $5)"
+    );
+    assert_eq!(
+        Fill::Linear.bucket_sql("$2", "ts.series_timestamp", "$5", "$3", "$4"),
+        "time_bucket_gapfill($2, ts.series_timestamp, $5, $3, $4)"
+    );
+    assert_eq!(Fill::Null.apply("avg(x)"), "avg(x)");
+    assert_eq!(Fill::Previous.apply("avg(x)"), "locf(avg(x))");
+    assert_eq!(Fill::Linear.apply("avg(x)"), "interpolate(avg(x))");
+    assert_eq!(Fill::Zero.apply("avg(x)"), "coalesce(avg(x), 0)");
+}
+
+#[test]
+fn test_rollup_for_query() {
+    use time::macros::datetime;
+
+    let utc = Timezone::default();
+    let berlin = Timezone {
+        tz: String::from("Europe/Berlin"),
+    };
+    let year = TimestampFilter {
+        from: Some(datetime!(2023-01-01 0:00 UTC)),
+        to: Some(datetime!(2023-12-31 23:59:59.999999 UTC)),
+        as_of: None,
+    };
+    let interval = |interval: &str| {
+        Resampling {
+            interval: String::from(interval),
+            ..Default::default()
+        }
+        .map_interval()
+        .unwrap()
+    };
+    assert_eq!(Rollup::for_query(None, &year, &utc), Some(Rollup::Day));
+    assert_eq!(
+        Rollup::for_query(Some(&interval("1month")), &year, &utc),
+        Some(Rollup::Day)
+    );
+    assert_eq!(
+        Rollup::for_query(Some(&interval("2hour")), &year, &utc),
+        Some(Rollup::Hour)
+    );
+    assert_eq!(
+        Rollup::for_query(Some(&interval("30min")), &year, &utc),
+        Some(Rollup::Quarter)
+    );
+    assert_eq!(
+        Rollup::for_query(Some(&interval("1day")), &year, &berlin),
+        Some(Rollup::Quarter)
+    );
+    assert_eq!(
+        Rollup::for_query(Some(&interval("5min")), &year, &utc),
+        None
+    );
+
+    // the unaligned edges are read from the raw datapoints
+    let unaligned = TimestampFilter {
+        from: Some(datetime!(2023-01-01 0:05 UTC)),
+        to: Some(datetime!(2023-01-03 12:20 UTC)),
+        as_of: None,
+    };
+    assert_eq!(Rollup::for_query(None, &unaligned, &utc), Some(Rollup::Day));
+    assert_eq!(
+        Rollup::Day.interior(&unaligned),
+        Some((
+            datetime!(2023-01-02 0:00 UTC),
+            datetime!(2023-01-03 0:00 UTC)
+        ))
+    );
+    assert_eq!(
+        Rollup::Quarter.interior(&unaligned),
+        Some((
+            datetime!(2023-01-01 0:15 UTC),
+            datetime!(2023-01-03 12:15 UTC)
+        ))
+    );
+    assert_eq!(
+        Rollup::Day.interior(&year),
+        Some((year.from.unwrap(), datetime!(2024-01-01 0:00 UTC)))
+    );
+    let short = TimestampFilter {
+        from: Some(datetime!(2023-01-01 0:05 UTC)),
+        to: Some(datetime!(2023-01-01 0:20 UTC)),
+        as_of: None,
+    };
+    assert_eq!(Rollup::for_query(None, &short, &utc), None);
+    let as_of = TimestampFilter {
+        as_of: Some(datetime!(2024-01-01 0:00 UTC)),
+        ..year
+    };
+    assert_eq!(Rollup::for_query(None, &as_of, &utc), None);
 }
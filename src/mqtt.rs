@@ -0,0 +1,373 @@
+use crate::error::ApiError;
+use crate::handlers::timeseries::ingest_datapoints;
+use crate::infrastructure::AppState;
+use crate::models::NewDatapoint;
+use crate::models::{AddTimeseriesResponse, IngestionOptions, MqttConfig, MqttSubscription};
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use serde_json::Value;
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+/// Datapoints kept while the database is unavailable, the oldest are dropped beyond this many batches.
+const MAX_BUFFERED_BATCHES: usize = 10;
+
+/// Subscribe to the topics of `config` and insert the values of their messages in batches.
+/// Runs until the server stops, connection errors are logged and the broker is reconnected.
+pub async fn run_bridge(app_state: AppState, config: MqttConfig) {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        options.set_credentials(username, password);
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 100);
+    let mut flush = tokio::time::interval(Duration::from_millis(config.flush_interval_ms));
+    let mut bridge = Bridge::new(config);
+
+    loop {
+        tokio::select! {
+            event = eventloop.poll() => match event {
+                // subscriptions are lost with the session, so subscribe after every connect
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    tracing::info!("Connected to MQTT broker");
+                    for subscription in &bridge.config.subscriptions {
+                        if let Err(e) = client.try_subscribe(&subscription.topic, QoS::AtLeastOnce) {
+                            tracing::error!("Failed to subscribe to {}: {}", subscription.topic, e);
+                        }
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    bridge.handle_message(&publish.topic, &publish.payload, OffsetDateTime::now_utc());
+                    if bridge.buffer.len() >= bridge.config.batch_size {
+                        bridge.flush(&app_state).await;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("MQTT connection error: {}", e);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            },
+            _ = flush.tick() => bridge.flush(&app_state).await,
+        }
+    }
+}
+
+struct Bridge {
+    config: MqttConfig,
+    buffer: Vec<NewDatapoint>,
+}
+
+impl Bridge {
+    fn new(config: MqttConfig) -> Self {
+        Self {
+            config,
+            buffer: vec![],
+        }
+    }
+
+    /// Buffer the values of all subscriptions matching `topic`, messages without them are ignored.
+    fn handle_message(&mut self, topic: &str, payload: &[u8], received_at: OffsetDateTime) {
+        let Ok(payload) = serde_json::from_slice::<Value>(payload) else {
+            tracing::warn!("Ignoring MQTT message on {} without json payload", topic);
+            return;
+        };
+        for subscription in &self.config.subscriptions {
+            let Some(levels) = match_topic(&subscription.topic, topic) else {
+                continue;
+            };
+            let Some(timestamp) = message_timestamp(subscription, &payload, received_at) else {
+                tracing::warn!("Ignoring MQTT message on {} without valid timestamp", topic);
+                continue;
+            };
+            for value in &subscription.values {
+                let Some(number) = lookup(&payload, value.path.as_deref()).and_then(as_f64) else {
+                    continue;
+                };
+                self.buffer.push(NewDatapoint {
+                    timestamp,
+                    value: number,
+                    identifier: render(&value.identifier, &levels),
+                    quality: subscription.quality,
+                });
+            }
+        }
+    }
+
+    /// Insert the buffered datapoints, they are kept for the next flush if the database is unavailable.
+    /// Other errors fail the whole batch, so its datapoints are inserted one by one and only the
+    /// failing ones are dropped.
+    async fn flush(&mut self, app_state: &AppState) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        match self.insert(app_state, &self.buffer).await {
+            Ok(response) => {
+                log_rejected(&response);
+                self.buffer.clear();
+            }
+            Err(e) if is_transient(&e) => self.keep_buffer(e),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to insert datapoints received over MQTT, inserting them one by one: {}",
+                    e
+                );
+                let mut buffer = std::mem::take(&mut self.buffer);
+                for i in 0..buffer.len() {
+                    let datapoint = &buffer[i];
+                    match self
+                        .insert(app_state, std::slice::from_ref(datapoint))
+                        .await
+                    {
+                        Ok(response) => log_rejected(&response),
+                        Err(e) if is_transient(&e) => {
+                            buffer.drain(..i);
+                            self.buffer = buffer;
+                            self.keep_buffer(e);
+                            return;
+                        }
+                        Err(e) => tracing::error!(
+                            "Dropping datapoint of '{}' at {} received over MQTT: {}",
+                            datapoint.identifier,
+                            datapoint.timestamp,
+                            e
+                        ),
+                    }
+                }
+            }
+        }
+    }
+
+    async fn insert(
+        &self,
+        app_state: &AppState,
+        datapoints: &[NewDatapoint],
+    ) -> Result<AddTimeseriesResponse, ApiError> {
+        let options = IngestionOptions {
+            on_conflict: self.config.on_conflict,
+            auto_create_meta: false,
+        };
+        ingest_datapoints(app_state, datapoints, &options, |_| Ok(None)).await
+    }
+
+    /// Keep the buffer for the next flush, the oldest datapoints are dropped beyond `MAX_BUFFERED_BATCHES`.
+    fn keep_buffer(&mut self, error: ApiError) {
+        tracing::error!("Failed to insert datapoints received over MQTT: {}", error);
+        let max = self.config.batch_size * MAX_BUFFERED_BATCHES;
+        if self.buffer.len() > max {
+            tracing::warn!(
+                "Dropping {} datapoints received over MQTT",
+                self.buffer.len() - max
+            );
+            self.buffer.drain(..self.buffer.len() - max);
+        }
+    }
+}
+
+fn log_rejected(response: &AddTimeseriesResponse) {
+    if let Some(first) = response.rejected.first() {
+        tracing::warn!(
+            "Rejected {} datapoints received over MQTT, first '{}': {}",
+            response.rejected.len(),
+            first.identifier,
+            first.reason.as_str()
+        );
+    }
+}
+
+/// Whether an insert may succeed when it is retried, like after connection or resource errors of the database.
+fn is_transient(error: &ApiError) -> bool {
+    match error {
+        ApiError::DatabaseError(sqlx::Error::Database(e)) => {
+            // connection exception, transaction rollback, insufficient resources, operator intervention
+            e.code().is_some_and(|code| {
+                ["08", "40", "53", "57"]
+                    .iter()
+                    .any(|class| code.starts_with(class))
+            })
+        }
+        ApiError::DatabaseError(e) => matches!(
+            e,
+            sqlx::Error::Io(_)
+                | sqlx::Error::Tls(_)
+                | sqlx::Error::Protocol(_)
+                | sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+                | sqlx::Error::WorkerCrashed
+        ),
+        _ => false,
+    }
+}
+
+/// Topic levels matched by the wildcards of `filter`, None if `topic` doesn't match.
+fn match_topic<'a>(filter: &str, topic: &'a str) -> Option<Vec<&'a str>> {
+    let mut levels = vec![];
+    let mut topic_levels = topic.split('/');
+    for (i, level) in filter.split('/').enumerate() {
+        if level == "#" {
+            // the remaining levels, `#` also matches the parent level itself
+            let start = topic.split('/').take(i).map(|x| x.len() + 1).sum::<usize>();
+            levels.push(topic.get(start..).unwrap_or_default());
+            return Some(levels);
+        }
+        let topic_level = topic_levels.next()?;
+        match level {
+            "+" => levels.push(topic_level),
+            _ if level == topic_level => {}
+            _ => return None,
+        }
+    }
+    topic_levels.next().is_none().then_some(levels)
+}
+
+/// Replace `{1}`, `{2}`, ... with the matched topic levels.
+fn render(template: &str, levels: &[&str]) -> String {
+    levels
+        .iter()
+        .enumerate()
+        .fold(template.to_string(), |identifier, (i, level)| {
+            identifier.replace(&format!("{{{}}}", i + 1), level)
+        })
+}
+
+fn lookup<'a>(payload: &'a Value, path: Option<&str>) -> Option<&'a Value> {
+    let Some(path) = path else {
+        return Some(payload);
+    };
+    path.split('.').try_fold(payload, |value, key| match value {
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => value.get(key),
+    })
+}
+
+/// Numbers, numeric strings and booleans as 0 and 1.
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(x) => x.as_f64(),
+        Value::String(x) => x.trim().parse().ok(),
+        Value::Bool(x) => Some(if *x { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+/// Timestamps without an offset, like those of Tasmota, are taken as UTC.
+fn message_timestamp(
+    subscription: &MqttSubscription,
+    payload: &Value,
+    received_at: OffsetDateTime,
+) -> Option<OffsetDateTime> {
+    let Some(path) = &subscription.timestamp else {
+        return Some(received_at);
+    };
+    match lookup(payload, Some(path))? {
+        Value::String(x) => OffsetDateTime::parse(x, &Rfc3339).ok().or_else(|| {
+            let format = format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]");
+            Some(PrimitiveDateTime::parse(x, &format).ok()?.assume_utc())
+        }),
+        Value::Number(x) => {
+            OffsetDateTime::from_unix_timestamp_nanos((x.as_f64()? * 1e9) as i128).ok()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_match_topic() {
+        assert_eq!(match_topic("a/b", "a/b"), Some(vec![]));
+        assert_eq!(match_topic("a/b", "a/b/c"), None);
+        assert_eq!(match_topic("a/b/c", "a/b"), None);
+        assert_eq!(
+            match_topic("tele/+/SENSOR", "tele/plug1/SENSOR"),
+            Some(vec!["plug1"])
+        );
+        assert_eq!(match_topic("tele/+/SENSOR", "tele/plug1/STATE"), None);
+        assert_eq!(match_topic("+/+", "a/b"), Some(vec!["a", "b"]));
+        assert_eq!(match_topic("a/#", "a/b/c"), Some(vec!["b/c"]));
+        assert_eq!(match_topic("a/#", "a"), Some(vec![""]));
+        assert_eq!(match_topic("+/#", "a/b"), Some(vec!["a", "b"]));
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&ApiError::DatabaseError(
+            sqlx::Error::PoolTimedOut
+        )));
+        assert!(!is_transient(&ApiError::DatabaseError(
+            sqlx::Error::RowNotFound
+        )));
+        assert!(!is_transient(&ApiError::DuplicateDatapoint));
+    }
+
+    #[test]
+    fn test_handle_message() {
+        let config: MqttConfig = serde_yaml::from_str(
+            r#"
+            host: localhost
+            subscriptions:
+              - topic: tele/+/SENSOR
+                timestamp: Time
+                values:
+                  - path: ENERGY.Power
+                    identifier: "{1}_power"
+                  - path: ENERGY.Phases.1
+                    identifier: "{1}_l2"
+                  - path: missing
+                    identifier: "{1}_missing"
+              - topic: meter/+/power
+                quality: estimated
+                values:
+                  - identifier: "meter_{1}"
+            "#,
+        )
+        .unwrap();
+        let mut bridge = Bridge::new(config);
+        let now = datetime!(2024-01-01 12:00 UTC);
+
+        bridge.handle_message(
+            "tele/plug1/SENSOR",
+            br#"{"Time": "2024-01-01T11:59:50", "ENERGY": {"Power": 12, "Phases": [1.5, "2.5"]}}"#,
+            now,
+        );
+        bridge.handle_message("meter/main/power", b"-3.5", now);
+        bridge.handle_message("meter/main/power", b"not json", now);
+        bridge.handle_message("tele/plug1/SENSOR", br#"{"ENERGY": {"Power": 1}}"#, now);
+
+        let datapoints = bridge
+            .buffer
+            .iter()
+            .map(|x| {
+                (
+                    x.identifier.as_str(),
+                    x.timestamp,
+                    x.value,
+                    x.quality.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            datapoints,
+            [
+                (
+                    "plug1_power",
+                    datetime!(2024-01-01 11:59:50 UTC),
+                    12.0,
+                    "measured"
+                ),
+                (
+                    "plug1_l2",
+                    datetime!(2024-01-01 11:59:50 UTC),
+                    2.5,
+                    "measured"
+                ),
+                ("meter_main", now, -3.5, "estimated"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use time::{Date, Duration, Month, OffsetDateTime};
+
+/// Calendar units named periods are aligned to.
+#[derive(Debug, Clone, Copy)]
+enum Calendar {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Calendar {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "day" => Some(Self::Day),
+            "week" => Some(Self::Week),
+            "month" => Some(Self::Month),
+            "quarter" => Some(Self::Quarter),
+            "year" => Some(Self::Year),
+            _ => None,
+        }
+    }
+
+    /// first day of the period containing `date`, weeks start on monday
+    fn start_of(&self, date: Date) -> Result<Date> {
+        let start = match self {
+            Self::Day => date,
+            Self::Week => date - Duration::days(date.weekday().number_days_from_monday() as i64),
+            Self::Month => date.replace_day(1)?,
+            Self::Quarter => {
+                let month = Month::try_from((date.month() as u8 - 1) / 3 * 3 + 1)?;
+                Date::from_calendar_date(date.year(), month, 1)?
+            }
+            Self::Year => Date::from_calendar_date(date.year(), Month::January, 1)?,
+        };
+        Ok(start)
+    }
+
+    /// first day of the period following the one starting at `start`
+    fn next(&self, start: Date) -> Result<Date> {
+        let next = match self {
+            Self::Day => start + Duration::days(1),
+            Self::Week => start + Duration::days(7),
+            Self::Month => add_months(start, 1)?,
+            Self::Quarter => add_months(start, 3)?,
+            Self::Year => add_months(start, 12)?,
+        };
+        Ok(next)
+    }
+
+    /// inclusive range of the period starting at `start`
+    fn range(&self, start: Date) -> Result<(OffsetDateTime, OffsetDateTime)> {
+        let from = start.midnight().assume_utc();
+        let to = self.next(start)?.midnight().assume_utc() - Duration::microseconds(1);
+        Ok((from, to))
+    }
+}
+
+fn add_months(date: Date, months: i32) -> Result<Date> {
+    let month_index = date.year() * 12 + date.month() as i32 - 1 + months;
+    let month = Month::try_from((month_index % 12 + 1) as u8)?;
+    Ok(Date::from_calendar_date(
+        month_index / 12,
+        month,
+        date.day(),
+    )?)
+}
+
+/// Resolve a named period into an inclusive `(from, to)` range in UTC.
+/// Supported are years ("2020"), quarters ("2020-Q3"), months ("2019-05"), days ("2019-05-01"),
+/// rolling ranges ending `now` ("last_12h", "last_30d", "last_2w"), "today", "yesterday"
+/// and the current or previous calendar week, month, quarter or year ("this_year", "last_month").
+/// Calendar periods, also "today" and the current or previous ones, are UTC days regardless of the
+/// timezone of a request.
+pub fn resolve(period: &str, now: OffsetDateTime) -> Result<(OffsetDateTime, OffsetDateTime)> {
+    let invalid = || anyhow!("invalid period '{}'", period);
+
+    let rolling = Regex::new(r"^last_(\d+)(h|d|w)$").unwrap();
+    if let Some(caps) = rolling.captures(period) {
+        let count: i64 = caps[1].parse().map_err(|_| invalid())?;
+        let unit = match &caps[2] {
+            "h" => 3600,
+            "d" => 86_400,
+            _ => 604_800,
+        };
+        // the constructors of `Duration` panic on overflow
+        let seconds = count.checked_mul(unit).ok_or_else(invalid)?;
+        let from = now
+            .checked_sub(Duration::seconds(seconds))
+            .ok_or_else(invalid)?;
+        return Ok((from, now));
+    }
+
+    let today = now.date();
+    match period {
+        "today" => return Calendar::Day.range(today),
+        "yesterday" => return Calendar::Day.range(today - Duration::days(1)),
+        _ => {}
+    }
+    if let Some((relative, unit)) = period.split_once('_') {
+        let calendar = Calendar::from_name(unit).ok_or_else(invalid)?;
+        let current = calendar.start_of(today)?;
+        return match relative {
+            "this" => calendar.range(current),
+            "last" => calendar.range(calendar.start_of(current - Duration::days(1))?),
+            _ => Err(invalid()),
+        };
+    }
+
+    let calendar_period = Regex::new(r"^(\d{4})(?:-Q([1-4])|-(\d{2})(?:-(\d{2}))?)?$").unwrap();
+    let caps = calendar_period.captures(period).ok_or_else(invalid)?;
+    let year: i32 = caps[1].parse()?;
+    let (calendar, month, day) = match (caps.get(2), caps.get(3), caps.get(4)) {
+        (Some(quarter), _, _) => (
+            Calendar::Quarter,
+            (quarter.as_str().parse::<u8>()? - 1) * 3 + 1,
+            1,
+        ),
+        (_, Some(month), None) => (Calendar::Month, month.as_str().parse()?, 1),
+        (_, Some(month), Some(day)) => (
+            Calendar::Day,
+            month.as_str().parse()?,
+            day.as_str().parse()?,
+        ),
+        _ => (Calendar::Year, 1, 1),
+    };
+    let start = Month::try_from(month)
+        .ok()
+        .and_then(|month| Date::from_calendar_date(year, month, day).ok())
+        .ok_or_else(invalid)?;
+    calendar.range(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_resolve() {
+        let now = datetime!(2024-02-14 10:30 UTC);
+        assert_eq!(
+            resolve("2019-05", now).unwrap(),
+            (
+                datetime!(2019-05-01 0:00 UTC),
+                datetime!(2019-05-31 23:59:59.999999 UTC)
+            )
+        );
+        assert_eq!(
+            resolve("2020-Q3", now).unwrap(),
+            (
+                datetime!(2020-07-01 0:00 UTC),
+                datetime!(2020-09-30 23:59:59.999999 UTC)
+            )
+        );
+        assert_eq!(
+            resolve("2020", now).unwrap().1,
+            datetime!(2020-12-31 23:59:59.999999 UTC)
+        );
+        assert_eq!(
+            resolve("2019-12-31", now).unwrap().0,
+            datetime!(2019-12-31 0:00 UTC)
+        );
+        assert_eq!(
+            resolve("last_30d", now).unwrap(),
+            (datetime!(2024-01-15 10:30 UTC), now)
+        );
+        assert_eq!(
+            resolve("this_year", now).unwrap(),
+            (
+                datetime!(2024-01-01 0:00 UTC),
+                datetime!(2024-12-31 23:59:59.999999 UTC)
+            )
+        );
+        assert_eq!(
+            resolve("last_month", now).unwrap().0,
+            datetime!(2024-01-01 0:00 UTC)
+        );
+        assert_eq!(
+            resolve("last_quarter", now).unwrap().0,
+            datetime!(2023-10-01 0:00 UTC)
+        );
+        assert_eq!(
+            resolve("this_week", now).unwrap().0,
+            datetime!(2024-02-12 0:00 UTC)
+        );
+        assert_eq!(
+            resolve("yesterday", now).unwrap().0,
+            datetime!(2024-02-13 0:00 UTC)
+        );
+
+        assert!(resolve("2019-13", now).is_err());
+        assert!(resolve("2020-Q5", now).is_err());
+        assert!(resolve("next_year", now).is_err());
+        assert!(resolve("this_decade", now).is_err());
+        assert!(resolve("last_30m", now).is_err());
+        assert!(resolve("last_9999999999999999h", now).is_err());
+        assert!(resolve("last_99999999999999999999w", now).is_err());
+    }
+}
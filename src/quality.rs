@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+
+/// Thresholds the datapoints of a series are checked against.
+#[derive(Debug, Clone)]
+pub struct QualityRules {
+    /// distance between two datapoints of a complete series
+    pub expected_interval: Duration,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// repeated values are reported once they last this long, runs of zeros are ignored
+    pub flatline: Duration,
+    /// a datapoint is a spike if it jumps by more than this from both of its neighbours in the same direction
+    pub spike_threshold: Option<f64>,
+}
+
+/// A period of datapoints with the same issue, `from` equals `to` for single datapoints.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityPeriod {
+    #[serde(with = "time::serde::rfc3339")]
+    pub from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub to: OffsetDateTime,
+    pub duration_seconds: f64,
+    /// datapoints within the period, for gaps the number of missing datapoints
+    pub datapoints: i64,
+    /// the repeated value of a flatline or the value of a spike
+    pub value: Option<f64>,
+}
+
+impl QualityPeriod {
+    fn new(from: OffsetDateTime, to: OffsetDateTime, datapoints: i64, value: Option<f64>) -> Self {
+        Self {
+            from,
+            to,
+            duration_seconds: (to - from).as_seconds_f64(),
+            datapoints,
+            value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QualityChecks {
+    pub datapoints: i64,
+    pub expected_datapoints: i64,
+    /// percentage of expected datapoints between the first and the last datapoint which are present
+    pub coverage: Option<f64>,
+    pub gaps: Vec<QualityPeriod>,
+    /// datapoints sharing their slot of the expected interval with a previous datapoint
+    pub duplicates: i64,
+    pub out_of_range: Vec<QualityPeriod>,
+    pub flatlines: Vec<QualityPeriod>,
+    pub spikes: Vec<QualityPeriod>,
+}
+
+/// Checks datapoints pushed in timestamp order, only the last few datapoints are held in memory.
+pub struct QualityAnalyzer {
+    rules: QualityRules,
+    checks: QualityChecks,
+    first: Option<OffsetDateTime>,
+    /// the two previous datapoints, the latest last
+    previous: [Option<(OffsetDateTime, f64)>; 2],
+    out_of_range: Option<QualityPeriod>,
+    flatline: Option<QualityPeriod>,
+}
+
+impl QualityAnalyzer {
+    pub fn new(rules: QualityRules) -> Self {
+        Self {
+            rules,
+            checks: QualityChecks::default(),
+            first: None,
+            previous: [None, None],
+            out_of_range: None,
+            flatline: None,
+        }
+    }
+
+    fn slot(&self, timestamp: OffsetDateTime) -> i128 {
+        timestamp.unix_timestamp_nanos() / self.rules.expected_interval.whole_nanoseconds().max(1)
+    }
+
+    pub fn push(&mut self, timestamp: OffsetDateTime, value: f64) {
+        self.checks.datapoints += 1;
+        self.first.get_or_insert(timestamp);
+
+        if let Some((last_timestamp, last_value)) = self.previous[1] {
+            let distance = timestamp - last_timestamp;
+            if distance > self.rules.expected_interval * 1.5 {
+                let missing = (distance.as_seconds_f64()
+                    / self.rules.expected_interval.as_seconds_f64())
+                .round() as i64
+                    - 1;
+                self.checks
+                    .gaps
+                    .push(QualityPeriod::new(last_timestamp, timestamp, missing, None));
+            }
+            if self.slot(timestamp) == self.slot(last_timestamp) {
+                self.checks.duplicates += 1;
+            }
+            if let (Some((_, before)), Some(threshold)) =
+                (self.previous[0], self.rules.spike_threshold)
+            {
+                let rise = last_value - before;
+                let fall = last_value - value;
+                if rise.abs() > threshold
+                    && fall.abs() > threshold
+                    && rise.signum() == fall.signum()
+                {
+                    self.checks.spikes.push(QualityPeriod::new(
+                        last_timestamp,
+                        last_timestamp,
+                        1,
+                        Some(last_value),
+                    ));
+                }
+            }
+        }
+
+        let outside = self.rules.min.map(|min| value < min).unwrap_or(false)
+            || self.rules.max.map(|max| value > max).unwrap_or(false);
+        match (&mut self.out_of_range, outside) {
+            (Some(period), true) => {
+                period.to = timestamp;
+                period.datapoints += 1;
+            }
+            (None, true) => {
+                self.out_of_range = Some(QualityPeriod::new(timestamp, timestamp, 1, None))
+            }
+            (_, false) => self.close_out_of_range(),
+        }
+
+        match &mut self.flatline {
+            Some(period) if period.value == Some(value) => {
+                period.to = timestamp;
+                period.datapoints += 1;
+            }
+            _ => {
+                self.close_flatline();
+                self.flatline = Some(QualityPeriod::new(timestamp, timestamp, 1, Some(value)));
+            }
+        }
+
+        self.previous = [self.previous[1], Some((timestamp, value))];
+    }
+
+    fn close_out_of_range(&mut self) {
+        if let Some(mut period) = self.out_of_range.take() {
+            period.duration_seconds = (period.to - period.from).as_seconds_f64();
+            self.checks.out_of_range.push(period);
+        }
+    }
+
+    fn close_flatline(&mut self) {
+        if let Some(mut period) = self.flatline.take() {
+            if period.value != Some(0.0) && period.to - period.from >= self.rules.flatline {
+                period.duration_seconds = (period.to - period.from).as_seconds_f64();
+                self.checks.flatlines.push(period);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> QualityChecks {
+        self.close_out_of_range();
+        self.close_flatline();
+        if let (Some(first), Some((last, _))) = (self.first, self.previous[1]) {
+            let expected = ((last - first).as_seconds_f64()
+                / self.rules.expected_interval.as_seconds_f64())
+            .floor() as i64
+                + 1;
+            let present = (self.checks.datapoints - self.checks.duplicates).min(expected);
+            self.checks.expected_datapoints = expected;
+            self.checks.coverage = Some(100.0 * present as f64 / expected as f64);
+        }
+        self.checks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QualityAnalyzer, QualityRules};
+    use time::macros::datetime;
+    use time::Duration;
+
+    #[test]
+    fn test_quality_analyzer() {
+        let start = datetime!(2024-01-01 0:00 UTC);
+        let mut analyzer = QualityAnalyzer::new(QualityRules {
+            expected_interval: Duration::minutes(15),
+            min: Some(0.0),
+            max: None,
+            flatline: Duration::hours(1),
+            spike_threshold: Some(50.0),
+        });
+        // 12 slots, 10:00 and 10:15 missing, a duplicate in the first slot
+        let datapoints = [
+            (0, 1.0),
+            (5, 2.0),
+            (15, 3.0),
+            (30, 100.0),
+            (45, 4.0),
+            (60, -14.0),
+            (75, -2.0),
+            (90, 7.0),
+            (135, 7.0),
+            (150, 7.0),
+            (165, 7.0),
+        ];
+        for (minutes, value) in datapoints {
+            analyzer.push(start + Duration::minutes(minutes), value);
+        }
+        let checks = analyzer.finish();
+        assert_eq!(checks.datapoints, 11);
+        assert_eq!(checks.duplicates, 1);
+        assert_eq!(checks.expected_datapoints, 12);
+        assert_eq!(checks.coverage, Some(100.0 * 10.0 / 12.0));
+
+        assert_eq!(checks.gaps.len(), 1);
+        assert_eq!(checks.gaps[0].from, start + Duration::minutes(90));
+        assert_eq!(checks.gaps[0].datapoints, 2);
+
+        assert_eq!(checks.spikes.len(), 1);
+        assert_eq!(checks.spikes[0].value, Some(100.0));
+
+        assert_eq!(checks.out_of_range.len(), 1);
+        assert_eq!(checks.out_of_range[0].datapoints, 2);
+        assert_eq!(checks.out_of_range[0].duration_seconds, 900.0);
+
+        assert_eq!(checks.flatlines.len(), 1);
+        assert_eq!(checks.flatlines[0].datapoints, 4);
+        assert_eq!(checks.flatlines[0].value, Some(7.0));
+    }
+}
@@ -3,49 +3,43 @@
 
 with local_production as (
     select
-        ts.series_timestamp as timestamp,
-        ts.series_value as value,
-        meta.identifier as identifier,
-        CASE
-            WHEN LAG(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp) IS NOT NULL 
-            THEN LEAST(extract(epoch FROM (ts.series_timestamp - lag(ts.series_timestamp) over (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp))) / 3600, 0.25)
-            ELSE LEAST(extract(epoch FROM (LEAD(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp)) - ts.series_timestamp) / 3600, 0.25)
-        END AS timestamp_distance
-    from ts
-            join meta on ts.meta_id = meta.id
-    where
-        meta.consumption = false
-        and ts.series_timestamp between $1 and $2
-), local_production_kwh as (
-    select
-        timestamp,
-        value * timestamp_distance as production
-    from local_production
+        energy.series_timestamp as timestamp,
+        energy.energy as production
+    from ts_energy_rollup(
+            array(select meta.id from meta where meta.consumption = false),
+            $1,
+            $2,
+            $4::interval,
+            exclude_flagged => $5::boolean,
+            as_of => $6::timestamptz
+         ) energy
 ), electricity_price_bucket as (
     select
-        time_bucket('1hour', ts.series_timestamp) as bucket,
-        avg(ts.series_value) as average_price,
+        time_bucket('1hour'::interval, ts.series_timestamp, $3::text) as bucket,
+        -- prices in EUR/kWh, the unit of the series is checked by `validate_price_unit`
+        avg(ts.series_value * unit_factor(meta.unit, 'price')) as average_price,
         meta.identifier as identifier,
         meta.unit as unit
-    from ts
+    from ts_as_of(null, $1, $2, $6::timestamptz) ts
         join meta on ts.meta_id = meta.id
     where
         meta.identifier = 'smard_market_price' and
-        ts.series_timestamp between $1 and $2
+        ts.series_timestamp between $1 and $2 and
+        (not $5::boolean or ts.quality = 'measured')
     group by
         bucket,
         meta.identifier,
         meta.unit
 ), local_production_bucket as (
     select
-        time_bucket('1hour', local_production_kwh.timestamp) as bucket,
-        sum(greatest(local_production_kwh.production, 0)) as production
-    from local_production_kwh
+        time_bucket('1hour'::interval, local_production.timestamp, $3::text) as bucket,
+        sum(greatest(local_production.production, 0)) as production
+    from local_production
     group by
         bucket
 ), intermediate as (
 select
-    local_production_bucket.production * (electricity_price_bucket.average_price / 1000) as value,
+    local_production_bucket.production * electricity_price_bucket.average_price as value,
     local_production_bucket.bucket
 from electricity_price_bucket
     join local_production_bucket on electricity_price_bucket.bucket = local_production_bucket.bucket
@@ -1,32 +1,23 @@
 -- get sum of energy in kWh produced by each local producer during time interval
-with local_production as (
+with kwh as (
     select
-        ts.series_timestamp as timestamp,
-        ts.series_value as value,
+        energy.series_timestamp as timestamp,
+        energy.energy as production,
         meta.unit as unit,
-        energy_carrier.name as energy_carrier,
-        CASE
-            WHEN LAG(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp) IS NOT NULL 
-            THEN LEAST(extract(epoch FROM (ts.series_timestamp - lag(ts.series_timestamp) over (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp))) / 3600, 0.25)
-            ELSE LEAST(extract(epoch FROM (LEAD(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp)) - ts.series_timestamp) / 3600, 0.25)
-        END AS timestamp_distance
-    from ts
-        join meta on ts.meta_id = meta.id
+        energy_carrier.name as energy_carrier
+    from ts_energy_rollup(
+            array(select meta.id from meta where meta.consumption = false and meta.local = true),
+            $1,
+            $2,
+            $5::interval,
+            exclude_flagged => $6::boolean,
+            as_of => $7::timestamptz
+         ) energy
+        join meta on energy.meta_id = meta.id
         join energy_carrier on meta.carrier = energy_carrier.id
-    where
-        meta.consumption = false and
-        meta.local = true
-        and ts.series_timestamp between $1 and $2
-), kwh as (
-    select
-        timestamp,
-        value * timestamp_distance as production,
-        unit,
-        energy_carrier
-    from local_production
 )
 select 
-    time_bucket($3, kwh.timestamp) as bucket,
+    time_bucket($3::interval, kwh.timestamp, $4::text) as bucket,
     sum(greatest(kwh.production, 0.0)) as bucket_consumption,
     kwh.unit as consumption_unit,
     kwh.energy_carrier as carrier_name,
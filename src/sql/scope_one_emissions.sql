@@ -1,42 +1,33 @@
-with local_production as (
+with kwh as (
     select
-        ts.series_timestamp as timestamp,
-        ts.series_value as value,
+        energy.series_timestamp as timestamp,
+        energy.energy as production,
         meta.identifier as identifier,
         energy_carrier.name as energy_carrier,
-        emission_factor.factor as emission_factor,
-        CASE
-            WHEN LAG(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp) IS NOT NULL 
-            THEN LEAST(extract(epoch FROM (ts.series_timestamp - lag(ts.series_timestamp) over (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp))) / 3600, 0.25)
-            ELSE LEAST(extract(epoch FROM (LEAD(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp)) - ts.series_timestamp) / 3600, 0.25)
-        END AS timestamp_distance
-    from ts
-             join meta on ts.meta_id = meta.id
+        emission_factor.factor as emission_factor
+    from ts_energy_rollup(
+            array(select meta.id from meta where meta.consumption = false and meta.local = true),
+            $1,
+            $2,
+            $6::interval,
+            exclude_flagged => $7::boolean,
+            as_of => $8::timestamptz
+         ) energy
+             join meta on energy.meta_id = meta.id
              join energy_carrier on meta.carrier = energy_carrier.id
              join emission_factor on energy_carrier.id = emission_factor.carrier
     where
-        meta.consumption = false and
-        meta.local = true and
         emission_factor.source = $4
-      and ts.series_timestamp between $1 and $2
-), kwh as (
-    select
-        timestamp,
-        value * timestamp_distance as production,
-        identifier,
-        energy_carrier,
-        emission_factor
-    from local_production
 ), production_with_emissions as (
     select
-        time_bucket($3::interval, kwh.timestamp) as bucket,
+        time_bucket($3::interval, kwh.timestamp, $5::text) as bucket,
         kwh.identifier                           as source_of_production,
         kwh.energy_carrier                       as production_carrier,
         sum(greatest(kwh.production, 0.0))       as production,
         kwh.emission_factor                      as emission_factor
     from kwh
     group by
-        time_bucket($3::interval, kwh.timestamp),
+        time_bucket($3::interval, kwh.timestamp, $5::text),
         kwh.energy_carrier,
         kwh.identifier,
         kwh.emission_factor
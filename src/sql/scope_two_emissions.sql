@@ -1,28 +1,30 @@
 with total_sum as (
     select
-        time_bucket($3, ts.series_timestamp) as bucket,
+        time_bucket($3::interval, ts.series_timestamp, $5::text) as bucket,
         sum(ts.series_value) as total
-    from ts
+    from ts_as_of(null, $1, $2, $8::timestamptz) ts
              join meta on ts.meta_id = meta.id
              join energy_carrier on meta.carrier = energy_carrier.id
     where
         meta.consumption = true and
         meta.local = false and
-        ts.series_timestamp between $1 and $2
+        ts.series_timestamp between $1 and $2 and
+        (not $7::boolean or ts.quality = 'measured')
 
     group by
         bucket
 ), carrier_sum as (
     select
-        time_bucket($3, ts.series_timestamp) as bucket,
+        time_bucket($3::interval, ts.series_timestamp, $5::text) as bucket,
         meta.carrier as carrier,
         sum(ts.series_value) as carrier_total
-    from ts
+    from ts_as_of(null, $1, $2, $8::timestamptz) ts
              join meta on ts.meta_id = meta.id
     where
         meta.consumption = true and
         meta.local = false and
-        ts.series_timestamp between $1 and $2
+        ts.series_timestamp between $1 and $2 and
+        (not $7::boolean or ts.quality = 'measured')
     group by
         bucket,
         carrier
@@ -35,24 +37,23 @@ with total_sum as (
              join total_sum on carrier_sum.bucket = total_sum.bucket
 ), consumption_by_carrier as (
     select
-        ts.series_timestamp as timestamp,
-        ts.series_value as value,
-        meta.carrier as carrier,
-        CASE
-            WHEN LAG(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp) IS NOT NULL 
-            THEN LEAST(extract(epoch FROM (ts.series_timestamp - lag(ts.series_timestamp) over (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp))) / 3600, 0.25)
-            ELSE LEAST(extract(epoch FROM (LEAD(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp)) - ts.series_timestamp) / 3600, 0.25)
-        END AS timestamp_distance
-    from ts
-             join meta on ts.meta_id = meta.id
-    where
-        meta.identifier = 'grid_reference_smard' and
-        ts.series_timestamp between $1 and $2
+        energy.series_timestamp as timestamp,
+        energy.energy as energy,
+        meta.carrier as carrier
+    from ts_energy_rollup(
+            array(select meta.id from meta where meta.identifier = 'grid_reference_smard'),
+            $1,
+            $2,
+            $6::interval,
+            exclude_flagged => $7::boolean,
+            as_of => $8::timestamptz
+         ) energy
+             join meta on energy.meta_id = meta.id
 ), consumption as (
     select
-        time_bucket($3::interval, timestamp) as bucket,
+        time_bucket($3::interval, timestamp, $5::text) as bucket,
         consumption_by_carrier.carrier as carrier,
-        sum(greatest(value * timestamp_distance, 0)) as consumption
+        sum(greatest(energy, 0)) as consumption
     from
         consumption_by_carrier
     group by
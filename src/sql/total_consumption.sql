@@ -1,21 +1,13 @@
 --
 -- get total sum of energy in kWh consumed in time period
 --
-with producers_ts as (
-    select
-        ts.series_timestamp as timestamp,
-        ts.series_value as value,
-        CASE
-            WHEN LAG(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp) IS NOT NULL 
-            THEN LEAST(extract(epoch FROM (ts.series_timestamp - lag(ts.series_timestamp) over (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp))) / 3600, 0.25)
-            ELSE LEAST(extract(epoch FROM (LEAD(ts.series_timestamp) OVER (PARTITION BY ts.meta_id ORDER BY ts.series_timestamp)) - ts.series_timestamp) / 3600, 0.25)
-        END AS timestamp_distance
-    from ts
-        join meta on ts.meta_id = meta.id
-    where
-        meta.identifier = 'total_load' and
-        ts.series_timestamp between $1 and $2
-)
-select 
-    sum(value * timestamp_distance) as value
-from producers_ts
\ No newline at end of file
+select
+    sum(energy.energy) as value
+from ts_energy_rollup(
+        array(select meta.id from meta where meta.identifier = 'total_load'),
+        $1,
+        $2,
+        $3::interval,
+        exclude_flagged => $4::boolean,
+        as_of => $5::timestamptz
+     ) energy
\ No newline at end of file
@@ -0,0 +1,114 @@
+use crate::models::{
+    ChunkReport, CompressionSettings, RetentionResult, SeriesRetention, Timeseries,
+};
+use crate::tests::test_util::{add_meta, get_client, get_random_string};
+use axum::http::StatusCode;
+use serde_json::json;
+use time::OffsetDateTime;
+
+#[tokio::test]
+async fn test_compression_policy() {
+    let client = get_client().await;
+
+    let response = client
+        .put("/v1/admin/compression/")
+        .json(&json!({"compress_after": "60day"}))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: CompressionSettings = response.json().await;
+    assert_eq!(body.compress_after.as_deref(), Some("60 days"));
+    assert_eq!(body.segment_by, vec!["meta_id"]);
+
+    let response = client
+        .put("/v1/admin/compression/")
+        .json(&json!({"compress_after": "P30D"}))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client.get("/v1/admin/compression/").send().await;
+    assert!(response.status().is_success());
+    let body: CompressionSettings = response.json().await;
+    assert_eq!(body.compress_after.as_deref(), Some("30 days"));
+
+    let response = client
+        .put("/v1/admin/compression/")
+        .json(&json!({"compress_after": "soon"}))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_series_retention() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let rfc_3339_format = &time::format_description::well_known::Rfc3339;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2000-01-01T00:00:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": OffsetDateTime::now_utc().format(rfc_3339_format).unwrap(),
+            "value": 2,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .put(&format!("/v1/admin/retention/{}/", identifier))
+        .json(&json!({"retention": "365day"}))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: SeriesRetention = response.json().await;
+    assert_eq!(body.retention.as_deref(), Some("365 days"));
+
+    let response = client.get("/v1/admin/retention/").send().await;
+    let body: Vec<SeriesRetention> = response.json().await;
+    assert!(body.iter().any(|x| x.identifier == identifier));
+
+    let response = client.post("/v1/admin/retention/apply/").send().await;
+    assert!(response.status().is_success());
+    let body: RetentionResult = response.json().await;
+    assert!(body.deleted >= 1);
+
+    let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 1);
+    assert_eq!(body.datapoints[0].value, 2.0);
+
+    let response = client
+        .put(&format!("/v1/admin/retention/{}/", identifier))
+        .json(&json!({"retention": null}))
+        .send()
+        .await;
+    let body: SeriesRetention = response.json().await;
+    assert_eq!(body.retention, None);
+
+    let response = client
+        .put(&format!("/v1/admin/retention/{}/", get_random_string(10)))
+        .json(&json!({"retention": "1day"}))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_chunks() {
+    let client = get_client().await;
+
+    let response = client.get("/v1/admin/chunks/").send().await;
+    assert!(response.status().is_success());
+    let body: ChunkReport = response.json().await;
+    assert_eq!(
+        body.total_bytes,
+        body.chunks.iter().map(|x| x.total_bytes).sum::<i64>()
+    );
+}
@@ -0,0 +1,269 @@
+use crate::app_config::AppConfig;
+use crate::columnar::{read_parquet, ARROW_STREAM_CONTENT_TYPE, PARQUET_CONTENT_TYPE};
+use crate::import::{import, import_parquet};
+use crate::infrastructure::create_connection_pool;
+use crate::models::{ImportConfig, NewDatapoint, Timeseries};
+use crate::tests::test_util::add_meta;
+use crate::tests::test_util::get_client;
+use crate::tests::test_util::get_random_string;
+
+use arrow::array::{Array, Float64Array, StringArray};
+use arrow::ipc::reader::StreamReader;
+use axum::http::header;
+use serde_json::json;
+use time::macros::datetime;
+
+#[tokio::test]
+async fn test_export_csv_round_trip() {
+    let client = get_client().await;
+    let first = get_random_string(10);
+    let second = get_random_string(10);
+
+    add_meta(&client, &first).await;
+    add_meta(&client, &second).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2019-01-01T00:00:00Z",
+            "value": 1.5,
+            "identifier": first
+        }, {
+            "timestamp": "2019-01-01T00:00:00Z",
+            "value": -14,
+            "identifier": second
+        }, {
+            "timestamp": "2019-01-01T00:15:00Z",
+            "value": 2.25,
+            "identifier": first
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let export_url = format!(
+        "/v1/ts/export/?identifiers={},{}&from=2019-01-01T00:00:00Z&to=2019-01-02T00:00:00Z",
+        first, second
+    );
+    let response = client.get(&export_url).send().await;
+    assert!(response.status().is_success());
+    let csv = response.text().await;
+    assert_eq!(
+        csv,
+        format!(
+            "Time,{},{}\n2019-01-01 00:00:00+00:00,1.5,-14\n2019-01-01 00:15:00+00:00,2.25,\n",
+            first, second
+        )
+    );
+
+    let response = client
+        .get(&format!("{}&format=import_config", export_url))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let import_config: ImportConfig = serde_yaml::from_str(&response.text().await).unwrap();
+    assert_eq!(import_config.time_column, "Time");
+    assert_eq!(import_config.timeseries.len(), 2);
+
+    for identifier in [&first, &second] {
+        let response = client
+            .delete(&format!("/v1/ts/{}/", identifier))
+            .send()
+            .await;
+        assert!(response.status().is_success());
+    }
+
+    let pool = create_connection_pool(&AppConfig::new()).await;
+    let reader = csv::Reader::from_reader(csv.as_bytes());
+    import(&pool, vec![reader], &import_config).await.unwrap();
+
+    let response = client.get(&format!("/v1/ts/{}/", first)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 2);
+    assert_eq!(body.datapoints[1].value, 2.25);
+
+    let response = client.get(&format!("/v1/ts/{}/", second)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 1);
+    assert_eq!(body.datapoints[0].value, -14.0);
+}
+
+#[tokio::test]
+async fn test_export_ndjson() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2019-01-01T00:00:00Z",
+            "value": 1.5,
+            "identifier": identifier
+        }, {
+            "timestamp": "2019-01-01T00:15:00Z",
+            "value": 2.25,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/export?identifiers={}&format=ndjson&from=2019-01-01T00:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let lines = response
+        .text()
+        .await
+        .lines()
+        .map(|line| serde_json::from_str::<NewDatapoint>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1].value, 2.25);
+    assert_eq!(lines[1].identifier, identifier);
+}
+
+#[tokio::test]
+async fn test_export_unknown_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    let response = client
+        .get(&format!("/v1/ts/export/?identifiers={}", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_export_parquet_round_trip() {
+    let client = get_client().await;
+    let first = get_random_string(10);
+    let second = get_random_string(10);
+
+    add_meta(&client, &first).await;
+    add_meta(&client, &second).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2019-01-01T00:00:00Z",
+            "value": 1.5,
+            "identifier": first
+        }, {
+            "timestamp": "2019-01-01T00:00:00Z",
+            "value": -14,
+            "identifier": second
+        }, {
+            "timestamp": "2019-01-01T00:15:00Z",
+            "value": 2.25,
+            "identifier": first
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let export_url = format!(
+        "/v1/ts/export/?identifiers={},{}&from=2019-01-01T00:00:00Z&to=2019-01-02T00:00:00Z",
+        first, second
+    );
+    let response = client
+        .get(&export_url)
+        .header(header::ACCEPT, PARQUET_CONTENT_TYPE)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        PARQUET_CONTENT_TYPE
+    );
+    let file = response.bytes().await;
+
+    let identifiers = [first.clone(), second.clone()];
+    let columns = read_parquet(file.clone(), "Time", &identifiers).unwrap();
+    assert_eq!(
+        columns.timestamps,
+        [
+            datetime!(2019-01-01 0:00 UTC),
+            datetime!(2019-01-01 0:15 UTC)
+        ]
+    );
+    assert_eq!(columns.values[&first], [Some(1.5), Some(2.25)]);
+    assert_eq!(columns.values[&second], [Some(-14.0), None]);
+
+    let response = client
+        .get(&format!("{}&format=import_config", export_url))
+        .send()
+        .await;
+    let import_config: ImportConfig = serde_yaml::from_str(&response.text().await).unwrap();
+    for identifier in &identifiers {
+        let response = client
+            .delete(&format!("/v1/ts/{}/", identifier))
+            .send()
+            .await;
+        assert!(response.status().is_success());
+    }
+
+    let pool = create_connection_pool(&AppConfig::new()).await;
+    import_parquet(&pool, file, &import_config).await.unwrap();
+
+    let response = client.get(&format!("/v1/ts/{}/", first)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 2);
+    assert_eq!(body.datapoints[1].value, 2.25);
+
+    let response = client.get(&format!("/v1/ts/{}/", second)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 1);
+}
+
+#[tokio::test]
+async fn test_get_timeseries_arrow_stream() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2019-01-01T00:00:00Z",
+            "value": 1.5,
+            "identifier": identifier
+        }, {
+            "timestamp": "2019-01-01T00:15:00Z",
+            "value": 2.25,
+            "identifier": identifier,
+            "quality": "estimated"
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/?from=2019-01-01T00:00:00Z&unit=W",
+            identifier
+        ))
+        .header(header::ACCEPT, ARROW_STREAM_CONTENT_TYPE)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body = response.bytes().await;
+    let mut reader = StreamReader::try_new(&body[..], None).unwrap();
+    assert_eq!(reader.schema().metadata()["unit"], "W");
+    let batch = reader.next().unwrap().unwrap();
+    let values = batch
+        .column_by_name("value")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap();
+    assert_eq!(values.values(), &[1500.0, 2250.0]);
+    let quality = batch
+        .column_by_name("quality")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(quality.value(1), "estimated");
+    assert_eq!(quality.len(), 2);
+}
@@ -0,0 +1,166 @@
+use crate::models::{AddForecastResponse, ForecastErrors, ForecastIssue, ForecastSeries};
+use crate::tests::test_util::add_meta;
+use crate::tests::test_util::get_client;
+use crate::tests::test_util::get_random_string;
+
+use axum::http::StatusCode;
+use serde_json::json;
+use time::macros::datetime;
+
+#[tokio::test]
+async fn test_add_and_get_forecast() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    // two runs forecast the same targets
+    for (issued_at, value) in [("2024-02-01T06:00:00Z", 1.0), ("2024-02-01T12:00:00Z", 2.0)] {
+        let response = client
+            .post(&format!("/v1/forecast/{}/", identifier))
+            .json(&json!({
+                "issued_at": issued_at,
+                "datapoints": [
+                    { "timestamp": "2024-02-01T13:00:00Z", "value": value },
+                    { "timestamp": "2024-02-01T14:00:00Z", "value": value }
+                ]
+            }))
+            .send()
+            .await;
+        assert!(response.status().is_success());
+        let body: AddForecastResponse = response.json().await;
+        assert_eq!(body.inserted, 2);
+    }
+
+    let response = client
+        .post(&format!("/v1/forecast/{}/", identifier))
+        .json(&json!({
+            "issued_at": "2024-02-01T12:00:00Z",
+            "datapoints": [{ "timestamp": "2024-02-01T14:00:00Z", "value": 3.0 }]
+        }))
+        .send()
+        .await;
+    let body: AddForecastResponse = response.json().await;
+    assert_eq!((body.inserted, body.updated), (0, 1));
+
+    let response = client
+        .get(&format!("/v1/forecast/{}/", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: ForecastSeries = response.json().await;
+    let values = body.datapoints.iter().map(|x| x.value).collect::<Vec<_>>();
+    assert_eq!(values, [2.0, 3.0]);
+    assert_eq!(
+        body.datapoints[0].issued_at,
+        datetime!(2024-02-01 12:00 UTC)
+    );
+
+    let response = client
+        .get(&format!(
+            "/v1/forecast/{}/?issued_at=2024-02-01T06:00:00Z&unit=W",
+            identifier
+        ))
+        .send()
+        .await;
+    let body: ForecastSeries = response.json().await;
+    let values = body.datapoints.iter().map(|x| x.value).collect::<Vec<_>>();
+    assert_eq!(values, [1000.0, 1000.0]);
+
+    // the latest forecast known before the second run
+    let response = client
+        .get(&format!(
+            "/v1/forecast/{}/?issued_before=2024-02-01T11:00:00Z&from=2024-02-01T14:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    let body: ForecastSeries = response.json().await;
+    assert_eq!(body.datapoints.len(), 1);
+    assert_eq!(body.datapoints[0].value, 1.0);
+
+    let response = client
+        .get(&format!("/v1/forecast/{}/issues/", identifier))
+        .send()
+        .await;
+    let body: Vec<ForecastIssue> = response.json().await;
+    assert_eq!(body.len(), 2);
+    assert_eq!(body[1].issued_at, datetime!(2024-02-01 12:00 UTC));
+    assert_eq!(body[1].datapoints, 2);
+
+    let response = client
+        .get(&format!(
+            "/v1/forecast/{}/?issued_at=2024-02-01T06:00:00Z&issued_before=2024-02-01T06:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_add_forecast_unknown_identifier() {
+    let client = get_client().await;
+    let response = client
+        .post(&format!("/v1/forecast/{}/", get_random_string(10)))
+        .json(&json!({ "issued_at": "2024-02-01T06:00:00Z", "datapoints": [] }))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_forecast_errors() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({
+            "timeseries": [
+                { "timestamp": "2024-02-01T13:00:00Z", "value": 10, "identifier": identifier },
+                { "timestamp": "2024-02-01T14:00:00Z", "value": 20, "identifier": identifier },
+                { "timestamp": "2024-02-01T15:00:00Z", "value": 0, "identifier": identifier }
+            ]
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let response = client
+        .post(&format!("/v1/forecast/{}/", identifier))
+        .json(&json!({
+            "issued_at": "2024-02-01T12:00:00Z",
+            "datapoints": [
+                { "timestamp": "2024-02-01T13:00:00Z", "value": 12 },
+                { "timestamp": "2024-02-01T14:00:00Z", "value": 16 },
+                { "timestamp": "2024-02-01T15:00:00Z", "value": 2 },
+                { "timestamp": "2024-02-01T16:00:00Z", "value": 5 }
+            ]
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!("/v1/forecast/{}/errors/", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: ForecastErrors = response.json().await;
+    // errors of 2, 4 and 2, the measurement of 0 is skipped by the mape
+    assert_eq!(body.datapoints, 3);
+    assert!((body.mae.unwrap() - 8.0 / 3.0).abs() < 1e-9);
+    assert!((body.rmse.unwrap() - 8.0_f64.sqrt()).abs() < 1e-9);
+    assert!((body.mape.unwrap() - 20.0).abs() < 1e-9);
+
+    let response = client
+        .get(&format!(
+            "/v1/forecast/{}/errors/?from=2024-02-01T16:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    let body: ForecastErrors = response.json().await;
+    assert_eq!(body.datapoints, 0);
+    assert_eq!(body.mae, None);
+}
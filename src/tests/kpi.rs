@@ -1,4 +1,12 @@
-use crate::tests::test_util::get_client;
+use crate::app_config::AppConfig;
+use crate::error::ApiError;
+use crate::handlers::kpi::validate_price_unit;
+use crate::infrastructure::create_connection_pool;
+use crate::tests::test_util::{get_client, get_random_string};
+use axum::http::StatusCode;
+use serde_json::json;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 
 use crate::models::{EmissionsByCarrier, KpiResult};
 
@@ -41,6 +49,29 @@ async fn test_get_total_consumption() {
     let _body: KpiResult = response.json().await;
 }
 
+#[tokio::test]
+async fn test_get_total_consumption_unit_conversion() {
+    let client = get_client().await;
+
+    let response = client
+        .get(
+            "/v1/kpi/total_consumption/?from=2019-01-01T12:00:00Z&to=2019-02-01T12:00:00Z&unit=MWh",
+        )
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: KpiResult = response.json().await;
+    assert_eq!(body.unit.as_deref(), Some("MWh"));
+
+    let response = client
+        .get(
+            "/v1/kpi/total_consumption/?from=2019-01-01T12:00:00Z&to=2019-02-01T12:00:00Z&unit=EUR",
+        )
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn test_get_total_co2_emissions() {
     let client = get_client().await;
@@ -156,3 +187,194 @@ async fn test_scoped_emission_sum() {
     // we need to floor the values because the sum of the scopes might not be exactly the same as the total
     assert_eq!((sum_scope_one + sum_scope_two).floor(), body.value.floor());
 }
+
+#[tokio::test]
+async fn test_get_scope_two_emissions_timezone() {
+    let client = get_client().await;
+
+    let response = client
+        .get("/v1/kpi/scope_two_emissions/?from=2019-01-01T12:00:00Z&to=2019-02-01T12:00:00Z&interval=1day&tz=Europe/Berlin")
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let _body: Vec<EmissionsByCarrier> = response.json().await;
+
+    let response = client
+        .get("/v1/kpi/scope_two_emissions/?from=2019-01-01T12:00:00Z&to=2019-02-01T12:00:00Z&interval=1day&tz=Berlin")
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_kpis_from_rollups() {
+    let client = get_client().await;
+
+    // a calendar month with daily buckets lines up with the daily rollups
+    for kpi in [
+        "consumption",
+        "local_consumption",
+        "total_consumption",
+        "total_production",
+        "scope_one_emissions",
+        "scope_two_emissions",
+        "total_co2_emissions",
+        "self_consumption",
+        "cost_savings",
+        "co2_savings",
+        "total_grid_electricity_cost",
+    ] {
+        let response = client
+            .get(&format!("/v1/kpi/{}/?period=2019-01&interval=1day", kpi))
+            .send()
+            .await;
+        assert!(response.status().is_success(), "{}", kpi);
+    }
+}
+
+#[tokio::test]
+async fn test_validate_price_unit() {
+    let pool = create_connection_pool(&AppConfig::new()).await;
+
+    // prices in an energy unit are rejected instead of guessing their price unit
+    for (unit, valid) in [("EUR/MWh", true), ("MWh", false)] {
+        let identifier = get_random_string(10);
+        sqlx::query("insert into meta (identifier, unit) values ($1, $2)")
+            .bind(&identifier)
+            .bind(unit)
+            .execute(&pool)
+            .await
+            .unwrap();
+        match validate_price_unit(&pool, &identifier).await {
+            Ok(()) => assert!(valid, "{}", unit),
+            Err(ApiError::NotAPriceUnit(rejected)) => {
+                assert_eq!((valid, rejected.as_str()), (false, unit))
+            }
+            Err(error) => panic!("{}", error),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_kpi_quality_filter() {
+    let client = get_client().await;
+
+    // 1 kWh per reading, the third reading is estimated
+    let datapoints: Vec<_> = [
+        ("2023-03-05T00:00:00Z", "measured"),
+        ("2023-03-05T00:15:00Z", "measured"),
+        ("2023-03-05T00:30:00Z", "estimated"),
+        ("2023-03-05T00:45:00Z", "measured"),
+    ]
+    .iter()
+    .map(|(timestamp, quality)| {
+        json!({
+            "timestamp": timestamp,
+            "value": 4.0,
+            "identifier": "total_load",
+            "quality": quality
+        })
+    })
+    .collect();
+    let response = client
+        .post("/v1/ts/?on_conflict=overwrite&auto_create_meta=true")
+        .json(&json!({
+            "timeseries": datapoints,
+            "meta_defaults": { "unit": "kW", "consumption": true, "local": true }
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    // flagged readings of series the kpi doesn't read are not counted
+    let other = get_random_string(10);
+    let response = client
+        .post("/v1/ts/?auto_create_meta=true")
+        .json(&json!({
+            "timeseries": [{
+                "timestamp": "2023-03-05T00:15:00Z",
+                "value": 1.0,
+                "identifier": other,
+                "quality": "substituted"
+            }],
+            "meta_defaults": { "unit": "kW", "consumption": true, "local": true }
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let range = "from=2023-03-05T00:00:00Z&to=2023-03-05T00:45:00Z";
+    let response = client
+        .get(&format!("/v1/kpi/total_consumption/?{}", range))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: KpiResult = response.json().await;
+    assert_eq!(body.value, 4.0);
+    assert_eq!(body.flagged_datapoints, None);
+
+    // the estimated reading leaves a gap of 30 minutes, which is capped at 15 minutes
+    let response = client
+        .get(&format!(
+            "/v1/kpi/total_consumption/?{}&exclude_flagged=true&count_flagged=true",
+            range
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: KpiResult = response.json().await;
+    assert_eq!(body.value, 3.0);
+    assert_eq!(body.flagged_datapoints, Some(1));
+}
+
+#[tokio::test]
+async fn test_kpi_as_of() {
+    let client = get_client().await;
+
+    let upload = |value: f64| {
+        let datapoints: Vec<_> = [
+            "2023-03-06T00:00:00Z",
+            "2023-03-06T00:15:00Z",
+            "2023-03-06T00:30:00Z",
+            "2023-03-06T00:45:00Z",
+        ]
+        .iter()
+        .map(|timestamp| {
+            json!({
+                "timestamp": timestamp,
+                "value": value,
+                "identifier": "total_load"
+            })
+        })
+        .collect();
+        json!({
+            "timeseries": datapoints,
+            "meta_defaults": { "unit": "kW", "consumption": true, "local": true }
+        })
+    };
+    let url = "/v1/ts/?on_conflict=overwrite&auto_create_meta=true";
+    let response = client.post(url).json(&upload(4.0)).send().await;
+    assert!(response.status().is_success());
+    let before = OffsetDateTime::now_utc().format(&Rfc3339).unwrap();
+    let response = client.post(url).json(&upload(8.0)).send().await;
+    assert!(response.status().is_success());
+
+    let range = "from=2023-03-06T00:00:00Z&to=2023-03-06T00:45:00Z";
+    let response = client
+        .get(&format!("/v1/kpi/total_consumption/?{}", range))
+        .send()
+        .await;
+    let body: KpiResult = response.json().await;
+    assert_eq!(body.value, 8.0);
+
+    let response = client
+        .get(&format!(
+            "/v1/kpi/total_consumption/?{}&as_of={}",
+            range, before
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: KpiResult = response.json().await;
+    assert_eq!(body.value, 4.0);
+}
@@ -4,6 +4,7 @@ use crate::tests::test_util::add_meta;
 use crate::tests::test_util::get_client;
 use crate::tests::test_util::get_random_string;
 
+use axum::http::StatusCode;
 use serde_json::json;
 
 #[tokio::test]
@@ -69,7 +70,7 @@ async fn test_add_meta_empty_carrier() {
 
     let meta = MetaInput {
         identifier: identifier.to_string(),
-        unit: String::from("testUnit"),
+        unit: String::from("kW"),
         carrier: None,
         consumption: Some(true),
         description: Some("description".to_string()),
@@ -82,3 +83,19 @@ async fn test_add_meta_empty_carrier() {
     assert_eq!(r.identifier, identifier);
     assert!(r.carrier.is_none());
 }
+
+#[tokio::test]
+async fn test_add_meta_unknown_unit() {
+    let client = get_client().await;
+
+    let meta = MetaInput {
+        identifier: get_random_string(10),
+        unit: String::from("testUnit"),
+        carrier: None,
+        consumption: Some(true),
+        description: None,
+        local: Some(true),
+    };
+    let res = client.post("/v1/meta/").json(&meta).send().await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
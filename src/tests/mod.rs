@@ -1,14 +1,28 @@
 #[cfg(test)]
+pub mod admin;
+#[cfg(test)]
 pub mod config;
 #[cfg(test)]
 pub mod emission_factor;
 #[cfg(test)]
+pub mod export;
+#[cfg(test)]
+pub mod forecast;
+#[cfg(test)]
 pub mod kpi;
 #[cfg(test)]
 pub mod meta;
 #[cfg(test)]
+pub mod mqtt;
+#[cfg(test)]
+pub mod quality;
+#[cfg(test)]
+pub mod subscription;
+#[cfg(test)]
 pub mod test_util;
 #[cfg(test)]
 pub mod timeseries;
 #[cfg(test)]
 pub mod util;
+#[cfg(test)]
+pub mod write;
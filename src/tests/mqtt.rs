@@ -0,0 +1,156 @@
+use crate::app_config::AppConfig;
+use crate::infrastructure::{create_connection_pool, AppState};
+use crate::models::{MqttConfig, Timeseries};
+use crate::mqtt::run_bridge;
+use crate::tests::test_util::{add_meta, get_client, get_random_string};
+use axum_test_helper::TestClient;
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use serde_json::{json, Value};
+use std::env::var;
+use std::time::Duration;
+use time::macros::datetime;
+
+fn broker_port() -> u16 {
+    var("MQTT_EXPOSE_PORT").map_or(1883, |x| x.parse().unwrap())
+}
+
+async fn spawn_bridge(prefix: &str, on_conflict: &str) -> tokio::task::JoinHandle<()> {
+    let config: MqttConfig = serde_json::from_value(json!({
+        "host": "localhost",
+        "port": broker_port(),
+        "client_id": format!("bridge-{}", prefix),
+        "flush_interval_ms": 100,
+        "on_conflict": on_conflict,
+        "subscriptions": [{
+            "topic": format!("{}/+/SENSOR", prefix),
+            "timestamp": "Time",
+            "values": [{"path": "ENERGY.Power", "identifier": "{1}"}]
+        }]
+    }))
+    .unwrap();
+    let config_app = AppConfig::new();
+    let pool = create_connection_pool(&config_app).await;
+    tokio::spawn(run_bridge(AppState::new(pool, &config_app), config))
+}
+
+/// Publish retained messages, which reach the bridge also if it subscribes after they were published.
+/// An empty payload deletes the retained message on the broker.
+async fn publish_retained(prefix: &str, messages: &[(&str, Option<Value>)]) {
+    let (publisher, mut eventloop) = AsyncClient::new(
+        MqttOptions::new(format!("publisher-{}", prefix), "localhost", broker_port()),
+        10,
+    );
+    let events = tokio::spawn(async move { while eventloop.poll().await.is_ok() {} });
+    for (device, payload) in messages {
+        publisher
+            .publish(
+                format!("{}/{}/SENSOR", prefix, device),
+                QoS::AtLeastOnce,
+                true,
+                payload
+                    .as_ref()
+                    .map_or(vec![], |x| x.to_string().into_bytes()),
+            )
+            .await
+            .unwrap();
+    }
+    publisher.disconnect().await.unwrap();
+    events.await.unwrap();
+}
+
+async fn wait_for_datapoints(client: &TestClient, identifier: &str, count: usize) -> Timeseries {
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+        let timeseries: Timeseries = response.json().await;
+        if timeseries.datapoints.len() >= count {
+            return timeseries;
+        }
+    }
+    panic!("datapoints published over MQTT were not stored");
+}
+
+// needs the mosquitto broker of docker-compose.yml
+#[tokio::test]
+async fn test_mqtt_bridge() {
+    let client = get_client().await;
+    let prefix = get_random_string(10);
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let bridge = spawn_bridge(&prefix, "skip").await;
+    let payload = |power| json!({"Time": "2019-01-01T00:00:00Z", "ENERGY": {"Power": power}});
+    publish_retained(
+        &prefix,
+        &[
+            (identifier.as_str(), Some(payload(1.5))),
+            ("unknown", Some(payload(2.0))),
+        ],
+    )
+    .await;
+
+    let body = wait_for_datapoints(&client, &identifier, 1).await;
+    assert_eq!(body.datapoints.len(), 1);
+    assert_eq!(body.datapoints[0].timestamp, datetime!(2019-01-01 0:00 UTC));
+    assert_eq!(body.datapoints[0].value, 1.5);
+
+    bridge.abort();
+    publish_retained(&prefix, &[(identifier.as_str(), None), ("unknown", None)]).await;
+}
+
+// needs the mosquitto broker of docker-compose.yml
+#[tokio::test]
+async fn test_mqtt_bridge_drops_failing_datapoints() {
+    let client = get_client().await;
+    let prefix = get_random_string(10);
+    let identifier = get_random_string(10);
+    let other = get_random_string(10);
+    add_meta(&client, &identifier).await;
+    add_meta(&client, &other).await;
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({"timeseries": [{
+            "timestamp": "2019-01-01T00:00:00Z",
+            "value": 1.0,
+            "identifier": identifier
+        }]}))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    // the duplicate fails its batch, it must neither block the other datapoints nor later batches
+    let bridge = spawn_bridge(&prefix, "reject").await;
+    let payload = |time, power| json!({"Time": time, "ENERGY": {"Power": power}});
+    publish_retained(
+        &prefix,
+        &[
+            (
+                identifier.as_str(),
+                Some(payload("2019-01-01T00:00:00Z", 9.0)),
+            ),
+            (other.as_str(), Some(payload("2019-01-01T00:00:00Z", 2.0))),
+        ],
+    )
+    .await;
+    let body = wait_for_datapoints(&client, &other, 1).await;
+    assert_eq!(body.datapoints[0].value, 2.0);
+
+    publish_retained(
+        &prefix,
+        &[(
+            identifier.as_str(),
+            Some(payload("2019-01-01T00:15:00Z", 3.0)),
+        )],
+    )
+    .await;
+    let body = wait_for_datapoints(&client, &identifier, 2).await;
+    let values = body.datapoints.iter().map(|x| x.value).collect::<Vec<_>>();
+    assert_eq!(values, [1.0, 3.0]);
+
+    bridge.abort();
+    publish_retained(
+        &prefix,
+        &[(identifier.as_str(), None), (other.as_str(), None)],
+    )
+    .await;
+}
@@ -0,0 +1,150 @@
+use crate::models::{QualityReport, QualitySummary};
+use crate::tests::test_util::add_meta;
+use crate::tests::test_util::get_client;
+use crate::tests::test_util::get_random_string;
+
+use axum::http::StatusCode;
+use serde_json::json;
+use time::macros::datetime;
+
+#[tokio::test]
+async fn test_quality_by_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let start = datetime!(2024-01-10 0:00 UTC);
+    // 15 minute readings with a spike, a negative value, a flatline and a missing hour
+    let values = [
+        (0, 1.0),
+        (15, 2.0),
+        (30, 500.0),
+        (45, 3.0),
+        (60, -4.0),
+        (75, 2.0),
+        (90, 6.0),
+        (105, 6.0),
+        (120, 6.0),
+        (135, 6.0),
+        (150, 6.0),
+        (225, 1.0),
+        (240, 2.0),
+    ];
+    let datapoints: Vec<_> = values
+        .iter()
+        .map(|(minutes, value)| {
+            json!({
+                "timestamp": (start + time::Duration::minutes(*minutes)).format(&time::format_description::well_known::Rfc3339).unwrap(),
+                "value": value,
+                "identifier": identifier
+            })
+        })
+        .collect();
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({ "timeseries": datapoints }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/quality/?from=2024-01-10T00:00:00Z&to=2024-01-11T00:00:00Z&spike_threshold=1",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let report: QualityReport = response.json().await;
+    assert_eq!(report.meta.identifier, identifier);
+    assert_eq!(report.from, Some(start));
+    assert_eq!(report.expected_interval_seconds, Some(900.0));
+    assert_eq!(report.checks.datapoints, 13);
+    assert_eq!(report.checks.expected_datapoints, 17);
+    assert_eq!(report.checks.gaps.len(), 1);
+    assert_eq!(report.checks.gaps[0].datapoints, 4);
+    assert_eq!(report.checks.out_of_range.len(), 1);
+    assert_eq!(
+        report.checks.out_of_range[0].from,
+        start + time::Duration::minutes(60)
+    );
+    assert_eq!(report.checks.flatlines.len(), 1);
+    assert_eq!(report.checks.flatlines[0].datapoints, 5);
+    assert!(report.checks.spikes.iter().any(|x| x.value == Some(500.0)));
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/quality/?from=2024-01-10T00:00:00Z&to=2024-01-11T00:00:00Z&expected_interval=1hour&min=-10&max=100",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let report: QualityReport = response.json().await;
+    assert_eq!(report.expected_interval_seconds, Some(3600.0));
+    assert_eq!(report.checks.expected_datapoints, 5);
+    assert!(report.checks.gaps.is_empty());
+    assert_eq!(report.checks.out_of_range.len(), 1);
+    assert_eq!(
+        report.checks.out_of_range[0].from,
+        start + time::Duration::minutes(30)
+    );
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/quality/?expected_interval=soon",
+            identifier
+        ))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_quality_summary() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    let empty_identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+    add_meta(&client, &empty_identifier).await;
+
+    let start = datetime!(2024-01-12 0:00 UTC);
+    let datapoints: Vec<_> = [0, 15, 30, 90]
+        .iter()
+        .map(|minutes| {
+            json!({
+                "timestamp": (start + time::Duration::minutes(*minutes)).format(&time::format_description::well_known::Rfc3339).unwrap(),
+                "value": minutes,
+                "identifier": identifier
+            })
+        })
+        .collect();
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({ "timeseries": datapoints }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/quality/?identifiers={},{}&from=2024-01-12T00:00:00Z&to=2024-01-13T00:00:00Z",
+            identifier, empty_identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let summary: Vec<QualitySummary> = response.json().await;
+    assert_eq!(summary.len(), 2);
+    let series = summary.iter().find(|x| x.identifier == identifier).unwrap();
+    assert_eq!(series.datapoints, 4);
+    assert_eq!(series.gaps, 1);
+    assert_eq!(series.gap_seconds, 3600.0);
+    assert_eq!(series.coverage, Some(100.0 * 4.0 / 7.0));
+    let empty = summary
+        .iter()
+        .find(|x| x.identifier == empty_identifier)
+        .unwrap();
+    assert_eq!(empty.datapoints, 0);
+    assert_eq!(empty.coverage, None);
+}
@@ -0,0 +1,123 @@
+use crate::models::SubscribedDatapoint;
+use crate::tests::test_util::add_meta;
+use crate::tests::test_util::get_client;
+use crate::tests::test_util::get_random_string;
+
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use serde_json::json;
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::macros::datetime;
+use time::OffsetDateTime;
+
+/// Read server-sent events until `count` datapoints arrived, returning them with the last event id.
+async fn read_datapoints(
+    response: &mut TestResponse,
+    count: usize,
+) -> (Vec<SubscribedDatapoint>, String) {
+    let mut datapoints = vec![];
+    let mut id = String::new();
+    let mut buffer = String::new();
+    while datapoints.len() < count {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk_text())
+            .await
+            .expect("no event within 5 seconds")
+            .expect("stream ended");
+        buffer.push_str(&chunk);
+        while let Some(end) = buffer.find("\n\n") {
+            let event = buffer[..end].to_string();
+            buffer.drain(..end + 2);
+            // the space after the field name is optional, comments like keep-alives start with ':'
+            for line in event.lines() {
+                match line.split_once(':') {
+                    Some(("data", data)) => datapoints.push(serde_json::from_str(data).unwrap()),
+                    Some(("id", value)) => id = value.trim_start().to_string(),
+                    _ => {}
+                }
+            }
+        }
+    }
+    (datapoints, id)
+}
+
+#[tokio::test]
+async fn test_subscribe_timeseries() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    let other = get_random_string(10);
+    add_meta(&client, &identifier).await;
+    add_meta(&client, &other).await;
+
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({
+            "timeseries": [
+                { "timestamp": "2024-01-29T15:00:00Z", "value": 1, "identifier": identifier },
+                { "timestamp": "2024-01-29T15:15:00Z", "value": 2, "identifier": identifier }
+            ]
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let mut events = client
+        .get(&format!(
+            "/v1/ts/subscribe/?identifiers={}&since=2024-01-29T15:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(events.status().is_success());
+    let (datapoints, _) = read_datapoints(&mut events, 1).await;
+    assert_eq!(datapoints[0].value, 2.0);
+
+    // datapoints of other series are not pushed
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({
+            "timeseries": [
+                { "timestamp": "2024-01-29T15:30:00Z", "value": 5, "identifier": other },
+                { "timestamp": "2024-01-29T15:30:00Z", "value": 3, "identifier": identifier }
+            ]
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let (datapoints, id) = read_datapoints(&mut events, 1).await;
+    assert_eq!(datapoints[0].identifier, identifier);
+    assert_eq!(datapoints[0].value, 3.0);
+    // the id is the time the datapoint was written at, not its timestamp
+    assert!(OffsetDateTime::parse(&id, &Rfc3339).unwrap() > datetime!(2024-01-29 15:30 UTC));
+
+    // backfilled datapoints are pushed as well
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({
+            "timeseries": [
+                { "timestamp": "2024-01-29T14:00:00Z", "value": 0.5, "identifier": identifier }
+            ]
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let (datapoints, _) = read_datapoints(&mut events, 1).await;
+    assert_eq!(datapoints[0].value, 0.5);
+
+    // a reconnecting client gets everything written since its last event id, also older timestamps
+    let mut events = client
+        .get(&format!("/v1/ts/subscribe/?identifiers={}", identifier))
+        .header("last-event-id", id)
+        .send()
+        .await;
+    let (datapoints, _) = read_datapoints(&mut events, 2).await;
+    let values = datapoints.iter().map(|x| x.value).collect::<Vec<_>>();
+    assert_eq!(values, [3.0, 0.5]);
+}
+
+#[tokio::test]
+async fn test_subscribe_timeseries_without_selection() {
+    let client = get_client().await;
+    let response = client.get("/v1/ts/subscribe/").send().await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
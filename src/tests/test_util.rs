@@ -1,8 +1,8 @@
 use crate::app_config::AppConfig;
 use crate::infrastructure::create_connection_pool;
-use crate::infrastructure::create_router;
+use crate::infrastructure::{create_router, AppState};
 
-use crate::models::{Datapoint, MetaInput, MetaOutput};
+use crate::models::{AddTimeseriesResponse, MetaInput, MetaOutput};
 
 use crate::models::{NewDatapoint, TimeseriesBody};
 use axum_test_helper::TestClient;
@@ -17,7 +17,7 @@ pub fn get_random_string(size: usize) -> String {
 pub async fn get_client() -> TestClient {
     let config = AppConfig::new();
     let pool = create_connection_pool(&config).await;
-    let router = create_router(pool, &config);
+    let router = create_router(AppState::new(pool, &config));
 
     TestClient::new(router)
 }
@@ -25,7 +25,7 @@ pub async fn get_client() -> TestClient {
 pub async fn add_meta(client: &TestClient, identifier: &str) -> MetaOutput {
     let meta = MetaInput {
         identifier: identifier.to_string(),
-        unit: String::from("testUnit"),
+        unit: String::from("kW"),
         carrier: Some(String::from("oil")),
         consumption: Some(true),
         description: Some("description".to_string()),
@@ -43,11 +43,12 @@ pub async fn add_timeseries(
     client: &TestClient,
     identifier: &str,
     value: f64,
-) -> TimeseriesBody<Datapoint> {
+) -> AddTimeseriesResponse {
     let timeseries = NewDatapoint {
         timestamp: OffsetDateTime::now_utc(),
         value,
         identifier: identifier.to_string(),
+        quality: Default::default(),
     };
     let res = client
         .post("/v1/ts/")
@@ -58,7 +59,7 @@ pub async fn add_timeseries(
         .await;
     assert!(res.status().is_success());
 
-    let r: TimeseriesBody<Datapoint> = res.json().await;
+    let r: AddTimeseriesResponse = res.json().await;
     assert_eq!(r.timeseries[0].value, value);
     r
 }
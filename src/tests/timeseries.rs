@@ -1,13 +1,27 @@
+use crate::app_config::AppConfig;
+use crate::infrastructure::{create_connection_pool, create_router, AppState};
+use crate::models::AddTimeseriesResponse;
+use crate::models::AlignedTimeseries;
+use crate::models::DatapointQuality;
+use crate::models::LatestDatapoint;
+use crate::models::RejectionReason;
 use crate::models::ResampledTimeseries;
+use crate::models::{CorrectTimeseriesResponse, DeleteTimeseriesResponse};
+use crate::models::{EnergyTimeseries, IntegrationMethod};
 
 use crate::models::Timeseries;
 use crate::models::TimeseriesBody;
+use crate::models::TimeseriesStats;
 use crate::tests::test_util::add_meta;
 use crate::tests::test_util::add_timeseries;
 use crate::tests::test_util::get_client;
 use crate::tests::test_util::get_random_string;
 
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
 use serde_json::json;
+use time::format_description::well_known::Rfc3339;
+use time::macros::{datetime, offset};
 use time::OffsetDateTime;
 
 #[tokio::test]
@@ -58,6 +72,208 @@ async fn test_add_timeseries_json() {
     assert!(response.status().is_success());
 }
 
+#[tokio::test]
+async fn test_add_timeseries_duplicate_rejected() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_add_timeseries_on_conflict_skip() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 66,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:45:00Z",
+            "value": 66,
+            "identifier": identifier
+        }]
+    });
+    let response = client
+        .post("/v1/ts/?on_conflict=skip")
+        .json(&timeseries)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let body: AddTimeseriesResponse = response.json().await;
+    assert_eq!(body.summary.len(), 1);
+    assert_eq!(body.summary[0].inserted, 1);
+    assert_eq!(body.summary[0].skipped, 1);
+    assert_eq!(body.summary[0].updated, 0);
+}
+
+#[tokio::test]
+async fn test_add_timeseries_on_conflict_overwrite() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 66,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:45:00Z",
+            "value": 66,
+            "identifier": identifier
+        }]
+    });
+    let response = client
+        .post("/v1/ts/?on_conflict=overwrite")
+        .json(&timeseries)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let body: AddTimeseriesResponse = response.json().await;
+    assert_eq!(body.summary[0].inserted, 1);
+    assert_eq!(body.summary[0].skipped, 0);
+    assert_eq!(body.summary[0].updated, 1);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/?from=2024-01-29T15:30:00Z&to=2024-01-29T15:30:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints[0].value, 66.0);
+}
+
+#[tokio::test]
+async fn test_add_timeseries_rejected_datapoints() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    let unknown_identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": unknown_identifier
+        }, {
+            "timestamp": "1960-01-01T00:00:00Z",
+            "value": 42,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let body: AddTimeseriesResponse = response.json().await;
+    assert_eq!(body.timeseries.len(), 1);
+    assert_eq!(body.rejected.len(), 2);
+    assert_eq!(body.rejected[0].index, 1);
+    assert_eq!(body.rejected[0].reason, RejectionReason::UnknownIdentifier);
+    assert_eq!(body.rejected[1].index, 2);
+    assert_eq!(
+        body.rejected[1].reason,
+        RejectionReason::TimestampOutOfRange
+    );
+    assert!(body.created_meta.is_empty());
+}
+
+#[tokio::test]
+async fn test_add_timeseries_auto_create_meta() {
+    let client = get_client().await;
+    let identifier = get_random_string(10).to_lowercase();
+
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }],
+        "meta_defaults": {
+            "unit": "kw",
+            "carrier": "electricity",
+            "consumption": true,
+            "local": true
+        }
+    });
+    let response = client
+        .post("/v1/ts/?auto_create_meta=true")
+        .json(&timeseries)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let body: AddTimeseriesResponse = response.json().await;
+    assert_eq!(body.timeseries.len(), 1);
+    assert!(body.rejected.is_empty());
+    assert_eq!(body.created_meta.len(), 1);
+    assert_eq!(body.created_meta[0].identifier, identifier);
+    assert_eq!(body.created_meta[0].carrier.as_deref(), Some("electricity"));
+}
+
+#[tokio::test]
+async fn test_add_timeseries_auto_create_meta_without_defaults() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }]
+    });
+    let response = client
+        .post("/v1/ts/?auto_create_meta=true")
+        .json(&timeseries)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn test_get_timeseries_by_identifier() {
     let client = get_client().await;
@@ -131,23 +347,1264 @@ async fn test_get_timeseries_by_identifier_with_ts_filter() {
 }
 
 #[tokio::test]
-async fn test_resample_timeseries_by_identifier() {
+async fn test_get_timeseries_by_identifier_with_period() {
     let client = get_client().await;
     let identifier = get_random_string(10);
 
     add_meta(&client, &identifier).await;
-    add_timeseries(&client, &identifier, 42.0).await;
-    add_timeseries(&client, &identifier, 66.0).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-31T23:59:59Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-02-01T00:00:00Z",
+            "value": 2,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
 
     let response = client
-        .get(&format!("/v1/ts/{}/resample/?interval=1hour", identifier))
+        .get(&format!("/v1/ts/{}/?period=2024-01", identifier))
         .send()
         .await;
     assert!(response.status().is_success());
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 1);
+    assert_eq!(body.datapoints[0].value, 1.0);
 
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=P1M&period=2024-Q1",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
     let body: ResampledTimeseries = response.json().await;
-    assert_eq!(body.datapoints.first().unwrap().mean_value.unwrap(), 54.0);
+    assert_eq!(body.datapoints.len(), 2);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/?period=2024-01&from=2024-01-01T00:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    for period in ["next_year", "last_9999999999999999h"] {
+        let response = client
+            .get(&format!("/v1/ts/{}/?period={}", identifier, period))
+            .send()
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", period);
+    }
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=2147483647year",
+            identifier
+        ))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_timeseries_by_identifier_paginated() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:45:00Z",
+            "value": 2,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T16:00:00Z",
+            "value": 3,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!("/v1/ts/{}/?limit=2", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 2);
+    assert_eq!(body.datapoints[1].value, 2.0);
+    let next_cursor = body
+        .next_cursor
+        .unwrap()
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap();
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/?limit=2&after={}",
+            identifier, next_cursor
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 1);
+    assert_eq!(body.datapoints[0].value, 3.0);
+    assert!(body.next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn test_get_timeseries_by_identifier_streamed() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:45:00Z",
+            "value": 2,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!("/v1/ts/{}/?stream=true", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.meta.identifier, identifier);
+    assert_eq!(body.datapoints.len(), 2);
+    assert_eq!(body.datapoints[0].value, 1.0);
+}
+
+#[tokio::test]
+async fn test_get_timeseries_by_unknown_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_timeseries_by_identifier_unit_conversion() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    add_timeseries(&client, &identifier, 2.5).await;
+
+    let response = client
+        .get(&format!("/v1/ts/{}/?unit=W", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.meta.unit, "W");
+    assert_eq!(body.datapoints[0].value, 2500.0);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=1hour&agg=mean,count&unit=MW",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: ResampledTimeseries = response.json().await;
+    assert_eq!(body.meta.unit, "MW");
+    assert_eq!(body.datapoints[0].values["mean"], Some(0.0025));
+    assert_eq!(body.datapoints[0].values["count"], Some(1.0));
+
+    let response = client
+        .get(&format!("/v1/ts/{}/?unit=kWh", identifier))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .get(&format!("/v1/ts/{}/?unit=horsepower", identifier))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
+
+#[tokio::test]
+async fn test_delete_timeseries_by_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:45:00Z",
+            "value": 42,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T16:00:00Z",
+            "value": 42,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .delete(&format!(
+            "/v1/ts/{}/?from=2024-01-29T15:40:00Z&to=2024-01-29T16:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: DeleteTimeseriesResponse = response.json().await;
+    assert_eq!(body.deleted, 2);
+
+    let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 1);
+}
+
+#[tokio::test]
+async fn test_delete_timeseries_without_redis() {
+    let mut config = AppConfig::new();
+    config.redis_url = String::from("redis://localhost:1");
+    let pool = create_connection_pool(&config).await;
+    let client = TestClient::new(create_router(AppState::new(pool, &config)));
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    // the datapoints are deleted even though cached kpis can't be invalidated
+    let response = client
+        .delete(&format!(
+            "/v1/ts/{}/?from=2024-01-29T15:00:00Z&to=2024-01-29T16:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: DeleteTimeseriesResponse = response.json().await;
+    assert_eq!(body.deleted, 1);
+}
+
+#[tokio::test]
+async fn test_correct_timeseries_by_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 42,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let corrections = json!({
+        "datapoints": [{
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 66
+        }, {
+            "timestamp": "2024-01-29T15:45:00Z",
+            "value": 66
+        }]
+    });
+    let response = client
+        .patch(&format!("/v1/ts/{}/", identifier))
+        .json(&corrections)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: CorrectTimeseriesResponse = response.json().await;
+    assert_eq!(body.updated, 1);
+    assert_eq!(body.missing.len(), 1);
+
+    let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints[0].value, 66.0);
+    assert_eq!(body.datapoints[0].quality, DatapointQuality::Corrected);
+}
+
+#[tokio::test]
+async fn test_datapoint_quality() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:15:00Z",
+            "value": 2,
+            "identifier": identifier,
+            "quality": "estimated"
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+    let body: AddTimeseriesResponse = response.json().await;
+    assert_eq!(body.timeseries[1].quality, DatapointQuality::Estimated);
+
+    // overwriting with the same value but another quality updates the datapoint
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:15:00Z",
+            "value": 2,
+            "identifier": identifier,
+            "quality": "substituted"
+        }]
+    });
+    let response = client
+        .post("/v1/ts/?on_conflict=overwrite")
+        .json(&timeseries)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: AddTimeseriesResponse = response.json().await;
+    assert_eq!(body.summary[0].updated, 1);
+
+    let response = client
+        .patch(&format!("/v1/ts/{}/", identifier))
+        .json(&json!({
+            "datapoints": [{
+                "timestamp": "2024-01-29T15:00:00Z",
+                "value": 1.5,
+                "quality": "estimated"
+            }]
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+    let body: Timeseries = response.json().await;
+    let qualities = body
+        .datapoints
+        .iter()
+        .map(|x| x.quality)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        qualities,
+        [DatapointQuality::Estimated, DatapointQuality::Substituted]
+    );
+
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({
+            "timeseries": [{
+                "timestamp": "2024-01-29T15:30:00Z",
+                "value": 3,
+                "identifier": identifier,
+                "quality": "guessed"
+            }]
+        }))
+        .send()
+        .await;
+    assert!(response.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_get_timeseries_by_identifier_as_of() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:15:00Z",
+            "value": 2,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+    let before = OffsetDateTime::now_utc().format(&Rfc3339).unwrap();
+
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 10,
+            "identifier": identifier
+        }]
+    });
+    let response = client
+        .post("/v1/ts/?on_conflict=overwrite")
+        .json(&timeseries)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let response = client
+        .delete(&format!(
+            "/v1/ts/{}/?from=2024-01-29T15:15:00Z&to=2024-01-29T15:15:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+    let body: Timeseries = response.json().await;
+    let values = body.datapoints.iter().map(|x| x.value).collect::<Vec<_>>();
+    assert_eq!(values, [10.0]);
+
+    // overwritten and deleted datapoints are read from their revisions
+    let response = client
+        .get(&format!("/v1/ts/{}/?as_of={}", identifier, before))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: Timeseries = response.json().await;
+    let values = body.datapoints.iter().map(|x| x.value).collect::<Vec<_>>();
+    assert_eq!(values, [1.0, 2.0]);
+
+    // nothing was stored yet
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/?as_of=2000-01-01T00:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    let body: Timeseries = response.json().await;
+    assert!(body.datapoints.is_empty());
+}
+
+#[tokio::test]
+async fn test_resample_timeseries_by_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    add_timeseries(&client, &identifier, 42.0).await;
+    add_timeseries(&client, &identifier, 66.0).await;
+
+    let response = client
+        .get(&format!("/v1/ts/{}/resample/?interval=1hour", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let body: ResampledTimeseries = response.json().await;
+    assert_eq!(body.datapoints.first().unwrap().mean_value.unwrap(), 54.0);
+}
+#[tokio::test]
+async fn test_resample_timeseries_by_identifier_aggregates() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:15:00Z",
+            "value": 2,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 6,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=1hour&agg=sum,min,max,first,last,count,p50",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let body: ResampledTimeseries = response.json().await;
+    let values = &body.datapoints.first().unwrap().values;
+    assert_eq!(values["sum"], Some(9.0));
+    assert_eq!(values["min"], Some(1.0));
+    assert_eq!(values["max"], Some(6.0));
+    assert_eq!(values["first"], Some(1.0));
+    assert_eq!(values["last"], Some(6.0));
+    assert_eq!(values["count"], Some(3.0));
+    assert_eq!(values["p50"], Some(2.0));
+    assert!(body.datapoints.first().unwrap().mean_value.is_none());
+}
+
+#[tokio::test]
+async fn test_resample_timeseries_by_identifier_fill() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:15:00Z",
+            "value": 2,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=15min&fill=linear&from=2024-01-29T00:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let body: ResampledTimeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 2);
+    assert!(body.datapoints.iter().all(|x| !x.filled));
+    assert_eq!(body.datapoints[1].mean_value, Some(2.0));
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=15min&fill=zero&to=2024-01-01T00:00:00Z",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: ResampledTimeseries = response.json().await;
+    assert!(body.datapoints.is_empty());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=15min&fill=spline",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_resample_timeseries_by_identifier_timezone() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    // 23:30 and 00:30 in Berlin, both on the same day in UTC
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T22:30:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T23:30:00Z",
+            "value": 3,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!("/v1/ts/{}/resample/?interval=1day", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: ResampledTimeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 1);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=1day&tz=Europe/Berlin",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: ResampledTimeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 2);
+    assert_eq!(
+        body.datapoints[1].bucket,
+        Some(datetime!(2024-01-30 0:00 +1))
+    );
+    assert_eq!(body.datapoints[1].bucket.unwrap().offset(), offset!(+1));
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=1day&tz=Mars/Olympus_Mons",
+            identifier
+        ))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_resample_timeseries_by_identifier_invalid_aggregate() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=1hour&agg=median",
+            identifier
+        ))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_aligned_timeseries() {
+    let client = get_client().await;
+    let load = get_random_string(10);
+    let pv = get_random_string(10);
+
+    add_meta(&client, &load).await;
+    add_meta(&client, &pv).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 1,
+            "identifier": load
+        }, {
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 3,
+            "identifier": load
+        }, {
+            "timestamp": "2024-01-29T16:00:00Z",
+            "value": 5,
+            "identifier": load
+        }, {
+            "timestamp": "2024-01-29T16:15:00Z",
+            "value": 10,
+            "identifier": pv
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/aligned/?identifiers={},{}&interval=1hour",
+            pv, load
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let body: AlignedTimeseries = response.json().await;
+    assert_eq!(body.meta[0].identifier, pv);
+    assert_eq!(body.meta[1].identifier, load);
+    assert_eq!(body.rows.len(), 2);
+    assert_eq!(body.rows[0].values, vec![None, Some(2.0)]);
+    assert_eq!(body.rows[1].values, vec![Some(10.0), Some(5.0)]);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/aligned/?identifiers={},{}&interval=1day&agg=max",
+            pv, load
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: AlignedTimeseries = response.json().await;
+    assert_eq!(body.rows.len(), 1);
+    assert_eq!(body.rows[0].values, vec![Some(10.0), Some(5.0)]);
+}
+
+#[tokio::test]
+async fn test_get_aligned_timeseries_by_meta_filter() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 4,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get("/v1/ts/aligned/?carrier=oil&local=true&interval=1hour&from=2024-01-29T15:00:00Z&to=2024-01-29T15:59:59Z")
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: AlignedTimeseries = response.json().await;
+    let column = body
+        .meta
+        .iter()
+        .position(|m| m.identifier == identifier)
+        .unwrap();
+    assert!(body
+        .meta
+        .iter()
+        .all(|m| m.carrier.as_deref() == Some("oil")));
+    assert_eq!(body.rows.len(), 1);
+    assert_eq!(body.rows[0].values[column], Some(4.0));
+
+    let response = client
+        .get("/v1/ts/aligned/?carrier=does_not_exist&interval=1hour")
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: AlignedTimeseries = response.json().await;
+    assert!(body.meta.is_empty());
+    assert!(body.rows.is_empty());
+}
+
+#[tokio::test]
+async fn test_get_aligned_timeseries_bad_request() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    add_meta(&client, &identifier).await;
+    let response = client.get("/v1/ts/aligned/?interval=1hour").send().await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/aligned/?identifiers={}&interval=1hour&agg=min,max",
+            identifier
+        ))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/aligned/?identifiers={},{}&interval=1hour",
+            identifier,
+            get_random_string(10)
+        ))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_latest_datapoints() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    let empty = get_random_string(10);
+    add_meta(&client, &identifier).await;
+    add_meta(&client, &empty).await;
+
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 1,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:30:00Z",
+            "value": 3,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:15:00Z",
+            "value": 2,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    // also served without the trailing slash
+    let response = client
+        .get(&format!(
+            "/v1/ts/latest?identifiers={},{}",
+            identifier, empty
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: Vec<LatestDatapoint> = response.json().await;
+    assert_eq!(body.len(), 2);
+    assert_eq!(body[0].meta.identifier, identifier);
+    let datapoint = body[0].datapoint.as_ref().unwrap();
+    assert_eq!(datapoint.timestamp, datetime!(2024-01-29 15:30 UTC));
+    assert_eq!(datapoint.value, 3.0);
+    let age = (OffsetDateTime::now_utc() - datapoint.timestamp).as_seconds_f64();
+    assert!((body[0].age_seconds.unwrap() - age).abs() < 60.0);
+    assert!(body[1].datapoint.is_none());
+    assert!(body[1].age_seconds.is_none());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/latest/?identifiers={}",
+            get_random_string(10)
+        ))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_energy_by_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    let meta = json!({
+        "identifier": identifier,
+        "unit": "W",
+        "carrier": "electricity",
+        "consumption": true,
+        "local": true
+    });
+    let response = client.post("/v1/meta/").json(&meta).send().await;
+    assert!(response.status().is_success());
+    let timeseries = json!({
+        "timeseries": [{
+            "timestamp": "2024-01-29T15:00:00Z",
+            "value": 4000,
+            "identifier": identifier
+        }, {
+            "timestamp": "2024-01-29T15:15:00Z",
+            "value": 8000,
+            "identifier": identifier
+        }]
+    });
+    let response = client.post("/v1/ts/").json(&timeseries).send().await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!("/v1/ts/{}/energy/?interval=1day", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: EnergyTimeseries = response.json().await;
+    assert_eq!(body.method, IntegrationMethod::Rectangle);
+    assert_eq!(body.unit, "kWh");
+    assert_eq!(body.datapoints.len(), 1);
+    assert_eq!(body.total, 3.0);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/energy/?interval=1day&method=trapezoid",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: EnergyTimeseries = response.json().await;
+    assert_eq!(body.total, 1.5);
+
+    // read from rollups
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/energy/?interval=1hour&period=2024-01-29",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: EnergyTimeseries = response.json().await;
+    assert_eq!(body.total, 3.0);
+}
+
+#[tokio::test]
+async fn test_resample_timeseries_by_identifier_from_rollup() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let datapoints: Vec<_> = [
+        ("2024-01-29T15:00:00Z", 1),
+        ("2024-01-29T15:10:00Z", 2),
+        ("2024-01-29T15:30:00Z", 3),
+        ("2024-01-29T17:45:00Z", 6),
+    ]
+    .iter()
+    .map(|(timestamp, value)| json!({"timestamp": timestamp, "value": value, "identifier": identifier}))
+    .collect();
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({ "timeseries": datapoints }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    // the period lines up with hourly rollups, which have to give the same buckets as the raw datapoints
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/resample/?interval=1hour&period=2024-01-29&agg=mean,sum,min,max,count",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: ResampledTimeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 2);
+    let first = &body.datapoints[0];
+    assert_eq!(first.bucket, Some(datetime!(2024-01-29 15:00 UTC)));
+    assert_eq!(first.values["mean"], Some(2.0));
+    assert_eq!(first.values["sum"], Some(6.0));
+    assert_eq!(first.values["min"], Some(1.0));
+    assert_eq!(first.values["max"], Some(3.0));
+    assert_eq!(first.values["count"], Some(3.0));
+    assert_eq!(body.datapoints[1].values["mean"], Some(6.0));
+}
+
+#[tokio::test]
+async fn test_rollups_with_unaligned_range() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    let meta = json!({ "identifier": identifier, "unit": "kW" });
+    let response = client.post("/v1/meta/").json(&meta).send().await;
+    assert!(response.status().is_success());
+    // irregular readings, some closer than a quarter hour and some further apart
+    let datapoints: Vec<_> = [
+        ("2024-01-30T10:07:00Z", 4),
+        ("2024-01-30T10:11:00Z", 8),
+        ("2024-01-30T10:40:00Z", 2),
+        ("2024-01-30T11:02:00Z", 6),
+        ("2024-01-30T11:03:00Z", 1),
+        ("2024-01-30T12:50:00Z", 3),
+    ]
+    .iter()
+    .map(|(timestamp, value)| json!({"timestamp": timestamp, "value": value, "identifier": identifier}))
+    .collect();
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({ "timeseries": datapoints }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    // the aligned range is read from rollups only, the unaligned one from rollups and raw edges and
+    // 5 minute buckets from the raw datapoints only
+    let ranges = [
+        "period=2024-01-30",
+        "from=2024-01-30T10:05:00Z&to=2024-01-30T12:55:00Z",
+    ];
+    let mut totals = vec![];
+    for (range, interval) in ranges
+        .iter()
+        .zip(["1hour", "1hour"])
+        .chain([(&ranges[0], "5min")])
+    {
+        let response = client
+            .get(&format!(
+                "/v1/ts/{}/energy/?interval={}&{}",
+                identifier, interval, range
+            ))
+            .send()
+            .await;
+        assert!(response.status().is_success());
+        let body: EnergyTimeseries = response.json().await;
+        totals.push(body.total);
+    }
+    for total in totals {
+        assert!((total - 214.0 / 60.0).abs() < 1e-9, "{}", total);
+    }
+
+    let mut buckets = vec![];
+    for range in ranges {
+        let response = client
+            .get(&format!(
+                "/v1/ts/{}/resample/?interval=1hour&agg=sum,count&{}",
+                identifier, range
+            ))
+            .send()
+            .await;
+        assert!(response.status().is_success());
+        let body: ResampledTimeseries = response.json().await;
+        buckets.push(
+            body.datapoints
+                .iter()
+                .map(|x| (x.bucket, x.values["sum"], x.values["count"]))
+                .collect::<Vec<_>>(),
+        );
+    }
+    assert_eq!(
+        buckets[0],
+        [
+            (Some(datetime!(2024-01-30 10:00 UTC)), Some(14.0), Some(3.0)),
+            (Some(datetime!(2024-01-30 11:00 UTC)), Some(7.0), Some(2.0)),
+            (Some(datetime!(2024-01-30 12:00 UTC)), Some(3.0), Some(1.0)),
+        ]
+    );
+    assert_eq!(buckets[0], buckets[1]);
+}
+
+#[tokio::test]
+async fn test_energy_from_hourly_and_daily_rollups() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    let meta = json!({ "identifier": identifier, "unit": "kW" });
+    let response = client.post("/v1/meta/").json(&meta).send().await;
+    assert!(response.status().is_success());
+    // irregular readings over three days, with gaps across the edges of hours and days
+    let datapoints: Vec<_> = [
+        ("2024-02-05T10:07:00Z", 4),
+        ("2024-02-05T23:52:00Z", 8),
+        ("2024-02-06T00:01:00Z", 2),
+        ("2024-02-06T00:20:00Z", 6),
+        ("2024-02-06T13:03:00Z", 1),
+        ("2024-02-06T13:59:00Z", 5),
+        ("2024-02-06T23:58:00Z", 7),
+        ("2024-02-07T00:04:00Z", 3),
+        ("2024-02-07T11:50:00Z", 9),
+    ]
+    .iter()
+    .map(|(timestamp, value)| json!({"timestamp": timestamp, "value": value, "identifier": identifier}))
+    .collect();
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({ "timeseries": datapoints }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let pool = create_connection_pool(&AppConfig::new()).await;
+    let (meta_id,): (i32,) = sqlx::query_as("select id from meta where identifier = $1")
+        .bind(&identifier)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let from = datetime!(2024-02-05 10:05 UTC);
+    let to = datetime!(2024-02-07 12:00 UTC);
+    let (expected,): (f64,) = sqlx::query_as(
+        "select sum(energy) from ts_energy(array[$1], $2, $3, 'rectangle', interval '15 minutes')",
+    )
+    .bind(meta_id)
+    .bind(from)
+    .bind(to)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!((expected - 636.0 / 60.0).abs() < 1e-9, "{}", expected);
+
+    // the complete buckets of the rollup are attributed to their start, which no reading falls on
+    for (rollup, bucket) in [
+        ("1 hour", datetime!(2024-02-06 13:00 UTC)),
+        ("1 day", datetime!(2024-02-06 00:00 UTC)),
+    ] {
+        let rows: Vec<(OffsetDateTime, f64)> = sqlx::query_as(
+            "select series_timestamp, energy from ts_energy_rollup(array[$1], $2, $3, $4::interval)",
+        )
+        .bind(meta_id)
+        .bind(from)
+        .bind(to)
+        .bind(rollup)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert!(
+            rows.iter().any(|(timestamp, _)| *timestamp == bucket),
+            "{}",
+            rollup
+        );
+        let total: f64 = rows.iter().map(|(_, energy)| energy).sum();
+        assert!((total - expected).abs() < 1e-9, "{} {}", rollup, total);
+    }
+}
+
+#[tokio::test]
+async fn test_get_energy_by_identifier_not_a_power_unit() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+
+    let meta = json!({
+        "identifier": identifier,
+        "unit": "EUR/MWh",
+    });
+    let response = client.post("/v1/meta/").json(&meta).send().await;
+    assert!(response.status().is_success());
+    let response = client
+        .get(&format!("/v1/ts/{}/energy/?interval=1day", identifier))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_downsample_timeseries_by_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let start = datetime!(2024-01-29 0:00 UTC);
+    let datapoints: Vec<_> = (0..200)
+        .map(|i| {
+            json!({
+                "timestamp": (start + time::Duration::minutes(15 * i)).format(&time::format_description::well_known::Rfc3339).unwrap(),
+                "value": if i == 123 { 1000 } else { i % 7 },
+                "identifier": identifier
+            })
+        })
+        .collect();
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({ "timeseries": datapoints }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!("/v1/ts/{}/downsample/?points=20", identifier))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 20);
+    assert_eq!(body.datapoints.first().unwrap().timestamp, start);
+    assert!(body.datapoints.iter().any(|d| d.value == 1000.0));
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/downsample/?points=20&method=minmax",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: Timeseries = response.json().await;
+    assert!(body.datapoints.len() <= 20);
+    assert!(body.datapoints.iter().any(|d| d.value == 1000.0));
+
+    let response = client
+        .get(&format!("/v1/ts/{}/downsample/?points=2", identifier))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_stats_by_identifier() {
+    let client = get_client().await;
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let start = datetime!(2024-01-20 0:00 UTC);
+    let datapoints: Vec<_> = (1..=10)
+        .map(|i| {
+            json!({
+                "timestamp": (start + time::Duration::minutes(15 * i)).format(&time::format_description::well_known::Rfc3339).unwrap(),
+                "value": i,
+                "identifier": identifier
+            })
+        })
+        .collect();
+    let response = client
+        .post("/v1/ts/")
+        .json(&json!({ "timeseries": datapoints }))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/stats/?percentiles=0,50,100&bins=3",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: TimeseriesStats = response.json().await;
+    assert_eq!(body.count, 10);
+    assert_eq!(body.min, Some(1.0));
+    assert_eq!(body.max, Some(10.0));
+    assert_eq!(body.mean, Some(5.5));
+    assert_eq!(body.from, Some(start + time::Duration::minutes(15)));
+    let percentiles = body
+        .percentiles
+        .iter()
+        .map(|x| (x.percentile, x.value.unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(percentiles, [(0.0, 1.0), (50.0, 5.5), (100.0, 10.0)]);
+    let histogram = body.histogram.unwrap();
+    let counts = histogram.bins.iter().map(|x| x.count).collect::<Vec<_>>();
+    assert_eq!(counts, [3, 3, 4]);
+    assert_eq!(histogram.bins[1].from, 4.0);
+
+    let response = client
+        .get(&format!(
+            "/v1/ts/{}/stats/?bins=3&bins_from=2&bins_to=8&unit=kW",
+            identifier
+        ))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let body: TimeseriesStats = response.json().await;
+    assert_eq!(body.percentiles.len(), 5);
+    let histogram = body.histogram.unwrap();
+    let counts = histogram.bins.iter().map(|x| x.count).collect::<Vec<_>>();
+    assert_eq!(counts, [2, 2, 3]);
+    assert_eq!((histogram.below, histogram.above), (1, 2));
+
+    let response = client
+        .get(&format!("/v1/ts/{}/stats/?unit=W", identifier))
+        .send()
+        .await;
+    let body: TimeseriesStats = response.json().await;
+    assert_eq!(body.max, Some(10000.0));
+    assert!(body.histogram.is_none());
+
+    let response = client
+        .get(&format!("/v1/ts/{}/stats/?percentiles=50,101", identifier))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client
+        .get(&format!("/v1/ts/{}/stats/?bins=0", identifier))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn test_resample_timeseries_by_identifier_with_ts_filter_from() {
     let client = get_client().await;
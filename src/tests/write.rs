@@ -0,0 +1,105 @@
+use crate::models::{Timeseries, WriteMapping};
+use crate::tests::test_util::{add_meta, get_client, get_random_string};
+use axum::http::StatusCode;
+use serde_json::json;
+use time::macros::datetime;
+
+// mappings are global, so all writes are tested in one test
+#[tokio::test]
+async fn test_write_line_protocol() {
+    let client = get_client().await;
+    let measurement = get_random_string(10);
+    let device = get_random_string(10).to_lowercase();
+    let identifier = get_random_string(10);
+    add_meta(&client, &identifier).await;
+
+    let mappings = json!([{
+        "measurement": measurement,
+        "field": "power",
+        "tags": {"phase": "L1"},
+        "identifier": identifier
+    }, {
+        "measurement": measurement,
+        "identifier": "{device}_{field}",
+        "meta_defaults": {"unit": "W", "carrier": "electricity"}
+    }]);
+    let response = client
+        .put("/v1/write/mappings/")
+        .json(&mappings)
+        .send()
+        .await;
+    assert!(response.status().is_success());
+    let response = client.get("/v1/write/mappings/").send().await;
+    let body: Vec<WriteMapping> = response.json().await;
+    assert_eq!(body.len(), 2);
+    assert_eq!(body[1].meta_defaults.as_ref().unwrap().unit, "W");
+
+    let lines = format!(
+        "{m},phase=L1 power=1.5 1546300800\n\
+         {m},phase=L1 power=2i,state=\"on\" 1546301700\n",
+        m = measurement
+    );
+    let response = client
+        .post("/v1/write?precision=s")
+        .body(lines)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 2);
+    assert_eq!(body.datapoints[0].timestamp, datetime!(2019-01-01 0:00 UTC));
+    assert_eq!(body.datapoints[1].value, 2.0);
+
+    // unknown identifiers are only created on request
+    let lines = format!(
+        "{},device={} voltage=230 1546300800000",
+        measurement, device
+    );
+    let response = client
+        .post("/v1/write?precision=ms")
+        .body(lines.clone())
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(response.text().await.contains("unknown_identifier"));
+    let response = client
+        .post("/v1/write?precision=ms&auto_create_meta=true")
+        .body(lines)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = client
+        .get(&format!("/v1/ts/{}_voltage/", device))
+        .send()
+        .await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.meta.unit, "W");
+    assert_eq!(body.datapoints[0].value, 230.0);
+
+    // without a device tag only the explicit mapping applies
+    let lines = format!(
+        "{m},phase=L1 power=3 1546302600000000000\n{m},phase=L2 power=4 1546302600000000000",
+        m = measurement
+    );
+    let response = client.post("/v1/write").body(lines).send().await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(response.text().await.contains("1 fields were rejected"));
+    let response = client.get(&format!("/v1/ts/{}/", identifier)).send().await;
+    let body: Timeseries = response.json().await;
+    assert_eq!(body.datapoints.len(), 3);
+
+    let response = client
+        .post("/v1/write")
+        .body(format!("{} power", measurement))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .put("/v1/write/mappings/")
+        .json(&json!([]))
+        .send()
+        .await;
+    assert!(response.status().is_success());
+}